@@ -6,7 +6,9 @@ import {
 	serial,
 	text,
 	int,
+	bigint,
 	uniqueIndex,
+	index,
 	binary,
 } from 'drizzle-orm/mysql-core';
 import { createInsertSchema, createSelectSchema } from 'drizzle-zod';
@@ -44,6 +46,7 @@ export const auth = mysqlTable('auth', {
 	failed_login_attempts: int('failed_login_attempts').default(0).notNull(),
 	lockout_until: timestamp('lockout_until').notNull(),
 	two_factor_secret: varchar('two_factor_secret', { length: 255 }).notNull(),
+	two_factor_last_step: bigint('two_factor_last_step', { mode: 'number' }).default(0).notNull(),
 	recovery_codes: text('recovery_codes').notNull(),
 }, (table) => {
 	return {
@@ -126,7 +129,7 @@ export const settings = mysqlTable('settings', {
 	value: varchar('value', {length: 255}).notNull()
 }, (table) => {
 	return {
-		key_idx: uniqueIndex("key_idx").on(table.key),
+		userid_key_idx: uniqueIndex("userid_key_idx").on(table.userid, table.key),
 	};
 });
 
@@ -148,7 +151,11 @@ export const characters = mysqlTable('characters', {
     description: varchar('description', { length: 255 }).notNull(),
 	experience: int('experience').default(0).notNull(),
     reputation: int('reputation').default(0).notNull(),
-	faith: int('faith').default(0).notNull()
+	faith: int('faith').default(0).notNull(),
+	stat_points: int('stat_points').default(0).notNull(),
+	updated_at: timestamp('updated_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
 }, (table) => {
 	return {
 		name_idx: uniqueIndex("name_idx").on(table.name),
@@ -157,6 +164,97 @@ export const characters = mysqlTable('characters', {
 
 
 
+export const items = mysqlTable('items', {
+	id: serial('id').primaryKey().notNull(),
+	ulid: binary('ulid', { length: 16}).unique().notNull(),
+	name: varchar('name', { length: 64 }).unique().notNull(),
+	description: varchar('description', { length: 255 }).default('').notNull(),
+	slot: varchar('slot', { length: 16 }).default('').notNull(),
+	max_stack: int('max_stack').default(1).notNull(),
+	armour: int('armour').default(0).notNull(),
+	agility: int('agility').default(0).notNull(),
+	strength: int('strength').default(0).notNull(),
+	intelligence: int('intelligence').default(0).notNull(),
+	created_at: timestamp('created_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
+});
+
+export const character_items = mysqlTable('character_items', {
+	id: serial('id').primaryKey().notNull(),
+	ulid: binary('ulid', { length: 16}).unique().notNull(),
+	cid: binary('cid', { length: 16}).references(() => characters.cid).notNull(),
+	itemid: binary('itemid', { length: 16}).references(() => items.ulid).notNull(),
+	quantity: int('quantity').default(1).notNull(),
+	slot: varchar('slot', { length: 16 }),
+	created_at: timestamp('created_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
+}, (table) => {
+	return {
+		character_item_slot_idx: uniqueIndex("character_item_slot_idx").on(table.cid, table.slot),
+	};
+});
+
+export const sessions = mysqlTable('sessions', {
+	id: serial('id').primaryKey().notNull(),
+	ulid: binary('ulid', { length: 16}).unique().notNull(),
+	userid: binary("userid", { length: 16}).references(() => users.userid).notNull(),
+	refresh_hash: varchar('refresh_hash', { length: 255 }).notNull(),
+	previous_hash: varchar('previous_hash', { length: 255 }).default('').notNull(),
+	user_agent: varchar('user_agent', { length: 255 }).default('').notNull(),
+	ip: varchar('ip', { length: 64 }).default('').notNull(),
+	revoked: int('revoked').default(0).notNull(),
+	created_at: timestamp('created_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
+	last_used_at: timestamp('last_used_at').notNull(),
+	expires_at: timestamp('expires_at').notNull(),
+}, (table) => {
+	return {
+		refresh_hash_idx: uniqueIndex("refresh_hash_idx").on(table.refresh_hash),
+		previous_hash_idx: index("previous_hash_idx").on(table.previous_hash),
+	};
+});
+
+export const oauth_accounts = mysqlTable('oauth_accounts', {
+	id: serial('id').primaryKey().notNull(),
+	ulid: binary('ulid', { length: 16}).unique().notNull(),
+	userid: binary("userid", { length: 16}).references(() => users.userid).notNull(),
+	provider: varchar('provider', { length: 64 }).notNull(),
+	subject: varchar('subject', { length: 255 }).notNull(),
+	email: varchar('email', { length: 255 }).default('').notNull(),
+	created_at: timestamp('created_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
+	last_login_at: timestamp('last_login_at').notNull(),
+}, (table) => {
+	return {
+		provider_subject_idx: uniqueIndex("provider_subject_idx").on(table.provider, table.subject),
+		oauth_userid_idx: index("oauth_userid_idx").on(table.userid),
+	};
+});
+
+export const audit_log = mysqlTable('audit_log', {
+	id: serial('id').primaryKey().notNull(),
+	ulid: binary('ulid', { length: 16}).unique().notNull(),
+	actor: binary('actor', { length: 16}),
+	event: varchar('event', { length: 64 }).notNull(),
+	outcome: varchar('outcome', { length: 16 }).notNull(),
+	ip: varchar('ip', { length: 64 }).default('').notNull(),
+	user_agent: varchar('user_agent', { length: 255 }).default('').notNull(),
+	detail: varchar('detail', { length: 1024 }).default('').notNull(),
+	created_at: timestamp('created_at', { mode: 'string' })
+		.notNull()
+		.defaultNow(),
+}, (table) => {
+	return {
+		audit_actor_idx: index("audit_actor_idx").on(table.actor, table.created_at),
+		audit_created_idx: index("audit_created_idx").on(table.created_at),
+		audit_event_idx: index("audit_event_idx").on(table.event),
+	};
+});
+
 /**
  *	TODO: Bank
  * 	!		-> IGBC - [H]clickup#200
@@ -1,8 +1,10 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::{
 	extract::{ Extension },
-	routing::{ get, post },
+	handler::Handler,
+	routing::{ get, post, put, delete },
 	Router,
 	middleware,
 };
@@ -11,11 +13,35 @@ use tokio;
 
 use kbve::{
 	db::{ self },
-	authentication::{ graceful },
-	utility::{ cors_service, fallback, global_map_init, root_endpoint },
-	runes::{ GLOBAL },
+	utility::{ cors_service, fallback, root_endpoint },
+	config::{ config_init, config_spawn_reload },
+	utils::{ mail_sender_from_config },
+	audit::{ audit_init },
+	runes::{ TokenRune },
 	entity::{ KbveState },
-	session::{ middleware_jwt },
+	session::{
+		AuthLayer,
+		TokenJWT,
+		middleware_api_key,
+		RequireScope,
+		RateLimit,
+		RateLimitLayer,
+		SCOPE_CHARACTER_READ,
+		SCOPE_CHARACTER_WRITE,
+		SCOPE_CHARACTER_EXPERIENCE,
+		SCOPE_CHARACTER_VITALS,
+		SCOPE_CHARACTER_ITEMS,
+		SCOPE_APIKEY_READ,
+		SCOPE_APIKEY_WRITE,
+		SCOPE_SETTINGS_READ,
+		SCOPE_SETTINGS_WRITE,
+		SCOPE_ADMIN_SHIELDWALL,
+		SCOPE_ADMIN_GLOBALS,
+		SCOPE_ADMIN_AUDIT,
+		SCOPE_ADMIN_CHARACTERS,
+		keyring_refresh,
+		keyring_spawn_rotation,
+	},
 };
 
 use jedi::builder::ValidatorBuilder;
@@ -25,26 +51,145 @@ async fn main() {
 	println!("◈ [LAUNCH] 🚀");
 
 	let pool = db::establish_connection_pool();
+
+	//	`migrate` applies the pending migrations and exits, `--check` only reports them.
+	//	`repair-accounts [--dry-run]` cleans up partial accounts, see `hazardous_repair_partial_accounts`.
+	//	Without any of them the server refuses to start on a schema that is behind.
+	match std::env::args().nth(1).as_deref() {
+		Some("migrate") => {
+			match db::run_pending_migrations(&pool) {
+				Ok(applied) => {
+					println!("Schema -> migrate -> {} applied.", applied.len());
+					for version in applied {
+						println!("  {}", version);
+					}
+					std::process::exit(0);
+				}
+				Err(e) => {
+					eprintln!("Schema -> migrate -> fail -> {}", e);
+					std::process::exit(1);
+				}
+			}
+		}
+		Some("--check") => {
+			match db::pending_migrations(&pool) {
+				Ok(pending) if pending.is_empty() => {
+					println!("Schema -> check -> up to date.");
+					std::process::exit(0);
+				}
+				Ok(pending) => {
+					println!("Schema -> check -> {} pending.", pending.len());
+					for name in pending {
+						println!("  {}", name);
+					}
+					std::process::exit(1);
+				}
+				Err(e) => {
+					eprintln!("Schema -> check -> fail -> {}", e);
+					std::process::exit(1);
+				}
+			}
+		}
+		Some("repair-accounts") => {
+			let dry_run = std::env::args().nth(2).as_deref() == Some("--dry-run");
+
+			match kbve::guild::hazardous_repair_partial_accounts(dry_run, Arc::new(pool)).await {
+				Ok(report) => {
+					let verb = if dry_run { "would" } else { "did" };
+					println!(
+						"Accounts -> repair -> {} remove {}, restore {} profiles, skip {}.",
+						verb,
+						report.removed.len(),
+						report.profiles_restored.len(),
+						report.skipped.len()
+					);
+					for username in report.removed {
+						println!("  removed {}", username);
+					}
+					for username in report.profiles_restored {
+						println!("  profile {}", username);
+					}
+					for (username, reason) in report.skipped {
+						println!("  skipped {} -> {}", username, reason);
+					}
+					std::process::exit(0);
+				}
+				Err(e) => {
+					eprintln!("Accounts -> repair -> fail -> {}", e.code());
+					std::process::exit(1);
+				}
+			}
+		}
+		Some(other) => {
+			eprintln!(
+				"Unknown argument {}, expected `migrate`, `--check` or `repair-accounts`.",
+				other
+			);
+			std::process::exit(2);
+		}
+		None => {}
+	}
+
+	match db::pending_migrations(&pool) {
+		Ok(pending) if pending.is_empty() => println!("Schema -> up to date."),
+		Ok(pending) => {
+			eprintln!(
+				"Schema -> behind -> {} pending, run `rust_api_profile migrate` first.",
+				pending.len()
+			);
+			std::process::exit(1);
+		}
+		Err(e) => {
+			eprintln!("Schema -> fail -> {}", e);
+			std::process::exit(1);
+		}
+	}
+
 	let shared_pool = Arc::new(pool);
 	//let api_session_store = Arc::new(APISessionStore::new());
 
 	let validator_builder = ValidatorBuilder::<String, String>::new();
 	let shared_validator_builder = Arc::new(validator_builder);
 
+	kbve::entity::entity_config_register();
+
+	let config = match config_init(shared_pool.clone()).await {
+		Ok(config) => {
+			config_spawn_reload(shared_pool.clone());
+			println!("Config -> init -> {} keys.", config.values().len());
+			config
+		}
+		Err(e) => {
+			eprintln!("Config -> fail -> {}", e);
+			std::process::exit(1);
+		}
+	};
+
+	//	Without a transport, reset and verification mail would never leave the server.
+	let mailer = match mail_sender_from_config(&config) {
+		Ok(mailer) => mailer,
+		Err(e) => {
+			eprintln!("Mail -> fail -> {}", e);
+			std::process::exit(1);
+		}
+	};
+
 	// Create KbveState
 	let kbve_state = KbveState::new(
 		shared_pool.clone(),
 		shared_validator_builder
-	);
+	).with_mailer(mailer);
 
 	let application_state = Arc::new(kbve_state);
 
-	match global_map_init(shared_pool.clone()).await {
-		Ok(map) => {
-			GLOBAL.set(Arc::new(map)).expect("Failed to initialize GLOBAL");
-			println!("Global Map -> init.");
+	audit_init(shared_pool.clone());
+
+	match keyring_refresh() {
+		Ok(()) => {
+			keyring_spawn_rotation();
+			println!("Key Ring -> init.");
 		}
-		Err(e) => println!("Global Map -> fail -> {}", e),
+		Err(e) => println!("Key Ring -> fail -> {}", e),
 	}
 
 	let corslight = cors_service();
@@ -58,54 +203,332 @@ async fn main() {
 		.route(
 			"/graceful/profile",
 			get(kbve::authentication::graceful_jwt_profile).route_layer(
-				middleware::from_fn_with_state(shared_pool.clone(), graceful)
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
 			)
 		)
 		.route(
 			"/auth/profile",
 			get(kbve::authentication::auth_jwt_profile).route_layer(
-				middleware::from_fn_with_state(shared_pool.clone(), graceful)
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
 			)
 		)
 		.route(
 			"/auth/profile/update",
 			post(kbve::authentication::auth_jwt_update_profile).route_layer(
-				middleware::from_fn_with_state(shared_pool.clone(), graceful)
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
 			)
 		)
 		//	! Character Creation
 		.route(
 			"/auth/character-creation",
-			post(kbve::entity::character_creation_handler).route_layer(
-				middleware::from_fn_with_state(
-					shared_pool.clone(),
-					middleware_jwt
+			post(kbve::entity::character_creation_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(
+					RateLimitLayer::per_user(
+						"character_creation",
+						RateLimit::new(5, 60)
+					)
 				)
-			)
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
 		)
 		//	! Character List
 		.route(
 			"/auth/characters",
-			get(kbve::entity::authorized_character_data_to_json).route_layer(
-				middleware::from_fn_with_state(
-					shared_pool.clone(),
-					middleware_jwt
+			get(kbve::entity::authorized_character_data_to_json)
+				.route_layer(RequireScope(SCOPE_CHARACTER_READ))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid",
+			put(kbve::entity::character_update_handler)
+				.delete(kbve::entity::character_delete_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid/rename",
+			post(kbve::entity::character_rename_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(
+					RateLimitLayer::per_user(
+						"character_rename",
+						RateLimit::new(5, 300)
+					)
+				)
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		//	! Progression
+		.route(
+			"/auth/characters/:cid/stats",
+			post(kbve::entity::stat_allocation_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/apikey/characters/:cid/experience",
+			post(kbve::entity::grant_experience_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_EXPERIENCE))
+				.route_layer(
+					RateLimitLayer::per_api_key("progression", RateLimit::new(600, 60))
+				)
+				.route_layer(
+					middleware::from_fn_with_state(
+						shared_pool.clone(),
+						middleware_api_key
+					)
 				)
+		)
+		//	! Vitals
+		.route(
+			"/auth/characters/:cid/vitals",
+			get(kbve::entity::vitals_get_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_READ))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/apikey/characters/:cid/vitals",
+			post(kbve::entity::vitals_action_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_VITALS))
+				.route_layer(
+					RateLimitLayer::per_api_key("vitals", RateLimit::new(1200, 60))
+				)
+				.route_layer(
+					middleware::from_fn_with_state(
+						shared_pool.clone(),
+						middleware_api_key
+					)
+				)
+		)
+		//	! Inventory
+		.route(
+			"/auth/characters/:cid/items",
+			get(kbve::entity::inventory_list_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_READ))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid/items/:ulid",
+			delete(kbve::entity::inventory_drop_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid/items/:ulid/equip",
+			post(kbve::entity::inventory_equip_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid/items/:ulid/unequip",
+			post(kbve::entity::inventory_unequip_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/characters/:cid/items/:ulid/transfer",
+			post(kbve::entity::inventory_transfer_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/apikey/characters/:cid/items",
+			post(kbve::entity::inventory_grant_handler)
+				.route_layer(RequireScope(SCOPE_CHARACTER_ITEMS))
+				.route_layer(
+					RateLimitLayer::per_api_key("inventory", RateLimit::new(600, 60))
+				)
+				.route_layer(
+					middleware::from_fn_with_state(
+						shared_pool.clone(),
+						middleware_api_key
+					)
+				)
+		)
+		//	! Public Roster
+		.route(
+			"/characters",
+			get(kbve::entity::character_list_handler).route_layer(
+				RateLimitLayer::per_ip("characters", RateLimit::new(60, 60))
 			)
 		)
 		.route(
-			"/shieldwall/:action",
-			get(kbve::authentication::shieldwall_action).route_layer(
-				middleware::from_fn(kbve::authentication::shieldwall)
+			"/characters/classes",
+			get(kbve::entity::character_classes_handler).route_layer(
+				RateLimitLayer::per_ip("character_classes", RateLimit::new(60, 60))
 			)
 		)
+		//	! API Keys
+		.route(
+			"/auth/apikeys",
+			get(
+				kbve::entity::api_key_list_handler.layer(
+					RequireScope(SCOPE_APIKEY_READ)
+				)
+			)
+				.post(
+					kbve::entity::api_key_creation_handler.layer(
+						RequireScope(SCOPE_APIKEY_WRITE)
+					)
+				)
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/apikeys/:ulid",
+			delete(kbve::entity::api_key_revoke_handler)
+				.route_layer(RequireScope(SCOPE_APIKEY_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/apikey/whoami",
+			get(kbve::entity::api_key_whoami_handler)
+				.route_layer(
+					RateLimitLayer::per_api_key("apikey", RateLimit::new(120, 60))
+				)
+				.route_layer(
+					middleware::from_fn_with_state(
+						shared_pool.clone(),
+						middleware_api_key
+					)
+				)
+		)
+		//	! Settings
+		.route(
+			"/auth/settings",
+			get(kbve::entity::settings_list_handler)
+				.route_layer(RequireScope(SCOPE_SETTINGS_READ))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/auth/settings/:key",
+			put(kbve::entity::settings_put_handler)
+				.delete(kbve::entity::settings_delete_handler)
+				.route_layer(RequireScope(SCOPE_SETTINGS_WRITE))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		//	! Admin Globals
+		.route(
+			"/admin/globals",
+			get(kbve::entity::admin_globals_list_handler)
+				.route_layer(RequireScope(SCOPE_ADMIN_GLOBALS))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/admin/globals/:key",
+			get(kbve::entity::admin_globals_get_handler)
+				.put(kbve::entity::admin_globals_put_handler)
+				.delete(kbve::entity::admin_globals_delete_handler)
+				.route_layer(RequireScope(SCOPE_ADMIN_GLOBALS))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		//	! Admin Characters
+		.route(
+			"/admin/users/:userid/character-slots",
+			put(kbve::entity::admin_character_slots_handler)
+				.route_layer(RequireScope(SCOPE_ADMIN_CHARACTERS))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		//	! Audit Log
+		.route(
+			"/admin/audit",
+			get(kbve::audit::admin_audit_query_handler)
+				.route_layer(RequireScope(SCOPE_ADMIN_AUDIT))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
+		.route(
+			"/shieldwall/:action",
+			get(kbve::authentication::shieldwall_action)
+				.route_layer(RequireScope(SCOPE_ADMIN_SHIELDWALL))
+				.route_layer(AuthLayer::<TokenJWT>::new(shared_pool.clone()))
+		)
 
 		.route("/auth/logout", get(kbve::authentication::auth_logout))
+		.route(
+			"/auth/logout/all",
+			post(kbve::authentication::auth_logout_all).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
+		.route(
+			"/auth/refresh",
+			post(kbve::authentication::auth_session_refresh).route_layer(
+				RateLimitLayer::per_ip("auth_refresh", RateLimit::new(30, 60))
+			)
+		)
+		.route(
+			"/auth/sessions",
+			get(kbve::authentication::auth_session_list).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
+		.route(
+			"/auth/sessions/:ulid",
+			delete(kbve::authentication::auth_session_revoke).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
 		.route(
 			"/auth/register",
-			post(kbve::authentication::auth_player_register)
+			post(kbve::authentication::auth_player_register).route_layer(
+				RateLimitLayer::per_ip("auth_register", RateLimit::new(5, 300))
+			)
+		)
+		.route(
+			"/auth/login",
+			post(kbve::authentication::auth_player_login).route_layer(
+				RateLimitLayer::per_ip("auth_login", RateLimit::new(10, 60))
+			)
+		)
+		.route(
+			"/auth/login/mfa",
+			post(kbve::authentication::auth_player_login_mfa).route_layer(
+				RateLimitLayer::per_ip("auth_login_mfa", RateLimit::new(10, 60))
+			)
+		)
+		.route(
+			"/auth/oauth/:provider",
+			get(kbve::entity::oauth_authorize_handler).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone()).optional()
+			)
+		)
+		.route(
+			"/auth/oauth/:provider/callback",
+			get(kbve::entity::oauth_callback_handler)
+		)
+		.route(
+			"/auth/2fa/enrol",
+			post(kbve::authentication::auth_two_factor_enrol).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
+		.route(
+			"/auth/2fa/confirm",
+			post(kbve::authentication::auth_two_factor_confirm).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
+		.route(
+			"/auth/2fa/disable",
+			post(kbve::authentication::auth_two_factor_disable).route_layer(
+				AuthLayer::<TokenRune>::new(shared_pool.clone())
+			)
+		)
+		.route(
+			"/auth/password/reset",
+			post(kbve::authentication::auth_password_reset_request).route_layer(
+				RateLimitLayer::per_ip("auth_password_reset", RateLimit::new(5, 300))
+			)
+		)
+		.route(
+			"/auth/password/reset/confirm",
+			post(kbve::authentication::auth_password_reset_confirm)
+		)
+		.route("/auth/verify", post(kbve::authentication::auth_verify_email))
+		.route(
+			"/auth/verify/resend",
+			post(kbve::authentication::auth_verify_email_resend).route_layer(
+				RateLimitLayer::per_ip("auth_verify_resend", RateLimit::new(5, 300))
+			)
 		)
-		.route("/auth/login", post(kbve::authentication::auth_player_login))
 
 		.layer(Extension(shared_pool.clone()));
 	//.layer(Extension(api_session_store));
@@ -118,6 +541,7 @@ async fn main() {
 		.nest("/api/v1", api_routes)
 		.nest("/api/v2", apipanda_routes)
 		.route("/", get(root_endpoint))
+		.route("/.well-known/jwks.json", get(kbve::session::jwks_endpoint))
 		.layer(Extension(shared_pool.clone()))
 		.layer(Extension(application_state))
 		.layer(corslight)
@@ -126,6 +550,6 @@ async fn main() {
 
 	axum::Server
 		::bind(&"0.0.0.0:3000".parse().unwrap())
-		.serve(app.into_make_service()).await
+		.serve(app.into_make_service_with_connect_info::<SocketAddr>()).await
 		.unwrap();
 }
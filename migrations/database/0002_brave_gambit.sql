@@ -0,0 +1,19 @@
+CREATE TABLE `sessions` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`refresh_hash` varchar(255) NOT NULL,
+	`previous_hash` varchar(255) NOT NULL DEFAULT '',
+	`user_agent` varchar(255) NOT NULL DEFAULT '',
+	`ip` varchar(64) NOT NULL DEFAULT '',
+	`revoked` int NOT NULL DEFAULT 0,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	`last_used_at` timestamp NOT NULL,
+	`expires_at` timestamp NOT NULL,
+	CONSTRAINT `sessions_id` PRIMARY KEY(`id`),
+	CONSTRAINT `sessions_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `refresh_hash_idx` UNIQUE(`refresh_hash`)
+);
+--> statement-breakpoint
+CREATE INDEX `previous_hash_idx` ON `sessions` (`previous_hash`);--> statement-breakpoint
+ALTER TABLE `sessions` ADD CONSTRAINT `sessions_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`) ON DELETE no action ON UPDATE no action;
\ No newline at end of file
@@ -0,0 +1,16 @@
+CREATE TABLE `oauth_accounts` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`provider` varchar(64) NOT NULL,
+	`subject` varchar(255) NOT NULL,
+	`email` varchar(255) NOT NULL DEFAULT '',
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	`last_login_at` timestamp NOT NULL,
+	CONSTRAINT `oauth_accounts_id` PRIMARY KEY(`id`),
+	CONSTRAINT `oauth_accounts_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `provider_subject_idx` UNIQUE(`provider`,`subject`)
+);
+--> statement-breakpoint
+CREATE INDEX `oauth_userid_idx` ON `oauth_accounts` (`userid`);--> statement-breakpoint
+ALTER TABLE `oauth_accounts` ADD CONSTRAINT `oauth_accounts_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`) ON DELETE no action ON UPDATE no action;
\ No newline at end of file
@@ -0,0 +1,3 @@
+DROP INDEX `key_idx` ON `settings`;
+--> statement-breakpoint
+CREATE UNIQUE INDEX `userid_key_idx` ON `settings` (`userid`,`key`);
\ No newline at end of file
@@ -0,0 +1,17 @@
+CREATE TABLE `audit_log` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`actor` binary(16),
+	`event` varchar(64) NOT NULL,
+	`outcome` varchar(16) NOT NULL,
+	`ip` varchar(64) NOT NULL DEFAULT '',
+	`user_agent` varchar(255) NOT NULL DEFAULT '',
+	`detail` varchar(1024) NOT NULL DEFAULT '',
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `audit_log_id` PRIMARY KEY(`id`),
+	CONSTRAINT `audit_log_ulid_unique` UNIQUE(`ulid`)
+);
+--> statement-breakpoint
+CREATE INDEX `audit_actor_idx` ON `audit_log` (`actor`,`created_at`);--> statement-breakpoint
+CREATE INDEX `audit_created_idx` ON `audit_log` (`created_at`);--> statement-breakpoint
+CREATE INDEX `audit_event_idx` ON `audit_log` (`event`);
\ No newline at end of file
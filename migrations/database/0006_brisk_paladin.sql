@@ -0,0 +1 @@
+ALTER TABLE `characters` ADD `stat_points` int NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE `characters` ADD `updated_at` timestamp NOT NULL DEFAULT (now());
\ No newline at end of file
@@ -0,0 +1,32 @@
+CREATE TABLE `items` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`name` varchar(64) NOT NULL,
+	`description` varchar(255) NOT NULL DEFAULT '',
+	`slot` varchar(16) NOT NULL DEFAULT '',
+	`max_stack` int NOT NULL DEFAULT 1,
+	`armour` int NOT NULL DEFAULT 0,
+	`agility` int NOT NULL DEFAULT 0,
+	`strength` int NOT NULL DEFAULT 0,
+	`intelligence` int NOT NULL DEFAULT 0,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `items_id` PRIMARY KEY(`id`),
+	CONSTRAINT `items_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `items_name_unique` UNIQUE(`name`)
+);
+--> statement-breakpoint
+CREATE TABLE `character_items` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`cid` binary(16) NOT NULL,
+	`itemid` binary(16) NOT NULL,
+	`quantity` int NOT NULL DEFAULT 1,
+	`slot` varchar(16),
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `character_items_id` PRIMARY KEY(`id`),
+	CONSTRAINT `character_items_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `character_item_slot_idx` UNIQUE(`cid`,`slot`)
+);
+--> statement-breakpoint
+ALTER TABLE `character_items` ADD CONSTRAINT `character_items_cid_characters_cid_fk` FOREIGN KEY (`cid`) REFERENCES `characters`(`cid`) ON DELETE no action ON UPDATE no action;--> statement-breakpoint
+ALTER TABLE `character_items` ADD CONSTRAINT `character_items_itemid_items_ulid_fk` FOREIGN KEY (`itemid`) REFERENCES `items`(`ulid`) ON DELETE no action ON UPDATE no action;
\ No newline at end of file
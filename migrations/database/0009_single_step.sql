@@ -0,0 +1 @@
+ALTER TABLE `auth` ADD `two_factor_last_step` bigint NOT NULL DEFAULT 0;
\ No newline at end of file
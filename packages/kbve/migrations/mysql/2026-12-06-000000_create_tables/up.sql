@@ -0,0 +1,229 @@
+-- Every table as of drizzle migration 0008_quiet_armory. Tables that already exist are left alone,
+-- so a database created by the drizzle migrations is adopted as is. That only holds for one at
+-- 0008, `db::drizzle_baseline_check` refuses an older drizzle history before this runs.
+
+CREATE TABLE IF NOT EXISTS `users` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`username` varchar(255) NOT NULL,
+	`role` int NOT NULL DEFAULT 0,
+	`reputation` int NOT NULL DEFAULT 0,
+	`exp` int NOT NULL DEFAULT 0,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `users_id` PRIMARY KEY(`id`),
+	CONSTRAINT `users_userid_unique` UNIQUE(`userid`),
+	CONSTRAINT `users_username_unique` UNIQUE(`username`),
+	CONSTRAINT `username_idx` UNIQUE(`username`)
+);
+
+CREATE TABLE IF NOT EXISTS `auth` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`email` varchar(255) NOT NULL,
+	`hash` varchar(255) NOT NULL,
+	`salt` varchar(255) NOT NULL,
+	`password_reset_token` varchar(255) NOT NULL,
+	`password_reset_expiry` timestamp NOT NULL,
+	`verification_token` varchar(255) NOT NULL,
+	`verification_expiry` timestamp NOT NULL,
+	`status` int NOT NULL DEFAULT 0,
+	`last_login_at` timestamp NOT NULL,
+	`failed_login_attempts` int NOT NULL DEFAULT 0,
+	`lockout_until` timestamp NOT NULL,
+	`two_factor_secret` varchar(255) NOT NULL,
+	`recovery_codes` text NOT NULL,
+	CONSTRAINT `auth_id` PRIMARY KEY(`id`),
+	CONSTRAINT `auth_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `auth_email_unique` UNIQUE(`email`),
+	CONSTRAINT `email_idx` UNIQUE(`email`),
+	CONSTRAINT `auth_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `profile` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`name` varchar(255) NOT NULL DEFAULT 'Anon',
+	`bio` varchar(64) NOT NULL DEFAULT '',
+	`unsplash` varchar(64) NOT NULL DEFAULT '',
+	`github` varchar(64) NOT NULL DEFAULT '',
+	`instagram` varchar(64) NOT NULL DEFAULT '',
+	`discord` varchar(64) NOT NULL DEFAULT '',
+	`userid` binary(16) NOT NULL,
+	CONSTRAINT `profile_id` PRIMARY KEY(`id`),
+	CONSTRAINT `profile_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `profile_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `apikey` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`permissions` varchar(255) NOT NULL,
+	`keyhash` varchar(255) NOT NULL,
+	`label` varchar(255) NOT NULL,
+	CONSTRAINT `apikey_id` PRIMARY KEY(`id`),
+	CONSTRAINT `apikey_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `keyhash_idx` UNIQUE(`keyhash`),
+	CONSTRAINT `apikey_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `appwrite` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`appwrite_endpoint` varchar(255) NOT NULL,
+	`appwrite_projectid` varchar(255) NOT NULL,
+	`appwrite_api_key` varchar(255) NOT NULL,
+	`version` varchar(64) NOT NULL,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `appwrite_id` PRIMARY KEY(`id`),
+	CONSTRAINT `appwrite_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `appwrite_api_key_idx` UNIQUE(`appwrite_api_key`),
+	CONSTRAINT `appwrite_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `n8n` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`webhook` varchar(255) NOT NULL,
+	`permissions` varchar(255) NOT NULL,
+	`keyhash` varchar(255) NOT NULL,
+	`label` varchar(255) NOT NULL,
+	CONSTRAINT `n8n_id` PRIMARY KEY(`id`),
+	CONSTRAINT `n8n_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `keyhash_idx` UNIQUE(`keyhash`),
+	CONSTRAINT `n8n_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `sessions` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`refresh_hash` varchar(255) NOT NULL,
+	`previous_hash` varchar(255) NOT NULL DEFAULT '',
+	`user_agent` varchar(255) NOT NULL DEFAULT '',
+	`ip` varchar(64) NOT NULL DEFAULT '',
+	`revoked` int NOT NULL DEFAULT 0,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	`last_used_at` timestamp NOT NULL,
+	`expires_at` timestamp NOT NULL,
+	CONSTRAINT `sessions_id` PRIMARY KEY(`id`),
+	CONSTRAINT `sessions_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `refresh_hash_idx` UNIQUE(`refresh_hash`),
+	INDEX `previous_hash_idx` (`previous_hash`),
+	CONSTRAINT `sessions_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `settings` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`key` varchar(255) NOT NULL,
+	`value` varchar(255) NOT NULL,
+	CONSTRAINT `settings_id` PRIMARY KEY(`id`),
+	CONSTRAINT `settings_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `userid_key_idx` UNIQUE(`userid`,`key`),
+	CONSTRAINT `settings_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `oauth_accounts` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`provider` varchar(64) NOT NULL,
+	`subject` varchar(255) NOT NULL,
+	`email` varchar(255) NOT NULL DEFAULT '',
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	`last_login_at` timestamp NOT NULL,
+	CONSTRAINT `oauth_accounts_id` PRIMARY KEY(`id`),
+	CONSTRAINT `oauth_accounts_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `provider_subject_idx` UNIQUE(`provider`,`subject`),
+	INDEX `oauth_userid_idx` (`userid`),
+	CONSTRAINT `oauth_accounts_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `audit_log` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`actor` binary(16),
+	`event` varchar(64) NOT NULL,
+	`outcome` varchar(16) NOT NULL,
+	`ip` varchar(64) NOT NULL DEFAULT '',
+	`user_agent` varchar(255) NOT NULL DEFAULT '',
+	`detail` varchar(1024) NOT NULL DEFAULT '',
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `audit_log_id` PRIMARY KEY(`id`),
+	CONSTRAINT `audit_log_ulid_unique` UNIQUE(`ulid`),
+	INDEX `audit_actor_idx` (`actor`,`created_at`),
+	INDEX `audit_created_idx` (`created_at`),
+	INDEX `audit_event_idx` (`event`)
+);
+
+CREATE TABLE IF NOT EXISTS `globals` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`key` varchar(255) NOT NULL,
+	`value` varchar(255) NOT NULL,
+	CONSTRAINT `globals_id` PRIMARY KEY(`id`),
+	CONSTRAINT `key_idx` UNIQUE(`key`)
+);
+
+CREATE TABLE IF NOT EXISTS `characters` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`cid` binary(16) NOT NULL,
+	`userid` binary(16) NOT NULL,
+	`hp` int NOT NULL DEFAULT 0,
+	`mp` int NOT NULL DEFAULT 0,
+	`ep` int NOT NULL DEFAULT 0,
+	`health` int NOT NULL DEFAULT 0,
+	`mana` int NOT NULL DEFAULT 0,
+	`energy` int NOT NULL DEFAULT 0,
+	`armour` int NOT NULL DEFAULT 0,
+	`agility` int NOT NULL DEFAULT 0,
+	`strength` int NOT NULL DEFAULT 0,
+	`intelligence` int NOT NULL DEFAULT 0,
+	`name` varchar(255) NOT NULL,
+	`description` varchar(255) NOT NULL,
+	`experience` int NOT NULL DEFAULT 0,
+	`reputation` int NOT NULL DEFAULT 0,
+	`faith` int NOT NULL DEFAULT 0,
+	`stat_points` int NOT NULL DEFAULT 0,
+	`updated_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `characters_id` PRIMARY KEY(`id`),
+	CONSTRAINT `characters_cid_unique` UNIQUE(`cid`),
+	CONSTRAINT `name_idx` UNIQUE(`name`),
+	CONSTRAINT `characters_userid_users_userid_fk` FOREIGN KEY (`userid`) REFERENCES `users`(`userid`)
+);
+
+CREATE TABLE IF NOT EXISTS `items` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`name` varchar(64) NOT NULL,
+	`description` varchar(255) NOT NULL DEFAULT '',
+	`slot` varchar(16) NOT NULL DEFAULT '',
+	`max_stack` int NOT NULL DEFAULT 1,
+	`armour` int NOT NULL DEFAULT 0,
+	`agility` int NOT NULL DEFAULT 0,
+	`strength` int NOT NULL DEFAULT 0,
+	`intelligence` int NOT NULL DEFAULT 0,
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `items_id` PRIMARY KEY(`id`),
+	CONSTRAINT `items_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `items_name_unique` UNIQUE(`name`)
+);
+
+CREATE TABLE IF NOT EXISTS `character_items` (
+	`id` serial AUTO_INCREMENT NOT NULL,
+	`ulid` binary(16) NOT NULL,
+	`cid` binary(16) NOT NULL,
+	`itemid` binary(16) NOT NULL,
+	`quantity` int NOT NULL DEFAULT 1,
+	`slot` varchar(16),
+	`created_at` timestamp NOT NULL DEFAULT (now()),
+	CONSTRAINT `character_items_id` PRIMARY KEY(`id`),
+	CONSTRAINT `character_items_ulid_unique` UNIQUE(`ulid`),
+	CONSTRAINT `character_item_slot_idx` UNIQUE(`cid`,`slot`),
+	CONSTRAINT `character_items_cid_characters_cid_fk` FOREIGN KEY (`cid`) REFERENCES `characters`(`cid`),
+	CONSTRAINT `character_items_itemid_items_ulid_fk` FOREIGN KEY (`itemid`) REFERENCES `items`(`ulid`)
+);
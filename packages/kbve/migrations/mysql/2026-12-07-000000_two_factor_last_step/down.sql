@@ -0,0 +1 @@
+ALTER TABLE `auth` DROP COLUMN `two_factor_last_step`;
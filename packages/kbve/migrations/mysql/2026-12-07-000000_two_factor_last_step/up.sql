@@ -0,0 +1,11 @@
+-- Last TOTP time step accepted for the account, a code is only good once per step.
+-- A database adopted from the drizzle migrations already has it from 0009_single_step.
+SET @kbve_ddl = IF(
+	(SELECT COUNT(*) FROM information_schema.columns
+		WHERE table_schema = DATABASE() AND table_name = 'auth' AND column_name = 'two_factor_last_step') = 0,
+	'ALTER TABLE `auth` ADD COLUMN `two_factor_last_step` bigint NOT NULL DEFAULT 0',
+	'SELECT 1'
+);
+PREPARE kbve_stmt FROM @kbve_ddl;
+EXECUTE kbve_stmt;
+DEALLOCATE PREPARE kbve_stmt;
@@ -0,0 +1,214 @@
+-- Every table as of drizzle migration 0008_quiet_armory, translated for Postgres.
+-- ulid columns are BYTEA holding the 16 raw bytes, timestamps are stored in UTC.
+
+CREATE TABLE IF NOT EXISTS "users" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"userid" BYTEA NOT NULL,
+	"username" VARCHAR(255) NOT NULL,
+	"role" INTEGER NOT NULL DEFAULT 0,
+	"reputation" INTEGER NOT NULL DEFAULT 0,
+	"exp" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "users_userid_unique" UNIQUE("userid"),
+	CONSTRAINT "users_username_unique" UNIQUE("username")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "username_idx" ON "users" ("username");
+
+CREATE TABLE IF NOT EXISTS "auth" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"email" VARCHAR(255) NOT NULL,
+	"hash" VARCHAR(255) NOT NULL,
+	"salt" VARCHAR(255) NOT NULL,
+	"password_reset_token" VARCHAR(255) NOT NULL,
+	"password_reset_expiry" TIMESTAMP NOT NULL,
+	"verification_token" VARCHAR(255) NOT NULL,
+	"verification_expiry" TIMESTAMP NOT NULL,
+	"status" INTEGER NOT NULL DEFAULT 0,
+	"last_login_at" TIMESTAMP NOT NULL,
+	"failed_login_attempts" INTEGER NOT NULL DEFAULT 0,
+	"lockout_until" TIMESTAMP NOT NULL,
+	"two_factor_secret" VARCHAR(255) NOT NULL,
+	"recovery_codes" TEXT NOT NULL,
+	CONSTRAINT "auth_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "auth_email_unique" UNIQUE("email"),
+	CONSTRAINT "auth_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "email_idx" ON "auth" ("email");
+
+CREATE TABLE IF NOT EXISTS "profile" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"name" VARCHAR(255) NOT NULL DEFAULT 'Anon',
+	"bio" VARCHAR(64) NOT NULL DEFAULT '',
+	"unsplash" VARCHAR(64) NOT NULL DEFAULT '',
+	"github" VARCHAR(64) NOT NULL DEFAULT '',
+	"instagram" VARCHAR(64) NOT NULL DEFAULT '',
+	"discord" VARCHAR(64) NOT NULL DEFAULT '',
+	"userid" BYTEA NOT NULL,
+	CONSTRAINT "profile_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "profile_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+
+CREATE TABLE IF NOT EXISTS "apikey" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"permissions" VARCHAR(255) NOT NULL,
+	"keyhash" VARCHAR(255) NOT NULL,
+	"label" VARCHAR(255) NOT NULL,
+	CONSTRAINT "apikey_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "apikey_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "apikey_keyhash_idx" ON "apikey" ("keyhash");
+
+CREATE TABLE IF NOT EXISTS "appwrite" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"appwrite_endpoint" VARCHAR(255) NOT NULL,
+	"appwrite_projectid" VARCHAR(255) NOT NULL,
+	"appwrite_api_key" VARCHAR(255) NOT NULL,
+	"version" VARCHAR(64) NOT NULL,
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "appwrite_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "appwrite_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "appwrite_api_key_idx" ON "appwrite" ("appwrite_api_key");
+
+CREATE TABLE IF NOT EXISTS "n8n" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"webhook" VARCHAR(255) NOT NULL,
+	"permissions" VARCHAR(255) NOT NULL,
+	"keyhash" VARCHAR(255) NOT NULL,
+	"label" VARCHAR(255) NOT NULL,
+	CONSTRAINT "n8n_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "n8n_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "n8n_keyhash_idx" ON "n8n" ("keyhash");
+
+CREATE TABLE IF NOT EXISTS "sessions" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"refresh_hash" VARCHAR(255) NOT NULL,
+	"previous_hash" VARCHAR(255) NOT NULL DEFAULT '',
+	"user_agent" VARCHAR(255) NOT NULL DEFAULT '',
+	"ip" VARCHAR(64) NOT NULL DEFAULT '',
+	"revoked" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	"last_used_at" TIMESTAMP NOT NULL,
+	"expires_at" TIMESTAMP NOT NULL,
+	CONSTRAINT "sessions_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "sessions_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "refresh_hash_idx" ON "sessions" ("refresh_hash");
+CREATE INDEX IF NOT EXISTS "previous_hash_idx" ON "sessions" ("previous_hash");
+
+CREATE TABLE IF NOT EXISTS "settings" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"key" VARCHAR(255) NOT NULL,
+	"value" VARCHAR(255) NOT NULL,
+	CONSTRAINT "settings_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "settings_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "userid_key_idx" ON "settings" ("userid","key");
+
+CREATE TABLE IF NOT EXISTS "oauth_accounts" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"provider" VARCHAR(64) NOT NULL,
+	"subject" VARCHAR(255) NOT NULL,
+	"email" VARCHAR(255) NOT NULL DEFAULT '',
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	"last_login_at" TIMESTAMP NOT NULL,
+	CONSTRAINT "oauth_accounts_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "oauth_accounts_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "provider_subject_idx" ON "oauth_accounts" ("provider","subject");
+CREATE INDEX IF NOT EXISTS "oauth_userid_idx" ON "oauth_accounts" ("userid");
+
+CREATE TABLE IF NOT EXISTS "audit_log" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"actor" BYTEA,
+	"event" VARCHAR(64) NOT NULL,
+	"outcome" VARCHAR(16) NOT NULL,
+	"ip" VARCHAR(64) NOT NULL DEFAULT '',
+	"user_agent" VARCHAR(255) NOT NULL DEFAULT '',
+	"detail" VARCHAR(1024) NOT NULL DEFAULT '',
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "audit_log_ulid_unique" UNIQUE("ulid")
+);
+CREATE INDEX IF NOT EXISTS "audit_actor_idx" ON "audit_log" ("actor","created_at");
+CREATE INDEX IF NOT EXISTS "audit_created_idx" ON "audit_log" ("created_at");
+CREATE INDEX IF NOT EXISTS "audit_event_idx" ON "audit_log" ("event");
+
+CREATE TABLE IF NOT EXISTS "globals" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"key" VARCHAR(255) NOT NULL,
+	"value" VARCHAR(255) NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "key_idx" ON "globals" ("key");
+
+CREATE TABLE IF NOT EXISTS "characters" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"cid" BYTEA NOT NULL,
+	"userid" BYTEA NOT NULL,
+	"hp" INTEGER NOT NULL DEFAULT 0,
+	"mp" INTEGER NOT NULL DEFAULT 0,
+	"ep" INTEGER NOT NULL DEFAULT 0,
+	"health" INTEGER NOT NULL DEFAULT 0,
+	"mana" INTEGER NOT NULL DEFAULT 0,
+	"energy" INTEGER NOT NULL DEFAULT 0,
+	"armour" INTEGER NOT NULL DEFAULT 0,
+	"agility" INTEGER NOT NULL DEFAULT 0,
+	"strength" INTEGER NOT NULL DEFAULT 0,
+	"intelligence" INTEGER NOT NULL DEFAULT 0,
+	"name" VARCHAR(255) NOT NULL,
+	"description" VARCHAR(255) NOT NULL,
+	"experience" INTEGER NOT NULL DEFAULT 0,
+	"reputation" INTEGER NOT NULL DEFAULT 0,
+	"faith" INTEGER NOT NULL DEFAULT 0,
+	"stat_points" INTEGER NOT NULL DEFAULT 0,
+	"updated_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "characters_cid_unique" UNIQUE("cid"),
+	CONSTRAINT "characters_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "name_idx" ON "characters" ("name");
+
+CREATE TABLE IF NOT EXISTS "items" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"name" VARCHAR(64) NOT NULL,
+	"description" VARCHAR(255) NOT NULL DEFAULT '',
+	"slot" VARCHAR(16) NOT NULL DEFAULT '',
+	"max_stack" INTEGER NOT NULL DEFAULT 1,
+	"armour" INTEGER NOT NULL DEFAULT 0,
+	"agility" INTEGER NOT NULL DEFAULT 0,
+	"strength" INTEGER NOT NULL DEFAULT 0,
+	"intelligence" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "items_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "items_name_unique" UNIQUE("name")
+);
+
+CREATE TABLE IF NOT EXISTS "character_items" (
+	"id" BIGSERIAL PRIMARY KEY,
+	"ulid" BYTEA NOT NULL,
+	"cid" BYTEA NOT NULL,
+	"itemid" BYTEA NOT NULL,
+	"quantity" INTEGER NOT NULL DEFAULT 1,
+	"slot" VARCHAR(16),
+	"created_at" TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'utc'),
+	CONSTRAINT "character_items_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "character_items_cid_characters_cid_fk" FOREIGN KEY ("cid") REFERENCES "characters"("cid"),
+	CONSTRAINT "character_items_itemid_items_ulid_fk" FOREIGN KEY ("itemid") REFERENCES "items"("ulid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "character_item_slot_idx" ON "character_items" ("cid","slot");
@@ -0,0 +1,4 @@
+-- The baseline is not reverted. up.sql adopts a database created by the drizzle migrations, so
+-- dropping these tables here would drop users, auth and profile along with them. Reverting only
+-- forgets the migration, running it again leaves the existing tables alone.
+SELECT 1;
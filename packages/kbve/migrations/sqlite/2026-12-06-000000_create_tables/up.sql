@@ -0,0 +1,214 @@
+-- Every table as of drizzle migration 0008_quiet_armory, translated for SQLite.
+-- ulid columns are BLOBs holding the 16 raw bytes, foreign keys need `PRAGMA foreign_keys = ON`.
+
+CREATE TABLE IF NOT EXISTS "users" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"userid" BLOB NOT NULL,
+	"username" TEXT NOT NULL,
+	"role" INTEGER NOT NULL DEFAULT 0,
+	"reputation" INTEGER NOT NULL DEFAULT 0,
+	"exp" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "users_userid_unique" UNIQUE("userid"),
+	CONSTRAINT "users_username_unique" UNIQUE("username")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "username_idx" ON "users" ("username");
+
+CREATE TABLE IF NOT EXISTS "auth" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"email" TEXT NOT NULL,
+	"hash" TEXT NOT NULL,
+	"salt" TEXT NOT NULL,
+	"password_reset_token" TEXT NOT NULL,
+	"password_reset_expiry" TIMESTAMP NOT NULL,
+	"verification_token" TEXT NOT NULL,
+	"verification_expiry" TIMESTAMP NOT NULL,
+	"status" INTEGER NOT NULL DEFAULT 0,
+	"last_login_at" TIMESTAMP NOT NULL,
+	"failed_login_attempts" INTEGER NOT NULL DEFAULT 0,
+	"lockout_until" TIMESTAMP NOT NULL,
+	"two_factor_secret" TEXT NOT NULL,
+	"recovery_codes" TEXT NOT NULL,
+	CONSTRAINT "auth_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "auth_email_unique" UNIQUE("email"),
+	CONSTRAINT "auth_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "email_idx" ON "auth" ("email");
+
+CREATE TABLE IF NOT EXISTS "profile" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"name" TEXT NOT NULL DEFAULT 'Anon',
+	"bio" TEXT NOT NULL DEFAULT '',
+	"unsplash" TEXT NOT NULL DEFAULT '',
+	"github" TEXT NOT NULL DEFAULT '',
+	"instagram" TEXT NOT NULL DEFAULT '',
+	"discord" TEXT NOT NULL DEFAULT '',
+	"userid" BLOB NOT NULL,
+	CONSTRAINT "profile_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "profile_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+
+CREATE TABLE IF NOT EXISTS "apikey" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"permissions" TEXT NOT NULL,
+	"keyhash" TEXT NOT NULL,
+	"label" TEXT NOT NULL,
+	CONSTRAINT "apikey_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "apikey_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "apikey_keyhash_idx" ON "apikey" ("keyhash");
+
+CREATE TABLE IF NOT EXISTS "appwrite" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"appwrite_endpoint" TEXT NOT NULL,
+	"appwrite_projectid" TEXT NOT NULL,
+	"appwrite_api_key" TEXT NOT NULL,
+	"version" TEXT NOT NULL,
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "appwrite_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "appwrite_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "appwrite_api_key_idx" ON "appwrite" ("appwrite_api_key");
+
+CREATE TABLE IF NOT EXISTS "n8n" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"webhook" TEXT NOT NULL,
+	"permissions" TEXT NOT NULL,
+	"keyhash" TEXT NOT NULL,
+	"label" TEXT NOT NULL,
+	CONSTRAINT "n8n_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "n8n_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "n8n_keyhash_idx" ON "n8n" ("keyhash");
+
+CREATE TABLE IF NOT EXISTS "sessions" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"refresh_hash" TEXT NOT NULL,
+	"previous_hash" TEXT NOT NULL DEFAULT '',
+	"user_agent" TEXT NOT NULL DEFAULT '',
+	"ip" TEXT NOT NULL DEFAULT '',
+	"revoked" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	"last_used_at" TIMESTAMP NOT NULL,
+	"expires_at" TIMESTAMP NOT NULL,
+	CONSTRAINT "sessions_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "sessions_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "refresh_hash_idx" ON "sessions" ("refresh_hash");
+CREATE INDEX IF NOT EXISTS "previous_hash_idx" ON "sessions" ("previous_hash");
+
+CREATE TABLE IF NOT EXISTS "settings" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"key" TEXT NOT NULL,
+	"value" TEXT NOT NULL,
+	CONSTRAINT "settings_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "settings_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "userid_key_idx" ON "settings" ("userid","key");
+
+CREATE TABLE IF NOT EXISTS "oauth_accounts" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"provider" TEXT NOT NULL,
+	"subject" TEXT NOT NULL,
+	"email" TEXT NOT NULL DEFAULT '',
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	"last_login_at" TIMESTAMP NOT NULL,
+	CONSTRAINT "oauth_accounts_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "oauth_accounts_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "provider_subject_idx" ON "oauth_accounts" ("provider","subject");
+CREATE INDEX IF NOT EXISTS "oauth_userid_idx" ON "oauth_accounts" ("userid");
+
+CREATE TABLE IF NOT EXISTS "audit_log" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"actor" BLOB,
+	"event" TEXT NOT NULL,
+	"outcome" TEXT NOT NULL,
+	"ip" TEXT NOT NULL DEFAULT '',
+	"user_agent" TEXT NOT NULL DEFAULT '',
+	"detail" TEXT NOT NULL DEFAULT '',
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "audit_log_ulid_unique" UNIQUE("ulid")
+);
+CREATE INDEX IF NOT EXISTS "audit_actor_idx" ON "audit_log" ("actor","created_at");
+CREATE INDEX IF NOT EXISTS "audit_created_idx" ON "audit_log" ("created_at");
+CREATE INDEX IF NOT EXISTS "audit_event_idx" ON "audit_log" ("event");
+
+CREATE TABLE IF NOT EXISTS "globals" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"key" TEXT NOT NULL,
+	"value" TEXT NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "key_idx" ON "globals" ("key");
+
+CREATE TABLE IF NOT EXISTS "characters" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"cid" BLOB NOT NULL,
+	"userid" BLOB NOT NULL,
+	"hp" INTEGER NOT NULL DEFAULT 0,
+	"mp" INTEGER NOT NULL DEFAULT 0,
+	"ep" INTEGER NOT NULL DEFAULT 0,
+	"health" INTEGER NOT NULL DEFAULT 0,
+	"mana" INTEGER NOT NULL DEFAULT 0,
+	"energy" INTEGER NOT NULL DEFAULT 0,
+	"armour" INTEGER NOT NULL DEFAULT 0,
+	"agility" INTEGER NOT NULL DEFAULT 0,
+	"strength" INTEGER NOT NULL DEFAULT 0,
+	"intelligence" INTEGER NOT NULL DEFAULT 0,
+	"name" TEXT NOT NULL,
+	"description" TEXT NOT NULL,
+	"experience" INTEGER NOT NULL DEFAULT 0,
+	"reputation" INTEGER NOT NULL DEFAULT 0,
+	"faith" INTEGER NOT NULL DEFAULT 0,
+	"stat_points" INTEGER NOT NULL DEFAULT 0,
+	"updated_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "characters_cid_unique" UNIQUE("cid"),
+	CONSTRAINT "characters_userid_users_userid_fk" FOREIGN KEY ("userid") REFERENCES "users"("userid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "name_idx" ON "characters" ("name");
+
+CREATE TABLE IF NOT EXISTS "items" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"name" TEXT NOT NULL,
+	"description" TEXT NOT NULL DEFAULT '',
+	"slot" TEXT NOT NULL DEFAULT '',
+	"max_stack" INTEGER NOT NULL DEFAULT 1,
+	"armour" INTEGER NOT NULL DEFAULT 0,
+	"agility" INTEGER NOT NULL DEFAULT 0,
+	"strength" INTEGER NOT NULL DEFAULT 0,
+	"intelligence" INTEGER NOT NULL DEFAULT 0,
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "items_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "items_name_unique" UNIQUE("name")
+);
+
+CREATE TABLE IF NOT EXISTS "character_items" (
+	"id" INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+	"ulid" BLOB NOT NULL,
+	"cid" BLOB NOT NULL,
+	"itemid" BLOB NOT NULL,
+	"quantity" INTEGER NOT NULL DEFAULT 1,
+	"slot" TEXT,
+	"created_at" TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CONSTRAINT "character_items_ulid_unique" UNIQUE("ulid"),
+	CONSTRAINT "character_items_cid_characters_cid_fk" FOREIGN KEY ("cid") REFERENCES "characters"("cid"),
+	CONSTRAINT "character_items_itemid_items_ulid_fk" FOREIGN KEY ("itemid") REFERENCES "items"("ulid")
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "character_item_slot_idx" ON "character_items" ("cid","slot");
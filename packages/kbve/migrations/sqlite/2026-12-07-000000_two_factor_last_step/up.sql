@@ -0,0 +1,2 @@
+-- Last TOTP time step accepted for the account, a code is only good once per step.
+ALTER TABLE "auth" ADD COLUMN "two_factor_last_step" BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,469 @@
+//!         [AUDIT]
+//?         Security relevant events, written to the `audit_log` table in the background.
+
+use std::convert::Infallible;
+use std::net::{ IpAddr, SocketAddr };
+use std::sync::{ Arc, OnceLock };
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use axum::{
+	async_trait,
+	extract::{ ConnectInfo, Extension, FromRequestParts, Query },
+	http::{ header, request::Parts, HeaderMap, StatusCode },
+	response::IntoResponse,
+};
+
+use chrono::{ DateTime, NaiveDateTime, Utc };
+
+use diesel::prelude::*;
+use diesel::insert_into;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+use tokio::sync::mpsc;
+use crate::db::{ Pool, RowId, run_blocking };
+use crate::error::{ KbveError };
+use crate::schema::{ audit_log };
+use crate::models::{ AuditLog };
+use crate::response::{ GenericResponse };
+use crate::session::{ KbveState };
+use crate::utility::{ convert_ulid_bytes_to_string, convert_ulid_string_to_bytes };
+
+use crate::spellbook_generate_ulid_bytes;
+
+/**
+	`audit_record` never waits on the database. Records go into a bounded channel and one
+	background task, started by `audit_init`, writes them out. While a batch is being written
+	new records queue up, the next write takes all of them (up to `AUDIT_BATCH_MAX`) in one
+	insert, so the number of inserts drops as the load grows.
+
+	A full channel drops the record rather than slow the request down, drops are counted in
+	`audit_dropped` and logged.
+**/
+
+pub const AUDIT_CHANNEL_CAPACITY: usize = 4096;
+pub const AUDIT_BATCH_MAX: usize = 256;
+pub const AUDIT_DETAIL_MAX: usize = 1024;
+pub const AUDIT_QUERY_LIMIT_DEFAULT: i64 = 100;
+pub const AUDIT_QUERY_LIMIT_MAX: i64 = 500;
+
+//	?	[Events]
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditEvent {
+	//	`method` is `password`, `mfa` or `oauth:<provider>`.
+	Login {
+		method: String,
+	},
+	LoginFailed {
+		reason: String,
+	},
+	Register {
+		username: String,
+	},
+	ProfileUpdate,
+	Shieldwall {
+		action: String,
+	},
+	CharacterCreate {
+		name: String,
+	},
+	CharacterUpdate {
+		cid: String,
+	},
+	CharacterRename {
+		cid: String,
+		name: String,
+	},
+	CharacterDelete {
+		cid: String,
+	},
+	ExperienceGrant {
+		cid: String,
+		amount: i64,
+		reason: String,
+	},
+	StatAllocate {
+		cid: String,
+		points: i32,
+	},
+	ItemGrant {
+		cid: String,
+		item: String,
+		quantity: i32,
+	},
+	ItemTransfer {
+		from: String,
+		to: String,
+		item: String,
+		quantity: i32,
+	},
+	GlobalUpdate {
+		key: String,
+	},
+	GlobalDelete {
+		key: String,
+	},
+	CharacterSlotsGrant {
+		userid: String,
+		bonus: u32,
+	},
+}
+
+impl AuditEvent {
+	pub fn name(&self) -> &'static str {
+		match self {
+			AuditEvent::Login { .. } => "auth.login",
+			AuditEvent::LoginFailed { .. } => "auth.login_failed",
+			AuditEvent::Register { .. } => "auth.register",
+			AuditEvent::ProfileUpdate => "profile.update",
+			AuditEvent::Shieldwall { .. } => "admin.shieldwall",
+			AuditEvent::CharacterCreate { .. } => "character.create",
+			AuditEvent::CharacterUpdate { .. } => "character.update",
+			AuditEvent::CharacterRename { .. } => "character.rename",
+			AuditEvent::CharacterDelete { .. } => "character.delete",
+			AuditEvent::ExperienceGrant { .. } => "character.experience_grant",
+			AuditEvent::StatAllocate { .. } => "character.stat_allocate",
+			AuditEvent::ItemGrant { .. } => "character.item_grant",
+			AuditEvent::ItemTransfer { .. } => "character.item_transfer",
+			AuditEvent::GlobalUpdate { .. } => "admin.global_update",
+			AuditEvent::GlobalDelete { .. } => "admin.global_delete",
+			AuditEvent::CharacterSlotsGrant { .. } => "admin.character_slots_grant",
+		}
+	}
+
+	pub fn detail(&self) -> serde_json::Value {
+		match self {
+			AuditEvent::Login { method } => json!({"method": method}),
+			AuditEvent::LoginFailed { reason } => json!({"reason": reason}),
+			AuditEvent::Register { username } => json!({"username": username}),
+			AuditEvent::ProfileUpdate => json!({}),
+			AuditEvent::Shieldwall { action } => json!({"action": action}),
+			AuditEvent::CharacterCreate { name } => json!({"name": name}),
+			AuditEvent::CharacterUpdate { cid } | AuditEvent::CharacterDelete { cid } =>
+				json!({"cid": cid}),
+			AuditEvent::CharacterRename { cid, name } => json!({"cid": cid, "name": name}),
+			AuditEvent::ExperienceGrant { cid, amount, reason } =>
+				json!({"cid": cid, "amount": amount, "reason": reason}),
+			AuditEvent::StatAllocate { cid, points } => json!({"cid": cid, "points": points}),
+			AuditEvent::ItemGrant { cid, item, quantity } =>
+				json!({"cid": cid, "item": item, "quantity": quantity}),
+			AuditEvent::ItemTransfer { from, to, item, quantity } =>
+				json!({"from": from, "to": to, "item": item, "quantity": quantity}),
+			AuditEvent::GlobalUpdate { key } | AuditEvent::GlobalDelete { key } =>
+				json!({"key": key}),
+			AuditEvent::CharacterSlotsGrant { userid, bonus } =>
+				json!({"userid": userid, "bonus": bonus}),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+	Success,
+	//	The caller got something wrong, e.g. a bad password.
+	Failure,
+	//	Refused by policy, e.g. a locked or unverified account.
+	Denied,
+}
+
+impl AuditOutcome {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			AuditOutcome::Success => "success",
+			AuditOutcome::Failure => "failure",
+			AuditOutcome::Denied => "denied",
+		}
+	}
+
+	pub fn from_status(status: StatusCode) -> Self {
+		match status {
+			status if status.is_success() || status.is_redirection() => AuditOutcome::Success,
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AuditOutcome::Denied,
+			_ => AuditOutcome::Failure,
+		}
+	}
+}
+
+//	?	[Context]
+
+//	Client address and user agent of the request, the address follows `rate_limit_client_ip`.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+	pub ip: String,
+	pub user_agent: String,
+}
+
+impl AuditContext {
+	pub fn from_parts(headers: &HeaderMap, peer: Option<IpAddr>) -> Self {
+		AuditContext {
+			ip: crate::session
+				::rate_limit_client_ip(headers, peer)
+				.map(|ip| ip.to_string())
+				.unwrap_or_default(),
+			user_agent: headers
+				.get(header::USER_AGENT)
+				.and_then(|value| value.to_str().ok())
+				.map(|value| value.chars().take(255).collect())
+				.unwrap_or_default(),
+		}
+	}
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext where S: Send + Sync {
+	type Rejection = Infallible;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		let peer = parts.extensions
+			.get::<ConnectInfo<SocketAddr>>()
+			.map(|ConnectInfo(addr)| addr.ip());
+
+		Ok(AuditContext::from_parts(&parts.headers, peer))
+	}
+}
+
+//	?	[Writer]
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+struct NewAuditLog {
+	ulid: Vec<u8>,
+	actor: Option<Vec<u8>>,
+	event: String,
+	outcome: String,
+	ip: String,
+	user_agent: String,
+	detail: String,
+	created_at: NaiveDateTime,
+}
+
+static AUDIT_SENDER: OnceLock<mpsc::Sender<NewAuditLog>> = OnceLock::new();
+static AUDIT_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+//	Records lost to a full channel since startup.
+pub fn audit_dropped() -> u64 {
+	AUDIT_DROPPED.load(Ordering::Relaxed)
+}
+
+//	Queues an event, `actor` is the acting user's ulid when there is one.
+pub fn audit_record(
+	event: AuditEvent,
+	outcome: AuditOutcome,
+	actor: Option<&str>,
+	context: &AuditContext
+) {
+	let mut detail = event.detail().to_string();
+	if detail.len() > AUDIT_DETAIL_MAX {
+		detail = "{\"truncated\":true}".to_string();
+	}
+
+	let row = NewAuditLog {
+		ulid: spellbook_generate_ulid_bytes!(),
+		actor: actor.and_then(|actor| convert_ulid_string_to_bytes(actor).ok()),
+		event: event.name().to_string(),
+		outcome: outcome.as_str().to_string(),
+		ip: context.ip.clone(),
+		user_agent: context.user_agent.clone(),
+		detail,
+		created_at: Utc::now().naive_utc(),
+	};
+
+	let Some(sender) = AUDIT_SENDER.get() else {
+		tracing::debug!("[Audit] writer not started, dropped {}", row.event);
+		return;
+	};
+
+	if let Err(mpsc::error::TrySendError::Full(row)) = sender.try_send(row) {
+		let dropped = AUDIT_DROPPED.fetch_add(1, Ordering::Relaxed) + 1;
+		tracing::warn!("[Audit] channel full, dropped {} ({} so far)", row.event, dropped);
+	}
+}
+
+async fn hazardous_blocking_audit_insert(
+	rows: Vec<NewAuditLog>,
+	pool: Arc<Pool>
+) -> Result<usize, KbveError> {
+	run_blocking(pool, move |conn| {
+		insert_into(audit_log::table)
+			.values(&rows)
+			.execute(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Starts the background writer, later calls are ignored.
+pub fn audit_init(pool: Arc<Pool>) {
+	let (sender, mut receiver) = mpsc::channel::<NewAuditLog>(AUDIT_CHANNEL_CAPACITY);
+
+	if AUDIT_SENDER.set(sender).is_err() {
+		return;
+	}
+
+	tokio::spawn(async move {
+		while let Some(row) = receiver.recv().await {
+			let mut batch = vec![row];
+
+			while batch.len() < AUDIT_BATCH_MAX {
+				match receiver.try_recv() {
+					Ok(row) => batch.push(row),
+					Err(_) => {
+						break;
+					}
+				}
+			}
+
+			let count = batch.len();
+
+			if let Err(e) = hazardous_blocking_audit_insert(batch, pool.clone()).await {
+				tracing::warn!("[Audit] failed to write {} records: {}", count, e);
+			}
+		}
+	});
+}
+
+//	?	[Query]
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AuditQuery {
+	//	RFC 3339 bounds, `from` is inclusive and `to` exclusive.
+	pub from: Option<String>,
+	pub to: Option<String>,
+	pub actor: Option<String>,
+	pub event: Option<String>,
+	//	Only entries older than this id, for paging back through the log.
+	pub before: Option<RowId>,
+	pub limit: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+	pub from: Option<NaiveDateTime>,
+	pub to: Option<NaiveDateTime>,
+	pub actor: Option<Vec<u8>>,
+	pub event: Option<String>,
+	pub before: Option<RowId>,
+	pub limit: i64,
+}
+
+fn audit_parse_time(value: &str) -> Result<NaiveDateTime, &'static str> {
+	DateTime::parse_from_rfc3339(value.trim())
+		.map(|time| time.naive_utc())
+		.map_err(|_| "invalid_time")
+}
+
+impl AuditQuery {
+	pub fn filter(&self) -> Result<AuditFilter, &'static str> {
+		Ok(AuditFilter {
+			from: self.from.as_deref().map(audit_parse_time).transpose()?,
+			to: self.to.as_deref().map(audit_parse_time).transpose()?,
+			actor: self.actor
+				.as_deref()
+				.map(|actor| convert_ulid_string_to_bytes(actor.trim()).map_err(|_| "invalid_ulid"))
+				.transpose()?,
+			event: self.event.as_deref().map(|event| event.trim().to_lowercase()),
+			before: self.before,
+			limit: self.limit
+				.unwrap_or(AUDIT_QUERY_LIMIT_DEFAULT)
+				.clamp(1, AUDIT_QUERY_LIMIT_MAX),
+		})
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+	pub id: RowId,
+	pub ulid: String,
+	pub actor: Option<String>,
+	pub event: String,
+	pub outcome: String,
+	pub ip: String,
+	pub user_agent: String,
+	pub detail: serde_json::Value,
+	pub created_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+	fn from_row(row: AuditLog) -> Self {
+		AuditEntry {
+			id: row.id,
+			ulid: convert_ulid_bytes_to_string(&row.ulid).unwrap_or_default(),
+			actor: row.actor.and_then(|actor| convert_ulid_bytes_to_string(&actor).ok()),
+			event: row.event,
+			outcome: row.outcome,
+			ip: row.ip,
+			user_agent: row.user_agent,
+			detail: serde_json::from_str(&row.detail).unwrap_or(serde_json::Value::Null),
+			created_at: row.created_at,
+		}
+	}
+}
+
+pub async fn hazardous_blocking_audit_query(
+	filter: AuditFilter,
+	pool: Arc<Pool>
+) -> Result<Vec<AuditLog>, KbveError> {
+	run_blocking(pool, move |conn| {
+		let mut query = audit_log::table.into_boxed();
+
+		if let Some(from) = filter.from {
+			query = query.filter(audit_log::created_at.ge(from));
+		}
+		if let Some(to) = filter.to {
+			query = query.filter(audit_log::created_at.lt(to));
+		}
+		if let Some(actor) = filter.actor {
+			query = query.filter(audit_log::actor.eq(actor));
+		}
+		if let Some(event) = filter.event {
+			query = query.filter(audit_log::event.eq(event));
+		}
+		if let Some(before) = filter.before {
+			query = query.filter(audit_log::id.lt(before));
+		}
+
+		query
+			.order(audit_log::id.desc())
+			.limit(filter.limit)
+			.load::<AuditLog>(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Newest first, `next_before` pages further back when the page was full.
+pub async fn admin_audit_query_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Query(params): Query<AuditQuery>
+) -> impl IntoResponse {
+	let filter = match params.filter() {
+		Ok(filter) => filter,
+		Err(e) => {
+			return GenericResponse::error(
+				json!({}),
+				json!({"error": "Invalid audit query, times are RFC 3339 and actor is a ulid"}),
+				e.to_string(),
+				StatusCode::BAD_REQUEST
+			).into_response();
+		}
+	};
+
+	let limit = filter.limit;
+
+	match hazardous_blocking_audit_query(filter, state.db_pool.clone()).await {
+		Ok(rows) => {
+			let entries: Vec<AuditEntry> = rows.into_iter().map(AuditEntry::from_row).collect();
+			let next_before = match entries.last() {
+				Some(last) if (entries.len() as i64) == limit => Some(last.id),
+				_ => None,
+			};
+
+			GenericResponse::new(
+				json!({"entries": entries, "next_before": next_before}),
+				json!("Audit log retrieved successfully."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
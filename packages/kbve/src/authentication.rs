@@ -4,20 +4,37 @@
 //  ?   [crate]
 
 use crate::models::{ User, Profile };
-use crate::db::Pool;
+use crate::db::{ DbError, Pool, run_blocking };
+use crate::error::{ KbveError };
 use crate::runes::{
 	TokenRune,
-	GLOBAL,
 	WizardResponse,
 	AuthPlayerRegisterSchema,
 	AuthVerificationSchema,
 	UpdateProfileSchema,
 	LoginUserSchema,
+	PasswordResetRequestSchema,
+	PasswordResetConfirmSchema,
+	EmailVerificationSchema,
+	EmailVerificationResendSchema,
+	AuthTwoFactorSchema,
+	TwoFactorCodeSchema,
+	LoginTwoFactorSchema,
+	MfaPendingRune,
+	RefreshTokenSchema,
+	SessionRefreshOutcome,
 };
 
+use crate::entity::session::{
+	KbveState,
+	session_cache_mark_revoked,
+	session_cache_mark_revoked_bytes,
+};
+use crate::utils::MailMessage;
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
 use crate::{
 	spellbook_create_cookie,
-	spellbook_pool,
 	spellbook_username,
 	spellbook_ulid,
 	spellbook_email,
@@ -25,6 +42,7 @@ use crate::{
 	spellbook_complete,
 	spellbook_get_global,
 	spellbook_create_jwt,
+	spellbook_generate_ulid_bytes,
 };
 
 //	?	[Diesel]
@@ -36,7 +54,7 @@ use crate::schema::{ auth, profile, users };
 
 use axum::{
 	async_trait,
-	http::{ StatusCode, Request, header },
+	http::{ StatusCode, Request },
 	extract::{ Extension, Path, State, FromRequest },
 	response::{ IntoResponse, Response },
 	middleware::{ self, Next },
@@ -62,17 +80,28 @@ use serde::{ Serialize, Deserialize };
 
 //  ?   [std]
 use std::sync::{ Arc };
-use std::str::FromStr;
 
-pub async fn auth_logout() -> impl IntoResponse {
-	let cookie = spellbook_create_cookie!("token", "", -1);
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
 
-	let mut headers = axum::http::HeaderMap::new();
+//	Clears both cookies and revokes the session behind the refresh cookie, if there is one.
+pub async fn auth_logout(
+	Extension(pool): Extension<Arc<Pool>>,
+	cookie_jar: axum_extra::extract::cookie::CookieJar
+) -> impl IntoResponse {
+	if let Some(refresh_cookie) = cookie_jar.get(REFRESH_TOKEN_COOKIE) {
+		if
+			let Ok(Some(session)) =
+				crate::guild::hazardous_session_revoke_by_refresh_hash(
+					crate::utility::hash_secure_token(refresh_cookie.value()),
+					pool.clone()
+				).await
+		{
+			session_cache_mark_revoked_bytes(&session);
+		}
+	}
 
-	headers.insert(
-		axum::http::header::SET_COOKIE,
-		cookie.to_string().parse().unwrap()
-	);
+	let headers = auth_session_clear_cookies();
 
 	(
 		StatusCode::OK,
@@ -86,25 +115,49 @@ pub async fn auth_logout() -> impl IntoResponse {
 	)
 }
 
+//	Shared Argon2 path for every stored password hash (register and reset).
+pub fn argon2_hash_password(password: &str) -> Result<String, &'static str> {
+	let salt = SaltString::generate(&mut OsRng);
+
+	Argon2::default()
+		.hash_password(password.as_bytes(), &salt)
+		.map(|hash| hash.to_string())
+		.map_err(|_| "invaild_hash")
+}
+
+//	Hash of a random password with the same parameters as the stored ones, built once.
+static ARGON2_DUMMY_HASH: Lazy<String> = Lazy::new(|| {
+	argon2_hash_password(&crate::utility::generate_secure_token()).unwrap_or_default()
+});
+
+//	Spends the same Argon2 work as a real check, so an unknown email takes as long as a wrong password.
+pub fn argon2_dummy_verify(password: &str) {
+	if let Ok(hash) = PasswordHash::new(&ARGON2_DUMMY_HASH) {
+		let _ = Argon2::default().verify_password(password.as_bytes(), &hash);
+	}
+}
+
 pub async fn auth_player_register(
 	Extension(pool): Extension<Arc<Pool>>,
+	Extension(state): Extension<Arc<KbveState>>,
+	audit: AuditContext,
 	Json(mut body): Json<AuthPlayerRegisterSchema>
 ) -> impl IntoResponse {
-	// Captcha
-	match crate::utils::verify_token_via_hcaptcha(&body.token).await {
-		Ok(success) => {
-			if !success {
-				return (
-					StatusCode::UNPROCESSABLE_ENTITY,
-					"Invalid captcha",
-				).into_response();
-			}
+	// Captcha - the address follows the trusted proxies, see `AuditContext`.
+	match state.captcha.verify(&body.token, Some(&audit.ip)).await {
+		Ok(true) => {}
+		Ok(false) => {
+			audit_record(
+				AuditEvent::Register { username: body.username.clone() },
+				AuditOutcome::Denied,
+				None,
+				&audit
+			);
+			return KbveError::Validation("invalid_captcha").into_response();
 		}
-		Err(_) => {
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				"Captcha verification failed",
-			).into_response();
+		Err(e) => {
+			tracing::error!("[Captcha] verification failed: {}", e);
+			return KbveError::Unavailable("captcha_unavailable").into_response();
 		}
 	}
 
@@ -112,9 +165,6 @@ pub async fn auth_player_register(
 		return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
 	}
 
-	// Get a mutable connection from the pool and pass it along to verify.
-	let mut conn = spellbook_pool!(pool);
-
 	//	[!] Check Email - Check if the player email address exists within the database.
 	match
 		crate::guild::hazardous_boolean_email_exist(
@@ -130,7 +180,7 @@ pub async fn auth_player_register(
 			);
 		}
 		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
+			return e.into_response();
 		}
 	}
 
@@ -149,352 +199,1341 @@ pub async fn auth_player_register(
 			);
 		}
 		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
+			return e.into_response();
+		}
+	}
+
+	//	[START] => Password generation!
+	let hash = match argon2_hash_password(&body.password) {
+		Ok(value) => value,
+		Err(_) => {
+			return spellbook_error!(
+				axum::http::StatusCode::BAD_REQUEST,
+				"invaild_hash"
+			);
+		}
+	};
+
+	//	[&] Create Account - users, auth and profile commit together or not at all.
+	//	The account starts unverified with a fresh verification token.
+	let verification_token = crate::utility::generate_secure_token();
+
+	let ulid = match
+		crate::guild::hazardous_register_account(
+			body.username.clone(),
+			body.email.clone(),
+			hash,
+			crate::utility::hash_secure_token(&verification_token),
+			pool.clone()
+		).await
+	{
+		Ok(value) => value,
+		//	Lost a race against a concurrent registration, same answer as the checks above.
+		Err(KbveError::Conflict(code)) => {
+			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, code);
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	audit_record(
+		AuditEvent::Register { username: body.username.clone() },
+		AuditOutcome::Success,
+		crate::utility::convert_ulid_bytes_to_string(&ulid).ok().as_deref(),
+		&audit
+	);
+
+	//	[#] Verification - Delivery failures are logged, the user can request a resend.
+	auth_send_verification_mail(&state, body.email.clone(), &verification_token).await;
+
+	spellbook_complete!("register-complete")
+}
+
+//	?	[Login]
+
+//	Failed logins allowed before the account is locked, and how long the lock lasts.
+pub const LOGIN_MAX_FAILED_ATTEMPTS: i32 = 5;
+pub const LOGIN_LOCKOUT_MINUTES: i64 = 15;
+
+pub async fn auth_player_login(
+	Extension(pool): Extension<Arc<Pool>>,
+	audit: AuditContext,
+	Json(body): Json<LoginUserSchema>
+) -> impl IntoResponse {
+	let clean_email = match crate::utility::sanitize_email(&body.email) {
+		Ok(email) => email,
+		Err(_) => {
+			return KbveError::Validation("invalid_email").into_response();
+		}
+	};
+
+	match crate::utility::validate_password(&body.password) {
+		Ok(()) => {}
+		Err(_) => {
+			return KbveError::Unauthorized("invalid_password").into_response();
+		}
+	}
+
+	let lookup = run_blocking(pool.clone(), move |conn| {
+		Ok::<_, DbError>(
+			auth::table
+				.inner_join(users::table.on(auth::userid.eq(users::userid)))
+				.filter(auth::email.eq(clean_email))
+				.select((
+					users::username,
+					auth::email,
+					users::userid,
+					auth::hash,
+					auth::failed_login_attempts,
+					auth::lockout_until,
+					auth::status,
+					users::created_at,
+					auth::two_factor_secret,
+				))
+				.first::<AuthVerificationSchema>(conn)
+		)
+	}).await;
+
+	let lookup = match lookup {
+		Ok(lookup) => lookup,
+		Err(e) => {
+			return KbveError::from(e).into_response();
+		}
+	};
+
+	let auth_verification_data = match lookup {
+		Ok(data) => data,
+		Err(diesel::NotFound) => {
+			argon2_dummy_verify(&body.password);
+
+			audit_record(
+				AuditEvent::LoginFailed { reason: "unknown_account".to_string() },
+				AuditOutcome::Failure,
+				None,
+				&audit
+			);
+
+			return KbveError::Unauthorized("auth_error").into_response();
+		}
+		Err(e) => {
+			return KbveError::from(e).into_response();
+		}
+	};
+
+	let db_user_hash_password = auth_verification_data.hash;
+
+
+	let userid_ulid_string = match crate::utility::convert_ulid_bytes_to_string(&auth_verification_data.userid) {
+		Ok(ulid_str) => ulid_str,
+		Err(e) => {
+			// Handle the error, e.g., log it or return an error response
+			return KbveError::Internal("invalid_ulid").into_response();
+		}
+	};
+	
+
+
+	//	[!] Lockout - Refuse any attempt until `lockout_until` has passed, before touching Argon2.
+	if auth_verification_data.lockout_until > chrono::Utc::now().naive_utc() {
+		audit_record(
+			AuditEvent::LoginFailed { reason: "account_locked".to_string() },
+			AuditOutcome::Denied,
+			Some(&userid_ulid_string),
+			&audit
+		);
+		return KbveError::Forbidden("account_locked").into_response();
+	}
+
+	let operational_vaild_password = match
+		PasswordHash::new(&db_user_hash_password)
+	{
+		Ok(process_hash) =>
+			Argon2::default()
+				.verify_password(&body.password.as_bytes(), &process_hash)
+				.map_or(false, |_| true),
+		Err(_) => false,
+	};
+
+	if !operational_vaild_password {
+		audit_record(
+			AuditEvent::LoginFailed { reason: "invalid_password".to_string() },
+			AuditOutcome::Failure,
+			Some(&userid_ulid_string),
+			&audit
+		);
+
+		//	[&] Track the failure, the account is locked once the limit is reached.
+		if
+			let Ok(true) = crate::guild::hazardous_login_failure_from_ulid(
+				auth_verification_data.userid.clone(),
+				LOGIN_MAX_FAILED_ATTEMPTS,
+				LOGIN_LOCKOUT_MINUTES,
+				pool.clone()
+			).await
+		{
+			return KbveError::Forbidden("account_locked").into_response();
+		}
+
+		return KbveError::Unauthorized("invalid_password").into_response();
+	}
+
+	let Some(jwt_secret) = crate::entity::session::keyring_jwt_secret() else {
+		return KbveError::Internal("invalid_jwt").into_response();
+	};
+
+	//	[!] Verification - Unverified accounts are gated by the configured policy.
+	if
+		auth_verification_data.status != AUTH_STATUS_VERIFIED &&
+		!UnverifiedLoginPolicy::from_globals().permits(
+			auth_verification_data.created_at
+		)
+	{
+		audit_record(
+			AuditEvent::LoginFailed { reason: "account_unverified".to_string() },
+			AuditOutcome::Denied,
+			Some(&userid_ulid_string),
+			&audit
+		);
+		return KbveError::Forbidden("account_unverified").into_response();
+	}
+
+	//	[!] Two Factor - The password alone only earns a short-lived mfa_pending token.
+	if two_factor_enabled(&auth_verification_data.two_factor_secret) {
+		return auth_login_mfa_pending(&userid_ulid_string, &jwt_secret);
+	}
+
+	let response = auth_login_complete(
+		pool,
+		auth_verification_data.userid,
+		userid_ulid_string.clone(),
+		auth_verification_data.email,
+		auth_verification_data.username,
+		jwt_secret,
+		SessionClient::from_context(&audit)
+	).await;
+
+	audit_record(
+		AuditEvent::Login { method: "password".to_string() },
+		AuditOutcome::from_status(response.status()),
+		Some(&userid_ulid_string),
+		&audit
+	);
+
+	response
+}
+
+//	Final step of every successful login: reset the failure counter, stamp the login,
+//	then open a session and issue the JWT, the refresh token and their cookies.
+pub(crate) async fn auth_login_complete(
+	pool: Arc<Pool>,
+	clean_user_ulid: Vec<u8>,
+	userid_ulid_string: String,
+	email: String,
+	username: String,
+	jwt_secret: String,
+	client: SessionClient
+) -> Response {
+	//	[&] Reset the failure counter and stamp the login.
+	if
+		let Err(e) = crate::guild::hazardous_login_success_from_ulid(
+			clean_user_ulid.clone(),
+			pool.clone()
+		).await
+	{
+		return e.into_response();
+	}
+
+	let session_ulid = spellbook_generate_ulid_bytes!();
+
+	let session_id = match
+		crate::utility::convert_ulid_bytes_to_string(&session_ulid)
+	{
+		Ok(session_id) => session_id,
+		Err(_) => {
+			return KbveError::Internal("invalid_ulid").into_response();
+		}
+	};
+
+	let refresh_token = crate::utility::generate_secure_token();
+
+	if
+		let Err(e) = crate::guild::hazardous_session_create(
+			session_ulid,
+			clean_user_ulid,
+			crate::utility::hash_secure_token(&refresh_token),
+			client.user_agent,
+			client.ip,
+			REFRESH_TOKEN_DAYS,
+			pool
+		).await
+	{
+		return e.into_response();
+	}
+
+	let jwt_token = match
+		spellbook_create_jwt!(
+			userid_ulid_string,
+			email,
+			username,
+			jwt_secret,
+			ACCESS_TOKEN_HOURS,
+			Some(session_id)
+		)
+	{
+		Ok(token) => token,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	auth_session_response(jwt_token, refresh_token)
+}
+
+//	?	[Sessions]
+
+//	Access tokens stay short lived, the refresh token keeps the session going.
+pub const ACCESS_TOKEN_HOURS: i64 = 2;
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+
+//	Client details recorded on the session so users can tell their devices apart.
+pub struct SessionClient {
+	pub user_agent: String,
+	pub ip: String,
+}
+
+impl SessionClient {
+	//	The address is the one `AuditContext` resolved, a forwarded header only counts when it
+	//	comes from one of the `rate_limit_trusted_proxies`.
+	pub fn from_context(audit: &AuditContext) -> Self {
+		SessionClient {
+			user_agent: crate::utility::sanitize_string_limit(&audit.user_agent),
+			ip: audit.ip.clone(),
+		}
+	}
+}
+
+fn auth_session_response(jwt_token: String, refresh_token: String) -> Response {
+	let cookie = spellbook_create_cookie!(
+		"token",
+		jwt_token.to_owned(),
+		ACCESS_TOKEN_HOURS
+	);
+	let refresh_cookie = spellbook_create_cookie!(
+		REFRESH_TOKEN_COOKIE,
+		refresh_token.to_owned(),
+		REFRESH_TOKEN_DAYS * 24
+	);
+
+	let mut headers = axum::http::HeaderMap::new();
+
+	headers.append(
+		axum::http::header::SET_COOKIE,
+		cookie.to_string().parse().unwrap()
+	);
+	headers.append(
+		axum::http::header::SET_COOKIE,
+		refresh_cookie.to_string().parse().unwrap()
+	);
+
+	(
+		StatusCode::OK,
+		headers,
+		Json(WizardResponse {
+			data: serde_json::json!({"status": "complete"}),
+			message: serde_json::json!({
+				"token": jwt_token,
+				"refresh_token": refresh_token,
+			}),
+		}),
+	).into_response()
+}
+
+fn auth_session_clear_cookies() -> axum::http::HeaderMap {
+	let cookie = spellbook_create_cookie!("token", "", -1);
+	let refresh_cookie = spellbook_create_cookie!(REFRESH_TOKEN_COOKIE, "", -1);
+
+	let mut headers = axum::http::HeaderMap::new();
+
+	headers.append(
+		axum::http::header::SET_COOKIE,
+		cookie.to_string().parse().unwrap()
+	);
+	headers.append(
+		axum::http::header::SET_COOKIE,
+		refresh_cookie.to_string().parse().unwrap()
+	);
+
+	headers
+}
+
+//	Trades a refresh token, from the body or the refresh cookie, for a new access token and a
+//	rotated refresh token. Presenting an already rotated token revokes the whole session.
+pub async fn auth_session_refresh(
+	Extension(pool): Extension<Arc<Pool>>,
+	cookie_jar: axum_extra::extract::cookie::CookieJar,
+	body: Option<Json<RefreshTokenSchema>>
+) -> impl IntoResponse {
+	let refresh_token = match
+		body
+			.and_then(|Json(body)| body.refresh_token)
+			.or_else(|| {
+				cookie_jar
+					.get(REFRESH_TOKEN_COOKIE)
+					.map(|cookie| cookie.value().to_string())
+			})
+	{
+		Some(token) if !token.trim().is_empty() => token.trim().to_string(),
+		_ => {
+			return spellbook_error!(
+				StatusCode::UNAUTHORIZED,
+				"invalid_refresh_token"
+			);
+		}
+	};
+
+	let Some(jwt_secret) = crate::entity::session::keyring_jwt_secret() else {
+		return spellbook_error!(StatusCode::INTERNAL_SERVER_ERROR, "invalid_jwt");
+	};
+
+	let next_refresh_token = crate::utility::generate_secure_token();
+
+	let (session, userid) = match
+		crate::guild::hazardous_session_rotate(
+			crate::utility::hash_secure_token(&refresh_token),
+			crate::utility::hash_secure_token(&next_refresh_token),
+			REFRESH_TOKEN_DAYS,
+			pool.clone()
+		).await
+	{
+		Ok(SessionRefreshOutcome::Rotated { session, userid }) => (session, userid),
+		Ok(SessionRefreshOutcome::Reused { session }) => {
+			session_cache_mark_revoked_bytes(&session);
+			return spellbook_error!(
+				StatusCode::UNAUTHORIZED,
+				"refresh_token_reused"
+			);
+		}
+		Ok(SessionRefreshOutcome::Invalid) => {
+			return spellbook_error!(
+				StatusCode::UNAUTHORIZED,
+				"invalid_refresh_token"
+			);
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let (username, email) = match
+		crate::guild::hazardous_session_identity_by_userid(
+			userid.clone(),
+			pool
+		).await
+	{
+		Ok(identity) => identity,
+		Err(KbveError::NotFound(code)) => {
+			return spellbook_error!(StatusCode::UNAUTHORIZED, code);
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let (session_id, userid_string) = match
+		(
+			crate::utility::convert_ulid_bytes_to_string(&session),
+			crate::utility::convert_ulid_bytes_to_string(&userid),
+		)
+	{
+		(Ok(session_id), Ok(userid_string)) => (session_id, userid_string),
+		_ => {
+			return spellbook_error!(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"invalid_ulid"
+			);
+		}
+	};
+
+	let jwt_token = match
+		spellbook_create_jwt!(
+			userid_string,
+			email,
+			username,
+			jwt_secret,
+			ACCESS_TOKEN_HOURS,
+			Some(session_id)
+		)
+	{
+		Ok(token) => token,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	auth_session_response(jwt_token, next_refresh_token)
+}
+
+//	Lists the live sessions of the user, `current` marks the one the request came from.
+pub async fn auth_session_list(
+	Extension(pool): Extension<Arc<Pool>>,
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>
+) -> impl IntoResponse {
+	let clean_user_ulid = match
+		crate::utility::convert_ulid_string_to_bytes(&privatedata.claims.userid)
+	{
+		Ok(bytes) => bytes,
+		Err(_) => {
+			return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_ulid");
+		}
+	};
+
+	let sessions = match
+		crate::guild::hazardous_session_list_by_userid(clean_user_ulid, pool).await
+	{
+		Ok(sessions) => sessions,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let sessions: Vec<serde_json::Value> = sessions
+		.into_iter()
+		.filter_map(|session| {
+			let ulid = crate::utility
+				::convert_ulid_bytes_to_string(&session.ulid)
+				.ok()?;
+
+			let current = privatedata.claims.jti.as_deref() == Some(ulid.as_str());
+
+			Some(
+				json!({
+					"ulid": ulid,
+					"current": current,
+					"user_agent": session.user_agent,
+					"ip": session.ip,
+					"created_at": session.created_at,
+					"last_used_at": session.last_used_at,
+					"expires_at": session.expires_at,
+				})
+			)
+		})
+		.collect();
+
+	spellbook_complete!(json!({"sessions": sessions}))
+}
+
+pub async fn auth_session_revoke(
+	Extension(pool): Extension<Arc<Pool>>,
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>,
+	Path(session_id): Path<String>
+) -> impl IntoResponse {
+	let (clean_session_ulid, clean_user_ulid) = match
+		(
+			crate::utility::convert_ulid_string_to_bytes(session_id.trim()),
+			crate::utility::convert_ulid_string_to_bytes(
+				&privatedata.claims.userid
+			),
+		)
+	{
+		(Ok(session), Ok(user)) => (session, user),
+		_ => {
+			return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_ulid");
+		}
+	};
+
+	match
+		crate::guild::hazardous_session_revoke_by_ulid(
+			clean_session_ulid,
+			clean_user_ulid,
+			pool
+		).await
+	{
+		Ok(true) => {
+			session_cache_mark_revoked(session_id.trim());
+			spellbook_complete!(json!({"revoked": session_id.trim()}))
+		}
+		Ok(false) => spellbook_error!(StatusCode::NOT_FOUND, "session_not_found"),
+		Err(e) => e.into_response(),
+	}
+}
+
+//	Log out all devices: every session of the user is revoked, including the current one.
+pub async fn auth_logout_all(
+	Extension(pool): Extension<Arc<Pool>>,
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>
+) -> impl IntoResponse {
+	let clean_user_ulid = match
+		crate::utility::convert_ulid_string_to_bytes(&privatedata.claims.userid)
+	{
+		Ok(bytes) => bytes,
+		Err(_) => {
+			return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_ulid");
+		}
+	};
+
+	let revoked = match
+		crate::guild::hazardous_session_revoke_all_by_userid(
+			clean_user_ulid,
+			pool
+		).await
+	{
+		Ok(revoked) => revoked,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	for session in revoked.iter() {
+		session_cache_mark_revoked_bytes(session);
+	}
+
+	(
+		StatusCode::OK,
+		auth_session_clear_cookies(),
+		Json(WizardResponse {
+			data: serde_json::json!({"status": "complete"}),
+			message: serde_json::json!({"revoked": revoked.len()}),
+		}),
+	).into_response()
+}
+
+//	?	[Password Reset]
+
+//	Lifetime of a password reset token.
+pub const PASSWORD_RESET_EXPIRY_MINUTES: i64 = 60;
+
+//	Issues a reset token for the email. The response is identical whether or not the
+//	email is registered, so the endpoint cannot be used to enumerate accounts.
+pub async fn auth_password_reset_request(
+	Extension(state): Extension<Arc<KbveState>>,
+	Json(body): Json<PasswordResetRequestSchema>
+) -> impl IntoResponse {
+	let clean_email = match crate::utility::sanitize_email(&body.email) {
+		Ok(email) => email,
+		Err(e) => {
+			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, e);
+		}
+	};
+
+	let token = crate::utility::generate_secure_token();
+
+	match
+		crate::guild::hazardous_password_reset_store_by_email(
+			clean_email.clone(),
+			crate::utility::hash_secure_token(&token),
+			PASSWORD_RESET_EXPIRY_MINUTES,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) => {
+			let mut body = format!(
+				"A password reset was requested for your account. Your reset token is {} and it expires in {} minutes.",
+				token,
+				PASSWORD_RESET_EXPIRY_MINUTES
+			);
+
+			if
+				let Ok(reset_url) = spellbook_get_global!(
+					"password_reset_url",
+					"missing_password_reset_url"
+				)
+			{
+				body.push_str(&format!("\n\n{}?token={}", reset_url, token));
+			}
+
+			let message = MailMessage {
+				to: clean_email,
+				subject: "Password reset".to_string(),
+				body,
+			};
+
+			if let Err(e) = state.mailer.send(message).await {
+				tracing::error!("[Password Reset] mail delivery failed: {}", e);
+			}
+		}
+		Ok(false) => {}
+		Err(e) => {
+			return e.into_response();
+		}
+	}
+
+	spellbook_complete!("password-reset-requested")
+}
+
+pub async fn auth_password_reset_confirm(
+	Extension(state): Extension<Arc<KbveState>>,
+	Json(body): Json<PasswordResetConfirmSchema>
+) -> impl IntoResponse {
+	let clean_token = body.token.trim();
+
+	if clean_token.len() != 64 || !clean_token.chars().all(|c| c.is_ascii_hexdigit()) {
+		return spellbook_error!(
+			axum::http::StatusCode::BAD_REQUEST,
+			"invalid_token"
+		);
+	}
+
+	if let Err(e) = crate::utility::validate_password(&body.password) {
+		return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, e);
+	}
+
+	let hash = match argon2_hash_password(&body.password) {
+		Ok(value) => value,
+		Err(e) => {
+			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, e);
+		}
+	};
+
+	//	[&] The new password signs every device out, whoever held the old one loses their sessions.
+	match
+		crate::guild::hazardous_password_reset_consume(
+			crate::utility::hash_secure_token(clean_token),
+			hash,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(Some(revoked)) => {
+			for session in revoked.iter() {
+				session_cache_mark_revoked_bytes(session);
+			}
+		}
+		Ok(None) => {
+			return spellbook_error!(
+				axum::http::StatusCode::BAD_REQUEST,
+				"invalid_token"
+			);
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	}
+
+	spellbook_complete!("password-reset-complete")
+}
+
+//	?	[Email Verification]
+
+//	Values of `auth.status`.
+pub const AUTH_STATUS_UNVERIFIED: i32 = 0;
+pub const AUTH_STATUS_VERIFIED: i32 = 1;
+
+//	Lifetime of a verification token and the minimum wait between resends.
+pub const VERIFICATION_EXPIRY_HOURS: i64 = 24;
+pub const VERIFICATION_RESEND_COOLDOWN_MINUTES: i64 = 5;
+
+/**
+	How `auth_player_login` treats accounts that have not verified their email.
+	Read from the `auth_unverified_policy` global:
+	- "allow" (default) lets every account log in.
+	- "grace" lets unverified accounts log in for `auth_unverified_grace_hours` (default 72) after registering.
+	- "deny" refuses unverified accounts outright.
+	Accounts created before verification existed are also unverified, so switch to "deny" with care.
+**/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnverifiedLoginPolicy {
+	Allow,
+	Grace(i64),
+	Deny,
+}
+
+impl UnverifiedLoginPolicy {
+	pub fn from_globals() -> Self {
+		let policy = spellbook_get_global!(
+			"auth_unverified_policy",
+			"missing_auth_unverified_policy"
+		).unwrap_or_default();
+
+		match policy.trim().to_lowercase().as_str() {
+			"deny" => UnverifiedLoginPolicy::Deny,
+			"grace" => {
+				let hours = spellbook_get_global!(
+					"auth_unverified_grace_hours",
+					"missing_auth_unverified_grace_hours"
+				)
+					.ok()
+					.and_then(|value| value.trim().parse::<i64>().ok())
+					.unwrap_or(72);
+				UnverifiedLoginPolicy::Grace(hours)
+			}
+			_ => UnverifiedLoginPolicy::Allow,
+		}
+	}
+
+	pub fn permits(&self, created_at: chrono::NaiveDateTime) -> bool {
+		match self {
+			UnverifiedLoginPolicy::Allow => true,
+			UnverifiedLoginPolicy::Grace(hours) =>
+				created_at + chrono::Duration::hours(*hours) >
+					chrono::Utc::now().naive_utc(),
+			UnverifiedLoginPolicy::Deny => false,
+		}
+	}
+}
+
+async fn auth_send_verification_mail(
+	state: &KbveState,
+	clean_email: String,
+	token: &str
+) {
+	let mut body = format!(
+		"Welcome! Your email verification token is {} and it expires in {} hours.",
+		token,
+		VERIFICATION_EXPIRY_HOURS
+	);
+
+	if
+		let Ok(verify_url) = spellbook_get_global!(
+			"verification_url",
+			"missing_verification_url"
+		)
+	{
+		body.push_str(&format!("\n\n{}?token={}", verify_url, token));
+	}
+
+	let message = MailMessage {
+		to: clean_email,
+		subject: "Verify your email".to_string(),
+		body,
+	};
+
+	if let Err(e) = state.mailer.send(message).await {
+		tracing::error!("[Verification] mail delivery failed: {}", e);
+	}
+}
+
+pub async fn auth_verify_email(
+	Extension(state): Extension<Arc<KbveState>>,
+	Json(body): Json<EmailVerificationSchema>
+) -> impl IntoResponse {
+	let clean_token = body.token.trim();
+
+	if clean_token.len() != 64 || !clean_token.chars().all(|c| c.is_ascii_hexdigit()) {
+		return spellbook_error!(
+			axum::http::StatusCode::BAD_REQUEST,
+			"invalid_token"
+		);
+	}
+
+	match
+		crate::guild::hazardous_verification_confirm(
+			crate::utility::hash_secure_token(clean_token),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) => {}
+		Ok(false) => {
+			return spellbook_error!(
+				axum::http::StatusCode::BAD_REQUEST,
+				"invalid_token"
+			);
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	}
+
+	spellbook_complete!("verification-complete")
+}
+
+//	Reissues the verification token. Like the reset request, the response does not reveal
+//	whether the email exists, is already verified or is still within the resend cooldown.
+pub async fn auth_verify_email_resend(
+	Extension(state): Extension<Arc<KbveState>>,
+	Json(body): Json<EmailVerificationResendSchema>
+) -> impl IntoResponse {
+	let clean_email = match crate::utility::sanitize_email(&body.email) {
+		Ok(email) => email,
+		Err(e) => {
+			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, e);
+		}
+	};
+
+	let token = crate::utility::generate_secure_token();
+
+	match
+		crate::guild::hazardous_verification_reissue_by_email(
+			clean_email.clone(),
+			crate::utility::hash_secure_token(&token),
+			VERIFICATION_RESEND_COOLDOWN_MINUTES,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) => {
+			auth_send_verification_mail(&state, clean_email, &token).await;
+		}
+		Ok(false) => {}
+		Err(e) => {
+			return e.into_response();
+		}
+	}
+
+	spellbook_complete!("verification-resent")
+}
+
+//	?	[Two Factor]
+
+//	`auth.two_factor_secret` holds "0" when 2FA is off, `pending:<secret>` between enrol and
+//	confirm, and the bare base32 secret once enabled. `auth.recovery_codes` holds the
+//	comma separated SHA-256 digests of the unused recovery codes.
+pub const TWO_FACTOR_DISABLED: &str = "0";
+pub const TWO_FACTOR_PENDING_PREFIX: &str = "pending:";
+pub const TWO_FACTOR_RECOVERY_CODE_COUNT: usize = 10;
+
+pub const MFA_PENDING_PURPOSE: &str = "mfa_pending";
+pub const MFA_PENDING_MINUTES: i64 = 5;
+
+//	jti -> exp of the mfa_pending tokens that already completed a login. Entries only have to
+//	outlive the token, a replay after `exp` is refused by the expiry check anyway.
+static MFA_PENDING_CONSUMED: Lazy<DashMap<String, usize>> = Lazy::new(DashMap::new);
+
+//	Once the store grows past this, expired entries are pruned on the next consume.
+const MFA_PENDING_CONSUMED_MAX: usize = 10_000;
+
+//	Burns the jti of an mfa_pending token, false when it was already used.
+pub fn mfa_pending_consume(jti: &str, exp: usize) -> bool {
+	let now = chrono::Utc::now().timestamp() as usize;
+
+	if MFA_PENDING_CONSUMED.len() >= MFA_PENDING_CONSUMED_MAX {
+		MFA_PENDING_CONSUMED.retain(|_, expires| *expires > now);
+	}
+
+	match MFA_PENDING_CONSUMED.entry(jti.to_string()) {
+		dashmap::mapref::entry::Entry::Occupied(_) => false,
+		dashmap::mapref::entry::Entry::Vacant(entry) => {
+			entry.insert(exp);
+			true
+		}
+	}
+}
+
+pub fn two_factor_enabled(secret: &str) -> bool {
+	!secret.is_empty() &&
+		secret != TWO_FACTOR_DISABLED &&
+		!secret.starts_with(TWO_FACTOR_PENDING_PREFIX)
+}
+
+pub enum TwoFactorCheck {
+	//	A TOTP code matched, carries its time step to record as the last one used.
+	Totp(u64),
+	//	A recovery code matched, carries the remaining digests to persist.
+	Recovery(String),
+	Invalid,
+}
+
+//	Accepts either a TOTP code from a step after `last_step` or one of the unused recovery codes.
+pub fn two_factor_check(
+	secret: &str,
+	recovery_codes: &str,
+	last_step: i64,
+	code: &str
+) -> TwoFactorCheck {
+	if let Some(step) = crate::utils::verify_totp_step(secret, code, last_step.max(0) as u64) {
+		return TwoFactorCheck::Totp(step);
+	}
+
+	let digest = crate::utility::hash_secure_token(
+		&crate::utils::normalize_recovery_code(code)
+	);
+
+	let mut codes: Vec<&str> = recovery_codes
+		.split(',')
+		.filter(|c| !c.is_empty() && *c != TWO_FACTOR_DISABLED)
+		.collect();
+
+	match codes.iter().position(|c| *c == digest) {
+		Some(index) => {
+			codes.remove(index);
+			let remaining = if codes.is_empty() {
+				TWO_FACTOR_DISABLED.to_string()
+			} else {
+				codes.join(",")
+			};
+			TwoFactorCheck::Recovery(remaining)
+		}
+		None => TwoFactorCheck::Invalid,
+	}
+}
+
+pub(crate) fn auth_login_mfa_pending(userid_ulid_string: &str, jwt_secret: &str) -> Response {
+	let now = chrono::Utc::now();
+	let exp = now + chrono::Duration::minutes(MFA_PENDING_MINUTES);
+
+	let mfa_token = match
+		crate::entity::session::jwt_encode(
+			&MfaPendingRune {
+				sub: userid_ulid_string.to_string(),
+				purpose: MFA_PENDING_PURPOSE.to_string(),
+				jti: crate::utility::generate_ulid_as_string(),
+				iat: now.timestamp() as usize,
+				exp: exp.timestamp() as usize,
+			},
+			jwt_secret
+		)
+	{
+		Ok(token) => token,
+		Err(_) => {
+			return KbveError::Internal("invalid_jwt").into_response();
+		}
+	};
+
+	(
+		StatusCode::OK,
+		Json(WizardResponse {
+			data: serde_json::json!({"status": MFA_PENDING_PURPOSE}),
+			message: serde_json::json!({"mfa_token": mfa_token}),
+		}),
+	).into_response()
+}
+
+//	Second login step, trades the mfa_pending token and a TOTP or recovery code for the session.
+pub async fn auth_player_login_mfa(
+	Extension(pool): Extension<Arc<Pool>>,
+	audit: AuditContext,
+	Json(body): Json<LoginTwoFactorSchema>
+) -> impl IntoResponse {
+	let Some(jwt_secret) = crate::entity::session::keyring_jwt_secret() else {
+		return KbveError::Unauthorized("invalid_jwt").into_response();
+	};
+
+	let pending = match
+		crate::entity::session::jwt_decode::<MfaPendingRune>(
+			&body.mfa_token,
+			&jwt_secret
+		)
+	{
+		Ok(data) if data.claims.purpose == MFA_PENDING_PURPOSE => data.claims,
+		_ => {
+			return KbveError::Unauthorized("invalid_mfa_token").into_response();
+		}
+	};
+
+	let clean_user_ulid = match
+		crate::utility::convert_ulid_string_to_bytes(&pending.sub)
+	{
+		Ok(bytes) => bytes,
+		Err(_) => {
+			return KbveError::Unauthorized("invalid_ulid").into_response();
+		}
+	};
+
+	let two_factor_data = match
+		crate::guild::hazardous_two_factor_fetch_by_userid(
+			clean_user_ulid.clone(),
+			pool.clone()
+		).await
+	{
+		Ok(data) => data,
+		Err(KbveError::NotFound(_)) => {
+			return KbveError::Unauthorized("auth_error").into_response();
+		}
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if two_factor_data.lockout_until > chrono::Utc::now().naive_utc() {
+		audit_record(
+			AuditEvent::LoginFailed { reason: "account_locked".to_string() },
+			AuditOutcome::Denied,
+			Some(&pending.sub),
+			&audit
+		);
+		return KbveError::Forbidden("account_locked").into_response();
+	}
+
+	if !two_factor_enabled(&two_factor_data.two_factor_secret) {
+		return KbveError::Unauthorized("invalid_mfa_token").into_response();
+	}
+
+	//	The code is burnt in the database, a TOTP step or a recovery code only passes once.
+	let accepted = match
+		two_factor_check(
+			&two_factor_data.two_factor_secret,
+			&two_factor_data.recovery_codes,
+			two_factor_data.two_factor_last_step,
+			&body.code
+		)
+	{
+		TwoFactorCheck::Totp(step) =>
+			crate::guild::hazardous_two_factor_step_advance(
+				clean_user_ulid.clone(),
+				step as i64,
+				pool.clone()
+			).await,
+		TwoFactorCheck::Recovery(remaining) =>
+			crate::guild::hazardous_two_factor_update_by_userid(
+				clean_user_ulid.clone(),
+				two_factor_data.recovery_codes.clone(),
+				two_factor_data.two_factor_secret.clone(),
+				remaining,
+				pool.clone()
+			).await,
+		TwoFactorCheck::Invalid => Ok(false),
+	};
+
+	let accepted = match accepted {
+		Ok(accepted) => accepted,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if !accepted {
+		audit_record(
+			AuditEvent::LoginFailed { reason: "invalid_mfa_code".to_string() },
+			AuditOutcome::Failure,
+			Some(&pending.sub),
+			&audit
+		);
+
+		if
+			let Ok(true) = crate::guild::hazardous_login_failure_from_ulid(
+				clean_user_ulid,
+				LOGIN_MAX_FAILED_ATTEMPTS,
+				LOGIN_LOCKOUT_MINUTES,
+				pool.clone()
+			).await
+		{
+			return KbveError::Forbidden("account_locked").into_response();
+		}
+		return KbveError::Unauthorized("invalid_mfa_code").into_response();
+	}
+
+	if !mfa_pending_consume(&pending.jti, pending.exp) {
+		return KbveError::Unauthorized("invalid_mfa_token").into_response();
+	}
+
+	let response = auth_login_complete(
+		pool,
+		clean_user_ulid,
+		pending.sub.clone(),
+		two_factor_data.email,
+		two_factor_data.username,
+		jwt_secret,
+		SessionClient::from_context(&audit)
+	).await;
+
+	audit_record(
+		AuditEvent::Login { method: "mfa".to_string() },
+		AuditOutcome::from_status(response.status()),
+		Some(&pending.sub),
+		&audit
+	);
+
+	response
+}
+
+//	Resolves the auth row behind the JWT for the 2FA management endpoints.
+async fn auth_two_factor_context(
+	pool: &Arc<Pool>,
+	privatedata: &jsonwebtoken::TokenData<TokenRune>
+) -> Result<(Vec<u8>, AuthTwoFactorSchema), Response> {
+	let clean_ulid_string = match
+		crate::utility::sanitizie_ulid(&privatedata.claims.userid)
+	{
+		Ok(ulid) => ulid,
+		Err(e) => {
+			return Err(spellbook_error!(StatusCode::UNAUTHORIZED, e));
 		}
-	}
-
-	//	[START] => Password generation!
-	let salt = SaltString::generate(&mut rand_core::OsRng);
+	};
 
-	let hash = match
-		Argon2::default().hash_password(body.password.as_bytes(), &salt)
+	let clean_user_ulid = match
+		crate::utility::convert_ulid_string_to_bytes(clean_ulid_string)
 	{
-		Ok(value) => value,
+		Ok(bytes) => bytes,
 		Err(_) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"invaild_hash"
-			);
+			return Err(spellbook_error!(StatusCode::BAD_REQUEST, "invalid_ulid"));
 		}
 	};
 
-	//	[&] Create User
 	match
-		crate::guild::hazardous_create_user(
-			body.username.clone(),
+		crate::guild::hazardous_two_factor_fetch_by_userid(
+			clean_user_ulid.clone(),
 			pool.clone()
 		).await
 	{
-		Ok(true) => {}
-		Ok(false) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"process-user-failed"
-			);
-		}
-		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
-		}
+		Ok(data) => Ok((clean_user_ulid, data)),
+		Err(KbveError::NotFound(code)) => Err(spellbook_error!(StatusCode::UNAUTHORIZED, code)),
+		Err(e) => Err(e.into_response()),
 	}
+}
 
-	//	[#]	Obtain UUID
-	//	!	Migration to ULID
-	let ulid = match
-		crate::guild::task_fetch_userid_by_username(
-			body.username.clone(),
-			pool.clone()
-		).await
+//	Starts enrolment with a fresh secret, 2FA stays off until the first code is confirmed.
+pub async fn auth_two_factor_enrol(
+	Extension(pool): Extension<Arc<Pool>>,
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>
+) -> impl IntoResponse {
+	let (clean_user_ulid, two_factor_data) = match
+		auth_two_factor_context(&pool, &privatedata).await
 	{
-		Ok(value) => value,
-		Err(_) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"process-ulid-failed"
-			);
+		Ok(context) => context,
+		Err(response) => {
+			return response;
 		}
 	};
 
-	//	[&] Create Auth
+	if two_factor_enabled(&two_factor_data.two_factor_secret) {
+		return spellbook_error!(StatusCode::BAD_REQUEST, "two_factor_enabled");
+	}
+
+	let secret = crate::utils::generate_totp_secret();
+
 	match
-		crate::guild::hazardous_create_auth_from_ulid(
-			hash.clone().to_string(),
-			body.email.clone(),
-			ulid.clone(),
+		crate::guild::hazardous_two_factor_update_by_userid(
+			clean_user_ulid,
+			two_factor_data.recovery_codes.clone(),
+			format!("{}{}", TWO_FACTOR_PENDING_PREFIX, secret),
+			two_factor_data.recovery_codes,
 			pool.clone()
 		).await
 	{
 		Ok(true) => {}
 		Ok(false) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"process-auth-failed"
-			);
+			return spellbook_error!(StatusCode::CONFLICT, "two_factor_conflict");
 		}
 		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
+			return e.into_response();
 		}
 	}
 
-	//	[&]	Create Profile
+	let issuer = spellbook_get_global!(
+		"two_factor_issuer",
+		"missing_two_factor_issuer"
+	).unwrap_or_else(|_| "KBVE".to_string());
+
+	let uri = crate::utils::totp_otpauth_uri(
+		&issuer,
+		&two_factor_data.email,
+		&secret
+	);
+
+	spellbook_complete!(json!({"secret": secret, "otpauth_uri": uri}))
+}
+
+//	Enables 2FA once a code from the pending secret checks out, and returns the recovery codes.
+//	This is the only time the plain recovery codes are ever shown.
+pub async fn auth_two_factor_confirm(
+	Extension(pool): Extension<Arc<Pool>>,
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>,
+	Json(body): Json<TwoFactorCodeSchema>
+) -> impl IntoResponse {
+	let (clean_user_ulid, two_factor_data) = match
+		auth_two_factor_context(&pool, &privatedata).await
+	{
+		Ok(context) => context,
+		Err(response) => {
+			return response;
+		}
+	};
+
+	let secret = match
+		two_factor_data.two_factor_secret.strip_prefix(TWO_FACTOR_PENDING_PREFIX)
+	{
+		Some(secret) => secret.to_string(),
+		None => {
+			return spellbook_error!(StatusCode::BAD_REQUEST, "two_factor_not_pending");
+		}
+	};
+
+	let Some(step) = crate::utils::verify_totp_step(&secret, &body.code, 0) else {
+		return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_mfa_code");
+	};
+
+	let recovery_codes = crate::utils::generate_recovery_codes(
+		TWO_FACTOR_RECOVERY_CODE_COUNT
+	);
+
+	let recovery_digests = recovery_codes
+		.iter()
+		.map(|code| crate::utility::hash_secure_token(code))
+		.collect::<Vec<String>>()
+		.join(",");
+
 	match
-		crate::guild::hazardous_create_profile_from_ulid(
-			body.username.clone(),
-			ulid.clone(),
+		crate::guild::hazardous_two_factor_update_by_userid(
+			clean_user_ulid.clone(),
+			two_factor_data.recovery_codes,
+			secret,
+			recovery_digests,
 			pool.clone()
 		).await
 	{
 		Ok(true) => {}
 		Ok(false) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"process-profile-failed"
-			);
+			return spellbook_error!(StatusCode::CONFLICT, "two_factor_conflict");
 		}
 		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
+			return e.into_response();
 		}
 	}
 
-	//	[#] Check Email - This time we want it to return true because the user should be registered.
-	match
-		crate::guild::hazardous_boolean_email_exist(
-			body.email.clone(),
+	//	The confirming code counts as used, it can not also complete a login.
+	if
+		let Err(e) = crate::guild::hazardous_two_factor_step_advance(
+			clean_user_ulid,
+			step as i64,
 			pool.clone()
 		).await
 	{
-		Ok(true) => {}
-		Ok(false) => {
-			return spellbook_error!(
-				axum::http::StatusCode::BAD_REQUEST,
-				"auth-register-fail"
-			);
-		}
-		Err(e) => {
-			return spellbook_error!(axum::http::StatusCode::BAD_REQUEST, &e);
-		}
+		return e.into_response();
 	}
 
-	spellbook_complete!("register-complete")
+	spellbook_complete!(json!({"recovery_codes": recovery_codes}))
 }
 
-//	?	[Login]
-
-pub async fn auth_player_login(
+pub async fn auth_two_factor_disable(
 	Extension(pool): Extension<Arc<Pool>>,
-	Json(body): Json<LoginUserSchema>
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>,
+	Json(body): Json<TwoFactorCodeSchema>
 ) -> impl IntoResponse {
-	let clean_email = match crate::utility::sanitize_email(&body.email) {
-		Ok(email) => email,
-		Err(error_message) => {
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", error_message))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
-
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": error_message.to_string()}),
-				}),
-			).into_response();
+	let (clean_user_ulid, two_factor_data) = match
+		auth_two_factor_context(&pool, &privatedata).await
+	{
+		Ok(context) => context,
+		Err(response) => {
+			return response;
 		}
 	};
 
-	match crate::utility::validate_password(&body.password) {
-		Ok(()) => {}
-		Err(_) => {
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", "invalid_password"))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
-
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": "invalid_password"}),
-				}),
-			).into_response();
-		}
+	if !two_factor_enabled(&two_factor_data.two_factor_secret) {
+		return spellbook_error!(StatusCode::BAD_REQUEST, "two_factor_disabled");
 	}
 
-	let mut conn = match pool.get() {
-		Ok(conn) => conn,
-		Err(_) => {
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", "invalid_password"))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
-
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": "invalid_password"}),
-				}),
-			).into_response();
-		}
-	};
-
-	let auth_verification_data = match
-		auth::table
-			.inner_join(users::table.on(auth::userid.eq(users::userid)))
-			.filter(auth::email.eq(clean_email))
-			.select((users::username, auth::email, users::userid, auth::hash))
-			.first::<AuthVerificationSchema>(&mut conn)
+	if
+		let TwoFactorCheck::Invalid = two_factor_check(
+			&two_factor_data.two_factor_secret,
+			&two_factor_data.recovery_codes,
+			two_factor_data.two_factor_last_step,
+			&body.code
+		)
 	{
-		Ok(data) => data,
-		Err(_) => {
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", "auth_error"))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
+		return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_mfa_code");
+	}
 
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": "auth_error"}),
-				}),
-			).into_response();
+	//	[&] Dropping the second factor revokes every session, like a password reset.
+	match
+		crate::guild::hazardous_two_factor_disable_by_userid(
+			clean_user_ulid,
+			two_factor_data.recovery_codes,
+			pool.clone()
+		).await
+	{
+		Ok(Some(revoked)) => {
+			for session in revoked.iter() {
+				session_cache_mark_revoked_bytes(session);
+			}
+		}
+		Ok(None) => {
+			return spellbook_error!(StatusCode::CONFLICT, "two_factor_conflict");
 		}
-	};
-
-	let db_user_hash_password = auth_verification_data.hash;
-
-
-	let userid_ulid_string = match crate::utility::convert_ulid_bytes_to_string(&auth_verification_data.userid) {
-		Ok(ulid_str) => ulid_str,
 		Err(e) => {
-			// Handle the error, e.g., log it or return an error response
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", "invalid_ulid"))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
-
-			return (
-				StatusCode::INTERNAL_SERVER_ERROR,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": "invalid_ulid"}),
-				}),
-			).into_response();
+			return e.into_response();
 		}
-	};
-	
-
-
-	let operational_vaild_password = match
-		PasswordHash::new(&db_user_hash_password)
-	{
-		Ok(process_hash) =>
-			Argon2::default()
-				.verify_password(&body.password.as_bytes(), &process_hash)
-				.map_or(false, |_| true),
-		Err(_) => false,
-	};
-
-	if !operational_vaild_password {
-		let mut headers = axum::http::HeaderMap::new();
-
-		let header_name = axum::http::header::HeaderName
-			::from_str("x-kbve")
-			.unwrap();
-		let header_value = axum::http::HeaderValue
-			::from_str(&format!("shield_{}", "invalid_password"))
-			.unwrap();
-
-		headers.insert(header_name, header_value);
-
-		return (
-			StatusCode::INTERNAL_SERVER_ERROR,
-			headers,
-			Json(WizardResponse {
-				data: serde_json::json!({"status": "error"}),
-				message: serde_json::json!({"error": "invalid_password"}),
-			}),
-		).into_response();
 	}
 
-	let jwt_secret = match spellbook_get_global!("jwt_secret", "invalid_jwt") {
-		Ok(secret) => secret,
-		Err(_) => {
-			let mut headers = axum::http::HeaderMap::new();
-
-			let header_name = axum::http::header::HeaderName
-				::from_str("x-kbve")
-				.unwrap();
-			let header_value = axum::http::HeaderValue
-				::from_str(&format!("shield_{}", "invalid_jwt"))
-				.unwrap();
-
-			headers.insert(header_name, header_value);
-
-			return (
-				StatusCode::UNAUTHORIZED,
-				headers,
-				Json(WizardResponse {
-					data: serde_json::json!({"status": "error"}),
-					message: serde_json::json!({"error": "invalid_jwt"}),
-				}),
-			).into_response();
-		}
-	};
-
-	let jwt_token = spellbook_create_jwt!(
-		userid_ulid_string,
-		auth_verification_data.email,
-		auth_verification_data.username,
-		jwt_secret,
-		2
-	);
-
-	let cookie = spellbook_create_cookie!("token", jwt_token.to_owned(), 2);
-
-	let mut headers = axum::http::HeaderMap::new();
-
-	headers.insert(
-		axum::http::header::SET_COOKIE,
-		cookie.to_string().parse().unwrap()
-	);
-
-	(
-		StatusCode::OK,
-		headers,
-		Json(WizardResponse {
-			data: serde_json::json!({"status": "complete"}),
-			message: serde_json::json!({
-	 			"token": jwt_token.to_string()
-		}),
-		}),
-	).into_response()
-
+	spellbook_complete!("two-factor-disabled")
 }
 
 //  ?   [Routes] -> JWTs
@@ -521,7 +1560,7 @@ pub async fn graceful_jwt_profile(
 /**
 	In auth_jwt_profile, the function retrieves a user's profile from the database using sanitized data from the JWT token.
 	It joins user and profile tables, filters by UUID, and selects relevant columns.
-	The function handles different outcomes: if successful, it returns user and profile data; if the user is not found, it returns a "username not found" error; for other errors, it returns a "database error" message.
+	The function handles different outcomes: if successful, it returns user and profile data; if the user is not found, it returns a "username not found" error; other errors go through `KbveError`, so a database failure answers 503 or 500.
 **/
 
 pub async fn auth_jwt_profile(
@@ -530,8 +1569,6 @@ pub async fn auth_jwt_profile(
 	// Extract JWT token data (assuming `jsonwebtoken::TokenData<TokenRune>` is a valid type)
 	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>
 ) -> impl IntoResponse {
-	// Get a mutable connection from the pool
-	let mut conn = spellbook_pool!(pool);
 	// Sanitize and validate the username, ULID, and email from the JWT token data
 	let clean_username = spellbook_username!(&privatedata.claims.username);
 	let clean_ulid_string = spellbook_ulid!(&privatedata.claims.userid);
@@ -541,24 +1578,24 @@ pub async fn auth_jwt_profile(
 		crate::utility::convert_ulid_string_to_bytes(&clean_ulid_string)
 	{
 		Ok(bytes) => bytes,
-		Err(error_message) => {
-			// Handle the error, e.g., return an appropriate response
-			return (
-				StatusCode::BAD_REQUEST,
-				Json(serde_json::json!({ "error": error_message })),
-			).into_response();
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
 		}
 	};
 
 	// Attempt to retrieve the user and their profile from the database
-	match
-		users::table
-			.inner_join(profile::table.on(profile::userid.eq(users::userid)))
-			.filter(users::userid.eq(clean_ulid_bytes))
-			.select((users::all_columns, profile::all_columns))
-			.first::<(User, Profile)>(&mut conn)
-	{
-		Ok((user, profile)) => {
+	let found = run_blocking(pool, move |conn| {
+		Ok::<_, DbError>(
+			users::table
+				.inner_join(profile::table.on(profile::userid.eq(users::userid)))
+				.filter(users::userid.eq(clean_ulid_bytes))
+				.select((users::all_columns, profile::all_columns))
+				.first::<(User, Profile)>(conn)
+		)
+	}).await;
+
+	match found {
+		Ok(Ok((user, profile))) => {
 			// If successful, return a JSON response with user and profile data
 			(
 				StatusCode::OK,
@@ -573,20 +1610,13 @@ pub async fn auth_jwt_profile(
 				),
 			).into_response()
 		}
-		Err(diesel::NotFound) => {
-			// If the user is not found, return an Unauthorized response with an error message
-			return (
-				axum::http::StatusCode::UNAUTHORIZED,
-				axum::Json(serde_json::json!({"error": "username_not_found"})),
-			).into_response();
-		}
-		Err(_) => {
-			// For any other database error, return an Unauthorized response with a generic error message
-			return (
-				axum::http::StatusCode::UNAUTHORIZED,
-				axum::Json(serde_json::json!({"error": "database_error"})),
-			).into_response();
+		Ok(Err(diesel::NotFound)) => {
+			// If the user is not found, the token belongs to an account that is gone
+			KbveError::Unauthorized("username_not_found").into_response()
 		}
+		// Any other failure is on our side, 503 when the pool is down and 500 otherwise
+		Ok(Err(e)) => KbveError::from(e).into_response(),
+		Err(e) => KbveError::from(e).into_response(),
 	}
 }
 
@@ -597,11 +1627,10 @@ pub async fn auth_jwt_update_profile(
 	Extension(pool): Extension<Arc<Pool>>,
 	// Extract JWT token data (assuming `jsonwebtoken::TokenData<TokenRune>` is a valid type)
 	Extension(privatedata): Extension<jsonwebtoken::TokenData<TokenRune>>,
+	audit: AuditContext,
 	// Extract JSON payload into `UpdateProfileSchema` struct
 	Json(mut body): Json<UpdateProfileSchema>
 ) -> impl IntoResponse {
-	// Get a mutable connection from the pool
-	let mut conn = spellbook_pool!(pool);
 	// Sanitize and validate the ULID from the JWT token data
 	let clean_user_ulid_string = spellbook_ulid!(&privatedata.claims.userid);
 
@@ -612,158 +1641,70 @@ pub async fn auth_jwt_update_profile(
 		crate::utility::convert_ulid_string_to_bytes(&clean_user_ulid_string)
 	{
 		Ok(bytes) => bytes,
-		Err(error_message) => {
-			// Handle the error, e.g., return an appropriate response
-			return (
-				StatusCode::BAD_REQUEST,
-				Json(serde_json::json!({ "error": error_message })),
-			).into_response();
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
 		}
 	};
 
 	// Attempt to update the profile in the database
-	match
-		diesel
-			::update(profile::table) // Specify the table to update
-			.filter(profile::userid.eq(clean_ulid_bytes)) // Filter to the specific user's UUID
-			.set(body) // Set the new profile data
-			.execute(&mut conn) // Execute the update query
-	{
-		Ok(_) => {
+	let updated = run_blocking(pool, move |conn| {
+		Ok::<_, DbError>(
+			diesel
+				::update(profile::table) // Specify the table to update
+				.filter(profile::userid.eq(clean_ulid_bytes)) // Filter to the specific user's UUID
+				.set(body) // Set the new profile data
+				.execute(conn) // Execute the update query
+		)
+	}).await;
+
+	match updated {
+		Ok(Ok(_)) => {
+			audit_record(
+				AuditEvent::ProfileUpdate,
+				AuditOutcome::Success,
+				Some(&clean_user_ulid_string),
+				&audit
+			);
+
 			// If the update is successful, return an OK status with a success message
 			(
 				StatusCode::OK,
 				Json(serde_json::json!({"status": "complete"})),
 			).into_response()
 		}
-		Err(diesel::NotFound) => {
+		Ok(Err(diesel::NotFound)) => {
 			// If the record to update is not found, return an Unauthorized status
 			// This could mean the UUID doesn't match any user
-			return (
-				axum::http::StatusCode::UNAUTHORIZED,
-				axum::Json(serde_json::json!({"error": "profile_not_found"})),
-			).into_response();
-		}
-		Err(_) => {
-			// For any other database error, return an Unauthorized status with a generic error message
-			// This branch catches all other kinds of errors that might occur during the update process
-			return (
-				axum::http::StatusCode::UNAUTHORIZED,
-				axum::Json(serde_json::json!({"error": "database_error"})),
-			).into_response();
+			KbveError::Unauthorized("profile_not_found").into_response()
 		}
+		// Any other failure is on our side, 503 when the pool is down and 500 otherwise
+		Ok(Err(e)) => KbveError::from(e).into_response(),
+		Err(e) => KbveError::from(e).into_response(),
 	}
 }
 
 //	!	[END] -> @JWTs
 
 pub async fn graceful<B>(
-	cookie_jar: axum_extra::extract::cookie::CookieJar,
-	State(_data): State<Arc<Pool>>,
+	State(data): State<Arc<Pool>>,
 	mut req: Request<B>,
 	next: axum::middleware::Next<B>
 ) -> impl IntoResponse {
-	let token_result: Result<String, ()> = cookie_jar
-		.get("token")
-		.map(|cookie| cookie.value().to_string())
-		.or_else(|| {
-			req.headers()
-				.get(header::AUTHORIZATION)
-				.and_then(|auth_header| auth_header.to_str().ok())
-				.and_then(|auth_value|
-					auth_value.strip_prefix("Bearer ").map(String::from)
-				)
-		})
-		.ok_or_else(|| ());
-
-	let jwt_secret = match spellbook_get_global!("jwt_secret", "invalid_jwt") {
-		Ok(secret) => secret,
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
-		}
-	};
-
-	let token: &str = match token_result {
-		Ok(ref token_str) => token_str.as_str(),
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
-		}
-	};
-
-	let privatedata = match
-		jsonwebtoken::decode::<TokenRune>(
-			&token,
-			&jsonwebtoken::DecodingKey::from_secret(jwt_secret.as_bytes()),
-			&jsonwebtoken::Validation::default()
-		)
+	match
+		crate::session::auth_authorize_request::<TokenRune, B>(
+			&crate::session::AuthConfig::default(),
+			data,
+			&mut req
+		).await
 	{
-		Ok(privatedata) => { privatedata }
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
-		}
-	};
-
-	req.extensions_mut().insert(privatedata);
-	next.run(req).await.into_response()
+		Ok(()) => next.run(req).await.into_response(),
+		Err(response) => response,
+	}
 }
 
 //	!	[Shield]
 
-pub async fn shieldwall<B>(
-	req: Request<B>,
-	next: axum::middleware::Next<B>
-) -> impl IntoResponse
-	where
-		B: Send // required by `axum::middleware::Next`
-{
-	// Extract the "kbve-shieldwall" header
-	let shieldwall_header_value = req
-		.headers()
-		.get("kbve-shieldwall")
-		.and_then(|value| value.to_str().ok())
-		.map(String::from);
-
-	match shieldwall_header_value {
-		Some(value) => {
-			// Access the GLOBAL store and compare the value
-			let is_valid = if let Some(global_store) = GLOBAL.get() {
-				global_store
-					.get("shieldwall")
-					.map(|expected_value| expected_value.value() == &value)
-					.unwrap_or(false)
-			} else {
-				false // GLOBAL store not initialized
-			};
-
-			if is_valid {
-				// If the header value is valid, proceed with the next middleware or handler
-				next.run(req).await.into_response()
-			} else {
-				// Invalid header value
-				(
-					StatusCode::UNAUTHORIZED,
-					Json(json!({"error": "Invalid shieldwall header value"})),
-				).into_response()
-			}
-		}
-		None => {
-			// Header not found
-			(
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "Shieldwall header missing"})),
-			).into_response()
-		}
-	}
-}
+//	Access to the shieldwall actions is granted through `RequireScope(SCOPE_ADMIN_SHIELDWALL)`.
 
 // Define a struct called `ShieldWallSchema` with Serde's derive macros for serialization and deserialization.
 // This will allow instances of ShieldWallSchema to be easily converted to/from JSON (or other formats).
@@ -811,6 +1752,8 @@ impl ShieldWallSchema {
 	**/
 
 pub async fn shieldwall_action(
+	Extension(privatedata): Extension<jsonwebtoken::TokenData<crate::session::TokenJWT>>,
+	audit: AuditContext,
 	Path(action): Path<String> // Extracts the 'action' parameter from the URL path
 ) -> impl IntoResponse {
 	// Sanitize the extracted action string to prevent injection attacks and limit its length
@@ -818,63 +1761,70 @@ pub async fn shieldwall_action(
 
 	// Create an instance of ShieldWallSchema with the sanitized action
 	let shieldwall_schema = ShieldWallSchema {
-		action: Some(clean_action),
+		action: Some(clean_action.clone()),
 	};
 
 	// Execute the action using the ShieldWallSchema's execute method
-	// Since execute now returns an impl IntoResponse, we can directly await it
-	shieldwall_schema.execute().await
+	let response = shieldwall_schema.execute().await.into_response();
+
+	audit_record(
+		AuditEvent::Shieldwall { action: clean_action },
+		AuditOutcome::from_status(response.status()),
+		Some(&privatedata.claims.userid),
+		&audit
+	);
+
+	response
 }
 
 /**
 
-	It retrieves the URL from the GLOBAL map using a predefined key.
+	It retrieves the URL from the `portainer_stack` key of the `KbveConfig`.
 	If the URL is found, it sends a POST request to that URL using reqwest::Client.
 	The response from the server is then read as a text (assuming it's JSON or a string).
-	If successful, the response text is returned. If there are any errors (like the URL not found in the map, the global map not initialized, failed to make the request, or failed to read the response), appropriate error messages are returned.
-	This function should be run within the context of a Tokio runtime since it is an async function and uses await. Make sure that the GLOBAL map is properly initialized and contains the expected URL before calling this function.
+	If successful, the response text is returned. If there are any errors (like the URL not being configured, the config not initialized, failed to make the request, or failed to read the response), appropriate error messages are returned.
+	This function should be run within the context of a Tokio runtime since it is an async function and uses await. Make sure that `config_init` has run and `portainer_stack` is set before calling this function.
 
 	**/
 
 // The `shieldwall_action_portainer_stack_deploy` function is an asynchronous function designed to
-// interact with a Portainer Stack using a URL retrieved from a global map. It sends a POST request
+// interact with a Portainer Stack using a URL retrieved from the config. It sends a POST request
 // to the Portainer Stack URL and returns the server's response. Error handling is integrated to
-// manage cases where the URL is not found in the global map, the global map is not initialized,
+// manage cases where the URL is not configured, the config is not initialized,
 // or there are issues with the HTTP request or response processing. This function requires a Tokio
 // runtime context as it relies on asynchronous operations.
 
 pub async fn shieldwall_action_portainer_stack_deploy() -> impl IntoResponse {
-	// Define the key for the Portainer Stack URL in the GLOBAL map
-	let portainer_stack_url_from_map = "portainer_stack";
-
-	// Retrieve the URL from the GLOBAL map
-	let url = if let Some(global_map) = GLOBAL.get() {
-		match global_map.get(portainer_stack_url_from_map) {
-			Some(url) => url.value().clone(),
-			None => {
-				return axum::response::Response
-					::builder()
-					.status(StatusCode::INTERNAL_SERVER_ERROR)
-					.body(
-						Json(
-							serde_json::json!({"error": "URL not found in global map"})
+	// Retrieve the URL from the typed config
+	let url = match crate::config::kbve_config() {
+		Some(config) =>
+			match config.portainer_stack.clone() {
+				Some(url) => url,
+				None => {
+					return axum::response::Response
+						::builder()
+						.status(StatusCode::INTERNAL_SERVER_ERROR)
+						.body(
+							Json(
+								serde_json::json!({"error": "portainer_stack is not configured"})
+							)
+								.into_response()
+								.into_body()
 						)
-							.into_response()
-							.into_body()
-					)
-					.unwrap();
+						.unwrap();
+				}
 			}
+		None => {
+			return axum::response::Response
+				::builder()
+				.status(StatusCode::INTERNAL_SERVER_ERROR)
+				.body(
+					Json(serde_json::json!({"error": "config not initialized"}))
+						.into_response()
+						.into_body()
+				)
+				.unwrap();
 		}
-	} else {
-		return axum::response::Response
-			::builder()
-			.status(StatusCode::INTERNAL_SERVER_ERROR)
-			.body(
-				Json(serde_json::json!({"error": "GLOBAL map not initialized"}))
-					.into_response()
-					.into_body()
-			)
-			.unwrap();
 	};
 
 	// Make the POST request to the URL
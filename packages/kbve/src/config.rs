@@ -0,0 +1,675 @@
+//!         [CONFIG]
+//?         Typed configuration merged from the TOML file, the `globals` table and the environment.
+
+use std::collections::{ BTreeMap, BTreeSet };
+use std::fmt;
+use std::sync::{ Arc, OnceLock, RwLock };
+use std::time::Duration;
+
+use dashmap::DashMap;
+use diesel::prelude::*;
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+
+use crate::db::{ DbError, Pool, run_blocking };
+use crate::schema::{ globals };
+
+/**
+	`KbveConfig` is built from three sources, each one overriding the one before:
+
+	1.	an optional TOML file named by `KBVE_CONFIG_PATH`, nested tables are flattened with `_`,
+		so `[oauth.github] client_id = ".."` becomes `oauth_github_client_id`.
+	2.	the `globals` table.
+	3.	`KBVE_<KEY>` environment variables, e.g. `KBVE_JWT_SECRET` for `jwt_secret`, or
+		`KBVE_<KEY>_FILE` naming a file that holds the value, like `DATABASE_URL_PROD_FILE`.
+
+	Every missing or invalid key is reported at once, modules that own keys add their own
+	validation through `config_register_check`. The table is re-read every
+	`config_reload_seconds`, subscribers get the keys that changed, and `runes::GLOBAL` is kept
+	in step so `spellbook_get_global!` keeps reading current values.
+**/
+
+pub const CONFIG_PATH_ENV: &str = "KBVE_CONFIG_PATH";
+pub const CONFIG_ENV_PREFIX: &str = "KBVE_";
+pub const CONFIG_RELOAD_SECONDS_DEFAULT: u64 = 60;
+pub const CONFIG_SUBSCRIBER_CAPACITY: usize = 16;
+
+pub const CONFIG_REQUIRED_KEYS: &[&str] = &["jwt_secret"];
+
+//	?	[Errors]
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigError {
+	pub missing: Vec<String>,
+	pub invalid: Vec<(String, String)>,
+	pub source: Option<String>,
+}
+
+impl ConfigError {
+	fn is_empty(&self) -> bool {
+		self.missing.is_empty() && self.invalid.is_empty() && self.source.is_none()
+	}
+
+	fn source(error: impl Into<String>) -> Self {
+		ConfigError { source: Some(error.into()), ..Default::default() }
+	}
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts: Vec<String> = Vec::new();
+
+		if let Some(source) = &self.source {
+			parts.push(source.clone());
+		}
+		if !self.missing.is_empty() {
+			parts.push(format!("missing keys: {}", self.missing.join(", ")));
+		}
+		for (key, reason) in &self.invalid {
+			parts.push(format!("invalid {}: {}", key, reason));
+		}
+
+		write!(f, "{}", parts.join("; "))
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<DbError> for ConfigError {
+	fn from(error: DbError) -> Self {
+		ConfigError::source(error.to_string())
+	}
+}
+
+//	?	[Checks]
+
+//	Validation owned by another module, returns every invalid `(key, reason)` in the merged values.
+pub type ConfigCheck = fn(&BTreeMap<String, String>) -> Vec<(String, String)>;
+
+static CONFIG_CHECKS: Lazy<RwLock<Vec<(&'static str, ConfigCheck)>>> = Lazy::new(|| {
+	RwLock::new(Vec::new())
+});
+
+//	Runs `check` on every config built afterwards, registering the same name again replaces it.
+//	Register before `config_init` so an invalid value refuses the start.
+pub fn config_register_check(name: &'static str, check: ConfigCheck) {
+	if let Ok(mut checks) = CONFIG_CHECKS.write() {
+		checks.retain(|(known, _)| *known != name);
+		checks.push((name, check));
+	}
+}
+
+//	?	[KbveConfig]
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KbveConfig {
+	pub jwt_secret: String,
+	pub jwt_key_dir: Option<String>,
+	pub jwt_key_rotation_days: i64,
+	pub jwt_accept_hs256: bool,
+	pub hcaptcha: Option<String>,
+	pub portainer_stack: Option<String>,
+	pub reload_seconds: u64,
+	values: BTreeMap<String, String>,
+}
+
+fn config_parse_bool(value: &str) -> Result<bool, String> {
+	match value.trim().to_lowercase().as_str() {
+		"true" | "1" | "yes" | "on" => Ok(true),
+		"false" | "0" | "no" | "off" => Ok(false),
+		other => Err(format!("expected a boolean, got {}", other)),
+	}
+}
+
+fn config_parse_positive(value: &str) -> Result<u64, String> {
+	match value.trim().parse::<u64>() {
+		Ok(number) if number > 0 => Ok(number),
+		_ => Err(format!("expected a positive integer, got {}", value)),
+	}
+}
+
+impl KbveConfig {
+	//	Merges the sources, later ones win, and validates the result.
+	pub fn from_sources(sources: &[BTreeMap<String, String>]) -> Result<Self, ConfigError> {
+		let mut values: BTreeMap<String, String> = BTreeMap::new();
+
+		for source in sources {
+			for (key, value) in source {
+				values.insert(key.to_lowercase(), value.trim().to_string());
+			}
+		}
+
+		let mut error = ConfigError::default();
+
+		for key in CONFIG_REQUIRED_KEYS {
+			if values.get(*key).map_or(true, |value| value.is_empty()) {
+				error.missing.push(key.to_string());
+			}
+		}
+
+		let present = |key: &str| values.get(key).filter(|value| !value.is_empty()).cloned();
+
+		let mut check = |key: &str, result: Result<(), String>| {
+			if let Err(reason) = result {
+				error.invalid.push((key.to_string(), reason));
+			}
+		};
+
+		let jwt_key_rotation_days = match present("jwt_key_rotation_days") {
+			Some(value) =>
+				match config_parse_positive(&value) {
+					Ok(days) => days as i64,
+					Err(reason) => {
+						check("jwt_key_rotation_days", Err(reason));
+						0
+					}
+				}
+			None => crate::session::KEYRING_ROTATION_DAYS_DEFAULT,
+		};
+
+		let jwt_accept_hs256 = match present("jwt_accept_hs256") {
+			Some(value) =>
+				match config_parse_bool(&value) {
+					Ok(accept) => accept,
+					Err(reason) => {
+						check("jwt_accept_hs256", Err(reason));
+						true
+					}
+				}
+			None => true,
+		};
+
+		let reload_seconds = match present("config_reload_seconds") {
+			Some(value) =>
+				match config_parse_positive(&value) {
+					Ok(seconds) => seconds,
+					Err(reason) => {
+						check("config_reload_seconds", Err(reason));
+						0
+					}
+				}
+			None => CONFIG_RELOAD_SECONDS_DEFAULT,
+		};
+
+		for (key, value) in &values {
+			if key.starts_with("rate_limit_") && key != "rate_limit_trusted_proxies" {
+				check(key, crate::session::RateLimit::parse(value).map(|_| ()).map_err(String::from));
+			}
+		}
+
+		if let Some(value) = present("captcha_min_score") {
+			check(
+				"captcha_min_score",
+				value
+					.parse::<f64>()
+					.map(|_| ())
+					.map_err(|_| format!("expected a number, got {}", value))
+			);
+		}
+
+		let checks: Vec<ConfigCheck> = CONFIG_CHECKS.read()
+			.map(|checks| checks.iter().map(|(_, check)| *check).collect())
+			.unwrap_or_default();
+
+		for config_check in checks {
+			for (key, reason) in config_check(&values) {
+				check(&key, Err(reason));
+			}
+		}
+
+		if !error.is_empty() {
+			return Err(error);
+		}
+
+		Ok(KbveConfig {
+			jwt_secret: present("jwt_secret").unwrap_or_default(),
+			jwt_key_dir: present("jwt_key_dir"),
+			jwt_key_rotation_days,
+			jwt_accept_hs256,
+			hcaptcha: present("hcaptcha"),
+			portainer_stack: present("portainer_stack"),
+			reload_seconds,
+			values,
+		})
+	}
+
+	//	Any key, including the ones without a typed field (`oauth_*`, `rate_limit_*`, ...).
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.values
+			.get(key)
+			.map(|value| value.as_str())
+			.filter(|value| !value.is_empty())
+	}
+
+	pub fn values(&self) -> &BTreeMap<String, String> {
+		&self.values
+	}
+
+	//	Keys added, removed or changed between two configs.
+	pub fn changed_keys(&self, other: &KbveConfig) -> Vec<String> {
+		let keys: BTreeSet<&String> = self.values.keys().chain(other.values.keys()).collect();
+
+		keys.into_iter()
+			.filter(|key| self.values.get(*key) != other.values.get(*key))
+			.cloned()
+			.collect()
+	}
+}
+
+//	?	[Sources]
+
+fn config_flatten_toml(prefix: &str, value: &toml::Value, out: &mut BTreeMap<String, String>) {
+	let key = |name: &str| {
+		if prefix.is_empty() { name.to_string() } else { format!("{}_{}", prefix, name) }
+	};
+
+	match value {
+		toml::Value::Table(table) => {
+			for (name, value) in table {
+				config_flatten_toml(&key(name), value, out);
+			}
+		}
+		toml::Value::String(text) => {
+			out.insert(prefix.to_string(), text.clone());
+		}
+		toml::Value::Array(items) => {
+			let items: Vec<String> = items
+				.iter()
+				.map(|item| match item {
+					toml::Value::String(text) => text.clone(),
+					other => other.to_string(),
+				})
+				.collect();
+			out.insert(prefix.to_string(), items.join(","));
+		}
+		other => {
+			out.insert(prefix.to_string(), other.to_string());
+		}
+	}
+}
+
+pub fn config_load_file() -> Result<BTreeMap<String, String>, ConfigError> {
+	let mut values = BTreeMap::new();
+
+	let Ok(path) = std::env::var(CONFIG_PATH_ENV) else {
+		return Ok(values);
+	};
+
+	let text = std::fs
+		::read_to_string(&path)
+		.map_err(|e| ConfigError::source(format!("Failed to read {}: {}", path, e)))?;
+
+	let document = text
+		.parse::<toml::Value>()
+		.map_err(|e| ConfigError::source(format!("Failed to parse {}: {}", path, e)))?;
+
+	config_flatten_toml("", &document, &mut values);
+
+	Ok(values)
+}
+
+pub fn config_load_env() -> Result<BTreeMap<String, String>, ConfigError> {
+	config_env_values(std::env::vars())
+}
+
+//	The `KBVE_*` entries of `vars`, keys lowercased without the prefix.
+fn config_env_values(
+	vars: impl IntoIterator<Item = (String, String)>
+) -> Result<BTreeMap<String, String>, ConfigError> {
+	let mut values = BTreeMap::new();
+
+	for (name, value) in vars {
+		let Some(key) = name.strip_prefix(CONFIG_ENV_PREFIX) else {
+			continue;
+		};
+
+		if name == CONFIG_PATH_ENV {
+			continue;
+		}
+
+		match key.strip_suffix("_FILE") {
+			Some(key) => {
+				let value = std::fs
+					::read_to_string(&value)
+					.map_err(|e| {
+						ConfigError::source(format!("Failed to read {} for {}: {}", value, name, e))
+					})?;
+				values.insert(key.to_lowercase(), value.trim().to_string());
+			}
+			None => {
+				values.insert(key.to_lowercase(), value);
+			}
+		}
+	}
+
+	Ok(values)
+}
+
+pub async fn config_load_db(pool: Arc<Pool>) -> Result<BTreeMap<String, String>, ConfigError> {
+	run_blocking(pool, move |conn| {
+		globals::table
+			.select((globals::key, globals::value))
+			.load::<(String, String)>(conn)
+			.map(|rows| rows.into_iter().collect())
+			.map_err(|_| ConfigError::source("Failed to load the globals table"))
+	}).await
+}
+
+pub async fn config_load(pool: Arc<Pool>) -> Result<KbveConfig, ConfigError> {
+	let file = config_load_file()?;
+	let database = config_load_db(pool).await?;
+	let env = config_load_env()?;
+
+	KbveConfig::from_sources(&[file, database, env])
+}
+
+//	Validates the config as it would be with `key` set to `value`, or removed, in the `globals` table.
+pub async fn config_preview_global(
+	pool: Arc<Pool>,
+	key: &str,
+	value: Option<&str>
+) -> Result<KbveConfig, ConfigError> {
+	let file = config_load_file()?;
+	let mut database = config_load_db(pool).await?;
+	let env = config_load_env()?;
+
+	match value {
+		Some(value) => {
+			database.insert(key.to_string(), value.to_string());
+		}
+		None => {
+			database.remove(key);
+		}
+	}
+
+	KbveConfig::from_sources(&[file, database, env])
+}
+
+//	A key set through `KBVE_<KEY>` wins over the `globals` table.
+pub fn config_env_overrides(key: &str) -> bool {
+	config_load_env()
+		.map(|env| env.contains_key(key))
+		.unwrap_or(false)
+}
+
+//	?	[Service]
+
+#[derive(Debug, Clone)]
+pub struct ConfigChange {
+	pub changed: Vec<String>,
+	pub config: Arc<KbveConfig>,
+}
+
+static CONFIG: OnceLock<RwLock<Arc<KbveConfig>>> = OnceLock::new();
+
+static CONFIG_SUBSCRIBERS: Lazy<broadcast::Sender<Arc<ConfigChange>>> = Lazy::new(|| {
+	broadcast::channel(CONFIG_SUBSCRIBER_CAPACITY).0
+});
+
+//	`None` until `config_init` has succeeded.
+pub fn kbve_config() -> Option<Arc<KbveConfig>> {
+	CONFIG.get()
+		.and_then(|config| config.read().ok())
+		.map(|config| config.clone())
+}
+
+//	Every successful reload that changed at least one key is sent to the subscribers.
+pub fn config_subscribe() -> broadcast::Receiver<Arc<ConfigChange>> {
+	CONFIG_SUBSCRIBERS.subscribe()
+}
+
+//	Mirrors the config into `runes::GLOBAL`, removed keys are dropped from the map.
+fn config_sync_global(config: &KbveConfig) {
+	let global_map = crate::runes::GLOBAL.get_or_init(|| Arc::new(DashMap::new()));
+
+	global_map.retain(|key, _| config.values.contains_key(key));
+
+	for (key, value) in &config.values {
+		if global_map.get(key).map_or(true, |current| current.value() != value) {
+			global_map.insert(key.clone(), value.clone());
+		}
+	}
+}
+
+//	Loads and validates the config at startup, the caller should refuse to run on an error.
+pub async fn config_init(pool: Arc<Pool>) -> Result<Arc<KbveConfig>, ConfigError> {
+	let config = Arc::new(config_load(pool).await?);
+
+	config_install(config.clone());
+
+	Ok(config)
+}
+
+fn config_install(config: Arc<KbveConfig>) {
+	config_sync_global(&config);
+
+	match CONFIG.get() {
+		Some(current) => {
+			if let Ok(mut current) = current.write() {
+				*current = config;
+			}
+		}
+		None => {
+			let _ = CONFIG.set(RwLock::new(config));
+		}
+	}
+}
+
+//	Re-reads every source. An invalid result is reported and the current config is kept.
+pub async fn config_reload(pool: Arc<Pool>) -> Result<Vec<String>, ConfigError> {
+	let fresh = Arc::new(config_load(pool).await?);
+
+	config_replace(fresh)
+}
+
+//	Swaps in `fresh` and tells the subscribers, nothing happens when no key changed.
+fn config_replace(fresh: Arc<KbveConfig>) -> Result<Vec<String>, ConfigError> {
+	let Some(current) = kbve_config() else {
+		return Err(ConfigError::source("config_init has not run"));
+	};
+
+	let changed = current.changed_keys(&fresh);
+
+	if changed.is_empty() {
+		return Ok(changed);
+	}
+
+	config_sync_global(&fresh);
+
+	if let Some(lock) = CONFIG.get() {
+		if let Ok(mut config) = lock.write() {
+			*config = fresh.clone();
+		}
+	}
+
+	let _ = CONFIG_SUBSCRIBERS.send(
+		Arc::new(ConfigChange {
+			changed: changed.clone(),
+			config: fresh,
+		})
+	);
+
+	Ok(changed)
+}
+
+pub fn config_spawn_reload(pool: Arc<Pool>) {
+	tokio::spawn(async move {
+		loop {
+			let seconds = kbve_config()
+				.map(|config| config.reload_seconds)
+				.unwrap_or(CONFIG_RELOAD_SECONDS_DEFAULT);
+
+			tokio::time::sleep(Duration::from_secs(seconds)).await;
+
+			match config_reload(pool.clone()).await {
+				Ok(changed) if !changed.is_empty() => {
+					tracing::info!("[Config] reloaded, changed: {}", changed.join(", "));
+				}
+				Ok(_) => {}
+				Err(e) => tracing::warn!("[Config] reload rejected, keeping the current config: {}", e),
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn source(values: &[(&str, &str)]) -> BTreeMap<String, String> {
+		values
+			.iter()
+			.map(|(key, value)| (key.to_string(), value.to_string()))
+			.collect()
+	}
+
+	fn toml_source(text: &str) -> BTreeMap<String, String> {
+		let mut values = BTreeMap::new();
+		config_flatten_toml("", &text.parse::<toml::Value>().unwrap(), &mut values);
+		values
+	}
+
+	#[test]
+	fn later_sources_win() {
+		let file = toml_source(
+			r#"
+				jwt_secret = "from-toml"
+				portainer_stack = "toml-stack"
+				config_reload_seconds = 30
+
+				[oauth.github]
+				client_id = "toml-client"
+			"#
+		);
+		let database = source(&[("jwt_secret", "from-globals"), ("portainer_stack", "globals-stack")]);
+		let env = config_env_values(
+			vec![
+				("KBVE_JWT_SECRET".to_string(), "from-env".to_string()),
+				("KBVE_CONFIG_PATH".to_string(), "/etc/kbve.toml".to_string()),
+				("PATH".to_string(), "/usr/bin".to_string()),
+			]
+		).unwrap();
+
+		let config = KbveConfig::from_sources(&[file, database, env]).unwrap();
+
+		assert_eq!(config.jwt_secret, "from-env");
+		assert_eq!(config.portainer_stack.as_deref(), Some("globals-stack"));
+		assert_eq!(config.reload_seconds, 30);
+		assert_eq!(config.get("oauth_github_client_id"), Some("toml-client"));
+		assert_eq!(config.get("config_path"), None);
+		assert_eq!(config.get("path"), None);
+	}
+
+	#[test]
+	fn env_file_suffix_reads_the_value_from_a_file() {
+		let path = std::env::temp_dir().join(
+			format!("kbve-config-{}", crate::utility::generate_secure_token())
+		);
+		std::fs::write(&path, "file-secret\n").unwrap();
+
+		let env = config_env_values(
+			vec![("KBVE_JWT_SECRET_FILE".to_string(), path.display().to_string())]
+		);
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(env.unwrap().get("jwt_secret").map(String::as_str), Some("file-secret"));
+
+		let missing = config_env_values(
+			vec![("KBVE_JWT_SECRET_FILE".to_string(), "/nonexistent/kbve-secret".to_string())]
+		);
+		assert!(missing.unwrap_err().source.is_some());
+	}
+
+	#[test]
+	fn every_problem_is_reported_at_once() {
+		let error = KbveConfig::from_sources(
+			&[
+				source(
+					&[
+						("jwt_secret", ""),
+						("jwt_key_rotation_days", "0"),
+						("jwt_accept_hs256", "maybe"),
+						("config_reload_seconds", "soon"),
+						("rate_limit_auth_login", "lots"),
+						("captcha_min_score", "high"),
+					]
+				),
+			]
+		).unwrap_err();
+
+		assert_eq!(error.missing, vec!["jwt_secret".to_string()]);
+
+		let invalid: Vec<&str> = error.invalid
+			.iter()
+			.map(|(key, _)| key.as_str())
+			.collect();
+		for key in [
+			"jwt_key_rotation_days",
+			"jwt_accept_hs256",
+			"config_reload_seconds",
+			"rate_limit_auth_login",
+			"captcha_min_score",
+		] {
+			assert!(invalid.contains(&key), "{} not reported", key);
+		}
+
+		let message = error.to_string();
+		assert!(message.contains("missing keys: jwt_secret"));
+		assert!(message.contains("invalid jwt_accept_hs256"));
+	}
+
+	#[test]
+	fn registered_checks_run_with_the_builtin_ones() {
+		config_register_check("test_check", |values| {
+			match values.get("test_check_value").map(String::as_str) {
+				Some("bad") => vec![("test_check_value".to_string(), "bad value".to_string())],
+				_ => Vec::new(),
+			}
+		});
+
+		let error = KbveConfig::from_sources(
+			&[source(&[("jwt_accept_hs256", "maybe"), ("test_check_value", "bad")])]
+		).unwrap_err();
+
+		assert!(error.invalid.contains(&("test_check_value".to_string(), "bad value".to_string())));
+		assert!(error.invalid.iter().any(|(key, _)| key == "jwt_accept_hs256"));
+		assert!(
+			KbveConfig::from_sources(
+				&[source(&[("jwt_secret", "secret"), ("test_check_value", "good")])]
+			).is_ok()
+		);
+	}
+
+	#[test]
+	fn reload_broadcasts_the_changed_keys() {
+		let first = Arc::new(
+			KbveConfig::from_sources(
+				&[source(&[("jwt_secret", "secret"), ("portainer_stack", "a"), ("test_removed", "x")])]
+			).unwrap()
+		);
+		let second = Arc::new(
+			KbveConfig::from_sources(
+				&[source(&[("jwt_secret", "secret"), ("portainer_stack", "b"), ("test_added", "y")])]
+			).unwrap()
+		);
+
+		config_install(first);
+		let mut changes = config_subscribe();
+
+		let changed = config_replace(second.clone()).unwrap();
+		assert_eq!(changed, vec!["portainer_stack", "test_added", "test_removed"]);
+
+		let change = changes.try_recv().unwrap();
+		assert_eq!(change.changed, changed);
+		assert_eq!(change.config, second);
+		assert_eq!(kbve_config().unwrap(), second);
+
+		let global = crate::runes::GLOBAL.get().unwrap();
+		assert_eq!(global.get("portainer_stack").map(|value| value.value().clone()), Some("b".to_string()));
+		assert!(global.get("test_removed").is_none());
+
+		//	An unchanged reload sends nothing.
+		assert!(config_replace(second).unwrap().is_empty());
+		assert!(changes.try_recv().is_err());
+	}
+}
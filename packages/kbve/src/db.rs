@@ -1,11 +1,160 @@
 use std::env;
+use std::fmt;
 use std::result::Result;
 use std::fs;
+use std::sync::Arc;
 use diesel::prelude::*;
-use diesel::mysql::MysqlConnection;
 use diesel::r2d2::{self, ConnectionManager};
+use diesel_migrations::{ embed_migrations, EmbeddedMigrations, MigrationHarness };
+use tokio::task;
 
-pub type Pool = r2d2::Pool<ConnectionManager<diesel::MysqlConnection>>;
+/*
+    The backend is picked at compile time, exactly one of the `mysql` (default), `postgres`
+    or `sqlite` features. Every query goes through `DbConnection`, so the same code builds
+    against whichever one is enabled, and `crate::schema` is the matching print-schema output.
+
+    ULIDs are stored as their 16 raw bytes everywhere, `BINARY(16)` on MySQL, `BYTEA` on
+    Postgres and a `BLOB` on SQLite, all of them read and written as `Vec<u8>`.
+    Row ids are `BIGINT UNSIGNED` on MySQL and a signed `BIGINT` on the other two, `RowId`
+    is the matching Rust type.
+*/
+
+#[cfg(any(
+    all(feature = "mysql", feature = "postgres"),
+    all(feature = "mysql", feature = "sqlite"),
+    all(feature = "postgres", feature = "sqlite")
+))]
+compile_error!("kbve: enable only one of the `mysql`, `postgres` or `sqlite` features");
+
+#[cfg(not(any(feature = "mysql", feature = "postgres", feature = "sqlite")))]
+compile_error!("kbve: enable one of the `mysql`, `postgres` or `sqlite` features");
+
+#[cfg(feature = "mysql")]
+pub type DbConnection = diesel::MysqlConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::PgConnection;
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::SqliteConnection;
+
+#[cfg(feature = "mysql")]
+pub type RowId = u64;
+#[cfg(not(feature = "mysql"))]
+pub type RowId = i64;
+
+pub type Pool = r2d2::Pool<ConnectionManager<DbConnection>>;
+
+/*
+    Versioned migrations for the enabled backend, compiled into the binary. The first one
+    creates every table with `IF NOT EXISTS`, so a MySQL database set up by the drizzle
+    migrations is adopted without touching its data and only gets recorded as migrated.
+
+    `IF NOT EXISTS` only skips whole tables, it can not bring an older drizzle schema up to
+    date. Before anything is read or applied, `drizzle_baseline_check` looks at
+    `__drizzle_migrations` and refuses a database that stopped short of `DRIZZLE_BASELINE_TAG`.
+    The drizzle migrations only ever targeted MySQL, the other backends have nothing to adopt.
+*/
+#[cfg(feature = "mysql")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/mysql");
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+
+/*
+    `SELECT ... FOR UPDATE` on MySQL and Postgres. SQLite has no row locks, a write locks the
+    whole database, so the query is left as is there and a writer that finds the database busy
+    waits up to `SQLITE_BUSY_TIMEOUT_MS` instead of failing at once.
+*/
+macro_rules! db_for_update {
+    ($query:expr) => {{
+        #[cfg(not(feature = "sqlite"))]
+        let query = $query.for_update();
+        #[cfg(feature = "sqlite")]
+        let query = $query;
+        query
+    }};
+}
+
+pub(crate) use db_for_update;
+
+#[cfg(feature = "sqlite")]
+pub const SQLITE_BUSY_TIMEOUT_MS: u32 = 5_000;
+
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+struct SqlitePragmas;
+
+#[cfg(feature = "sqlite")]
+impl r2d2::CustomizeConnection<DbConnection, r2d2::Error> for SqlitePragmas {
+    fn on_acquire(&self, conn: &mut DbConnection) -> Result<(), r2d2::Error> {
+        //  Foreign keys are off by default on SQLite, the schema relies on them.
+        use diesel::connection::SimpleConnection;
+
+        conn.batch_execute(
+            &format!("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = {};", SQLITE_BUSY_TIMEOUT_MS)
+        )
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+/*
+    Every query runs through `run_blocking`. Diesel is synchronous, so the work is moved to
+    Tokio's blocking pool together with a pooled connection and never holds up a worker
+    thread, waiting for a free connection included. A panic inside the work, or the task being
+    cancelled at shutdown, comes back as a `DbError` rather than unwinding into the handler.
+*/
+#[derive(Debug)]
+pub enum DbError {
+    //  No connection could be checked out of the pool in time.
+    Pool(String),
+    //  The blocking task panicked, the connection it held has been dropped.
+    Panicked,
+    //  The runtime shut down before the task ran.
+    Cancelled,
+}
+
+impl DbError {
+    //  Stable code for error responses and logs.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DbError::Pool(_) => "db_unavailable",
+            DbError::Panicked | DbError::Cancelled => "db_task_failed",
+        }
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Pool(error) => write!(f, "Failed to get a connection from the pool: {}", error),
+            DbError::Panicked => write!(f, "The database task panicked"),
+            DbError::Cancelled => write!(f, "The database task was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+pub async fn run_blocking<T, E, F>(pool: Arc<Pool>, work: F) -> Result<T, E>
+where
+    F: FnOnce(&mut DbConnection) -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<DbError> + Send + 'static,
+{
+    let joined = task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|error| DbError::Pool(error.to_string()))?;
+        work(&mut conn)
+    }).await;
+
+    match joined {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => {
+            tracing::error!("[DB] blocking task panicked");
+            Err(DbError::Panicked.into())
+        }
+        Err(_) => Err(DbError::Cancelled.into()),
+    }
+}
 
 fn get_env_var(name: &str) -> Result<String, String> {
     match env::var(name) {
@@ -18,16 +167,16 @@ fn get_env_var(name: &str) -> Result<String, String> {
     }
 }
 
-pub fn establish_connection_dev() -> Result<MysqlConnection, String> {
+pub fn establish_connection_dev() -> Result<DbConnection, String> {
     establish_connection_generic("DATABASE_URL_DEV")
 }
-pub fn establish_connection_prod() -> Result<MysqlConnection, String> {
+pub fn establish_connection_prod() -> Result<DbConnection, String> {
     establish_connection_generic("DATABASE_URL_PROD")
 }
 
-fn establish_connection_generic(env_var: &str) -> Result<MysqlConnection, String> {
+fn establish_connection_generic(env_var: &str) -> Result<DbConnection, String> {
     let database_url = get_env_var(env_var)?;
-    MysqlConnection::establish(&database_url)
+    DbConnection::establish(&database_url)
         .map_err(|err| format!("Error connecting to {}: {}", database_url, err))
 }
 
@@ -36,9 +185,90 @@ pub fn establish_connection_pool() -> Pool {
     let database_url = get_env_var("DATABASE_URL_PROD")
     .expect("DATABASE_URL_PROD must be set for production");
 
-    let manager = ConnectionManager::<MysqlConnection>::new(database_url);
+    let manager = ConnectionManager::<DbConnection>::new(database_url);
+
+    let builder = r2d2::Pool::builder();
 
-    r2d2::Pool::builder()
+    #[cfg(feature = "sqlite")]
+    let builder = builder.connection_customizer(Box::new(SqlitePragmas));
+
+    builder
     .build(manager)
     .expect("Failed to create the database connection pool")
-}
\ No newline at end of file
+}
+
+//  The drizzle migration the baseline matches, and its journal `when`, which drizzle stores
+//  as `created_at` of the row it records for it.
+pub const DRIZZLE_BASELINE_TAG: &str = "0008_quiet_armory";
+pub const DRIZZLE_BASELINE_WHEN: i64 = 1795939200000;
+
+#[cfg(feature = "mysql")]
+#[derive(QueryableByName)]
+struct DrizzleHead {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    drizzle_tables: i64,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::BigInt>)]
+    head: Option<i64>,
+}
+
+//  Fails when the database has a drizzle history that ends before the baseline.
+#[cfg(feature = "mysql")]
+fn drizzle_baseline_check(conn: &mut DbConnection) -> Result<(), String> {
+    let history = diesel::sql_query(
+        "SELECT COUNT(*) AS drizzle_tables, NULL AS head FROM information_schema.tables \
+         WHERE table_schema = DATABASE() AND table_name = '__drizzle_migrations'"
+    )
+        .get_result::<DrizzleHead>(conn)
+        .map_err(|err| format!("Error looking for the drizzle migrations: {}", err))?;
+
+    if history.drizzle_tables == 0 {
+        return Ok(());
+    }
+
+    let applied = diesel::sql_query(
+        "SELECT 1 AS drizzle_tables, CAST(MAX(created_at) AS SIGNED) AS head FROM __drizzle_migrations"
+    )
+        .get_result::<DrizzleHead>(conn)
+        .map_err(|err| format!("Error reading the drizzle migrations: {}", err))?;
+
+    match applied.head {
+        Some(head) if head >= DRIZZLE_BASELINE_WHEN => Ok(()),
+        _ => Err(format!(
+            "The database was created by the drizzle migrations but stops before {}, \
+             apply the drizzle migrations up to {} first.",
+            DRIZZLE_BASELINE_TAG,
+            DRIZZLE_BASELINE_TAG
+        )),
+    }
+}
+
+#[cfg(not(feature = "mysql"))]
+fn drizzle_baseline_check(_conn: &mut DbConnection) -> Result<(), String> {
+    Ok(())
+}
+
+//  Names of the embedded migrations the database has not applied yet, oldest first.
+pub fn pending_migrations(pool: &Pool) -> Result<Vec<String>, String> {
+    let mut pooled = pool.get()
+        .map_err(|err| format!("Error getting a connection from the pool: {}", err))?;
+    let conn: &mut DbConnection = &mut pooled;
+
+    drizzle_baseline_check(conn)?;
+
+    conn.pending_migrations(MIGRATIONS)
+        .map(|pending| pending.iter().map(|migration| migration.name().to_string()).collect())
+        .map_err(|err| format!("Error reading the applied migrations: {}", err))
+}
+
+//  Applies every pending migration in order and returns the versions it applied.
+pub fn run_pending_migrations(pool: &Pool) -> Result<Vec<String>, String> {
+    let mut pooled = pool.get()
+        .map_err(|err| format!("Error getting a connection from the pool: {}", err))?;
+    let conn: &mut DbConnection = &mut pooled;
+
+    drizzle_baseline_check(conn)?;
+
+    conn.run_pending_migrations(MIGRATIONS)
+        .map(|applied| applied.iter().map(|version| version.to_string()).collect())
+        .map_err(|err| format!("Error running the migrations: {}", err))
+}
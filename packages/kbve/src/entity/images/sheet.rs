@@ -24,7 +24,10 @@ use crate::db::{ Pool };
 
 use crate::response::{ GenericResponse, HeaderResponse };
 
-use crate::entity::{ hazardous_blocking_character_viewer_from_name };
+use crate::entity::{
+	hazardous_blocking_character_viewer_from_name,
+	hazardous_blocking_inventory_equipped,
+};
 
 use crate::models::{ Character };
 
@@ -72,26 +75,32 @@ pub async fn sheet_controller(
 	let character_data = match
 		hazardous_blocking_character_viewer_from_name(
 			sanitized_text.clone(),
-			pool
+			pool.clone()
 		).await
 	{
 		Ok(character) => character,
-		Err(error_msg) => {
-			let error_body =
-				json!({
-                "error": "Character was not found",
-                "details": error_msg
-            }).to_string();
-
-			let response = Response::builder()
-				.status(StatusCode::BAD_REQUEST)
-				.header(header::CONTENT_TYPE, "application/json")
-				.body(Body::from(error_body))
-				.unwrap();
-			return response.into_response();
+		Err(e) => {
+			return e.into_response();
 		}
 	};
 
+	//	A missing inventory should not take the whole sheet down.
+	let equipped = hazardous_blocking_inventory_equipped(character_data.cid.clone(), pool).await
+		.map(|entries| {
+			entries
+				.iter()
+				.map(|entry| entry.name.as_str())
+				.collect::<Vec<&str>>()
+				.join(", ")
+		})
+		.unwrap_or_default();
+
+	let equipped = if equipped.is_empty() {
+		"Nothing".to_string()
+	} else {
+		equipped.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+	};
+
 	let _sanitized_bg_l = "#000000";
 	let _sanitized_bg_m = "#000000";
 	let _sanitized_bg_r = "#000000";
@@ -136,7 +145,7 @@ pub async fn sheet_controller(
             <tspan x="340" dy="1.5em">Experience: {character_experience}</tspan>
             <tspan x="340" dy="1.5em">Reputation: {character_reputation}</tspan>
             <tspan x="340" dy="1.5em">Faith: {character_faith}</tspan>
-            <tspan x="340" dy="1.5em">X Line 11 - Slot 5 E</tspan>
+            <tspan x="340" dy="1.5em">Equipped: {character_equipped}</tspan>
         </text>
         
         <!-- Nam because gud musik, minus the ptsd, but the lsd -->
@@ -174,6 +183,7 @@ pub async fn sheet_controller(
 			&character_data.experience.to_string()
 		)
 		.replace("{character_faith}", &character_data.faith.to_string())
+		.replace("{character_equipped}", &equipped)
         .replace("{character_description}", &character_data.description)
         
         ;
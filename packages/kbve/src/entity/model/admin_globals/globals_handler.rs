@@ -0,0 +1,399 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::{ insert_into, update, delete };
+
+use crate::db::{ Pool, RowId, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ globals };
+
+use crate::models::{ Global };
+
+use crate::config::{ ConfigError, config_preview_global, config_reload, config_env_overrides };
+
+use crate::session::{ KbveState, TokenJWT };
+
+use crate::response::{ GenericResponse };
+
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
+use jsonwebtoken::TokenData;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+/**
+	Admin CRUD over the `globals` table, behind the `admin:globals` scope.
+
+	Every write is checked against the whole config first, so a change that would leave
+	`KbveConfig` invalid (a removed `jwt_secret`, a malformed `rate_limit_*`) is refused
+	before it reaches the table. Accepted writes reload the config straight away, which also
+	refreshes `runes::GLOBAL`, instead of waiting for the next `config_reload_seconds` tick.
+
+	Values are redacted by default and can only be overwritten. Only keys named in
+	`GLOBAL_PUBLIC_KEYS`, starting with one of `GLOBAL_PUBLIC_PREFIXES` or naming a public
+	`oauth_<provider>_<field>` are sent back, and never when they contain a secret marker.
+	A new key, say a webhook URL with its token, stays hidden until it is listed here.
+**/
+
+pub const GLOBAL_KEY_MAX: usize = 255;
+pub const GLOBAL_VALUE_MAX: usize = 255;
+pub const GLOBAL_REDACTED: &str = "[redacted]";
+
+//	Any key containing one of these is treated as a secret, even when it is listed as public.
+pub const GLOBAL_SECRET_MARKERS: &[&str] = &[
+	"secret",
+	"password",
+	"private",
+	"hcaptcha",
+	"portainer_stack",
+	"shieldwall",
+];
+
+pub const GLOBAL_PUBLIC_KEYS: &[&str] = &[
+	"jwt_key_dir",
+	"jwt_key_rotation_days",
+	"jwt_accept_hs256",
+	"config_reload_seconds",
+	"captcha_provider",
+	"captcha_verify_url",
+	"captcha_hostnames",
+	"captcha_min_score",
+];
+
+pub const GLOBAL_PUBLIC_PREFIXES: &[&str] = &["rate_limit_", "character_", "progression_"];
+
+//	`oauth_<provider>_<field>` fields that are shown, the client secret is not one of them.
+pub const GLOBAL_PUBLIC_OAUTH_FIELDS: &[&str] = &[
+	"client_id",
+	"authorize_url",
+	"token_url",
+	"userinfo_url",
+	"redirect_uri",
+	"scope",
+	"issuer",
+	"subject_field",
+];
+
+#[derive(Deserialize)]
+pub struct GlobalUpdateRequest {
+	pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct GlobalSummary {
+	pub key: String,
+	pub value: String,
+	pub secret: bool,
+	//	Set when a `KBVE_<KEY>` variable wins over the table value.
+	pub overridden: bool,
+}
+
+impl GlobalSummary {
+	fn from_row(row: Global) -> Self {
+		let secret = global_is_secret(&row.key);
+
+		GlobalSummary {
+			overridden: config_env_overrides(&row.key),
+			value: if secret {
+				GLOBAL_REDACTED.to_string()
+			} else {
+				row.value
+			},
+			key: row.key,
+			secret,
+		}
+	}
+}
+
+pub fn global_is_secret(key: &str) -> bool {
+	if GLOBAL_SECRET_MARKERS.iter().any(|marker| key.contains(marker)) {
+		return true;
+	}
+
+	let public_oauth = key.strip_prefix("oauth_").is_some_and(|rest| {
+		GLOBAL_PUBLIC_OAUTH_FIELDS.iter().any(|field| {
+			rest
+				.strip_suffix(field)
+				.is_some_and(|provider| provider.len() > 1 && provider.ends_with('_'))
+		})
+	});
+
+	!(
+		GLOBAL_PUBLIC_KEYS.contains(&key) ||
+		GLOBAL_PUBLIC_PREFIXES.iter().any(|prefix| key.starts_with(prefix)) ||
+		public_oauth
+	)
+}
+
+//	Keys are stored the way `KbveConfig` reads them, lowercase `a-z`, `0-9` and `_`.
+pub fn global_key_clean(key: &str) -> Result<String, KbveError> {
+	let key = key.trim().to_lowercase();
+
+	if
+		key.is_empty() ||
+		key.len() > GLOBAL_KEY_MAX ||
+		!key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+	{
+		return Err(KbveError::Validation("invalid_key"));
+	}
+
+	Ok(key)
+}
+
+//	?	[Hazardous]
+
+pub async fn hazardous_blocking_globals_list(pool: Arc<Pool>) -> Result<Vec<Global>, KbveError> {
+	run_blocking(pool, move |conn| {
+		globals::table
+			.order(globals::key.asc())
+			.load::<Global>(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_global_by_key(
+	clean_key: String,
+	pool: Arc<Pool>
+) -> Result<Option<Global>, KbveError> {
+	run_blocking(pool, move |conn| {
+		globals::table
+			.filter(globals::key.eq(clean_key))
+			.first::<Global>(conn)
+			.optional()
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Returns `true` when the key was created, `false` when an existing value was replaced.
+pub async fn hazardous_blocking_global_upsert(
+	clean_key: String,
+	clean_value: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn
+			.transaction::<bool, diesel::result::Error, _>(|conn| {
+				let existing = globals::table
+					.filter(globals::key.eq(&clean_key))
+					.select(globals::id)
+					.first::<RowId>(conn)
+					.optional()?;
+
+				match existing {
+					Some(id) => {
+						update(globals::table.filter(globals::id.eq(id)))
+							.set(globals::value.eq(&clean_value))
+							.execute(conn)?;
+						Ok(false)
+					}
+					None => {
+						insert_into(globals::table)
+							.values((
+								globals::key.eq(&clean_key),
+								globals::value.eq(&clean_value),
+							))
+							.execute(conn)?;
+						Ok(true)
+					}
+				}
+			})
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_global_delete(
+	clean_key: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		delete(globals::table.filter(globals::key.eq(clean_key)))
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	?	[Handlers]
+
+//	The preview error names the keys at fault, the admin needs them to fix the change.
+fn global_invalid_config(clean_key: &str, error: ConfigError) -> axum::response::Response {
+	//	The reason can quote the value back.
+	let details = if global_is_secret(clean_key) {
+		GLOBAL_REDACTED.to_string()
+	} else {
+		error.to_string()
+	};
+
+	GenericResponse::error(
+		json!({}),
+		json!({"error": "The change would leave the config invalid", "details": details}),
+		"invalid_config".to_string(),
+		StatusCode::BAD_REQUEST
+	).into_response()
+}
+
+//	Reloads the config after a write, the table is already updated so a failure is only reported.
+async fn global_refresh(pool: Arc<Pool>) -> bool {
+	match config_reload(pool).await {
+		Ok(_) => true,
+		Err(e) => {
+			tracing::warn!("[Globals] config reload after an admin write failed: {}", e);
+			false
+		}
+	}
+}
+
+pub async fn admin_globals_list_handler(
+	Extension(state): Extension<Arc<KbveState>>
+) -> impl IntoResponse {
+	match hazardous_blocking_globals_list(state.db_pool.clone()).await {
+		Ok(rows) => {
+			let globals: Vec<GlobalSummary> = rows
+				.into_iter()
+				.map(GlobalSummary::from_row)
+				.collect();
+
+			GenericResponse::new(
+				json!({"globals": globals}),
+				json!("Globals retrieved successfully."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn admin_globals_get_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Path(key): Path<String>
+) -> impl IntoResponse {
+	let clean_key = match global_key_clean(&key) {
+		Ok(key) => key,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match hazardous_blocking_global_by_key(clean_key, state.db_pool.clone()).await {
+		Ok(Some(row)) =>
+			GenericResponse::new(
+				json!({"global": GlobalSummary::from_row(row)}),
+				json!("Global retrieved successfully."),
+				StatusCode::OK
+			).into_response(),
+		Ok(None) => KbveError::NotFound("global_not_found").into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn admin_globals_put_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(key): Path<String>,
+	Json(payload): Json<GlobalUpdateRequest>
+) -> impl IntoResponse {
+	let clean_key = match global_key_clean(&key) {
+		Ok(key) => key,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let clean_value = payload.value.trim().to_string();
+
+	if clean_value.chars().count() > GLOBAL_VALUE_MAX {
+		return KbveError::Validation("invalid_value").into_response();
+	}
+
+	if
+		let Err(e) = config_preview_global(
+			state.db_pool.clone(),
+			&clean_key,
+			Some(&clean_value)
+		).await
+	{
+		return global_invalid_config(&clean_key, e);
+	}
+
+	let created = match
+		hazardous_blocking_global_upsert(
+			clean_key.clone(),
+			clean_value,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(created) => created,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	audit_record(
+		AuditEvent::GlobalUpdate { key: clean_key.clone() },
+		AuditOutcome::Success,
+		Some(&privatedata.claims.userid),
+		&audit
+	);
+
+	let reloaded = global_refresh(state.db_pool.clone()).await;
+
+	GenericResponse::new(
+		json!({
+			"key": clean_key,
+			"created": created,
+			"reloaded": reloaded,
+			"overridden": config_env_overrides(&clean_key),
+		}),
+		json!("Global stored."),
+		if created { StatusCode::CREATED } else { StatusCode::OK }
+	).into_response()
+}
+
+pub async fn admin_globals_delete_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(key): Path<String>
+) -> impl IntoResponse {
+	let clean_key = match global_key_clean(&key) {
+		Ok(key) => key,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if let Err(e) = config_preview_global(state.db_pool.clone(), &clean_key, None).await {
+		return global_invalid_config(&clean_key, e);
+	}
+
+	match hazardous_blocking_global_delete(clean_key.clone(), state.db_pool.clone()).await {
+		Ok(true) => {
+			audit_record(
+				AuditEvent::GlobalDelete { key: clean_key.clone() },
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
+			);
+
+			let reloaded = global_refresh(state.db_pool.clone()).await;
+
+			GenericResponse::new(
+				json!({"key": clean_key, "reloaded": reloaded}),
+				json!("Global deleted."),
+				StatusCode::OK
+			).into_response()
+		}
+		Ok(false) => KbveError::NotFound("global_not_found").into_response(),
+		Err(e) => e.into_response(),
+	}
+}
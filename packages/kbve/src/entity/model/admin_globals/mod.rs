@@ -0,0 +1,3 @@
+pub mod globals_handler;
+
+pub use globals_handler::*;
\ No newline at end of file
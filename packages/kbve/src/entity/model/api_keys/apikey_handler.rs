@@ -0,0 +1,470 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::{ insert_into, delete };
+
+use crate::db::{ Pool, RowId, db_for_update, run_blocking };
+
+use crate::schema::{ apikey, users };
+
+use crate::models::{ Apikey };
+
+use crate::session::{ KbveState, TokenJWT, TokenAPIKey, Scopes, is_known_scope };
+
+use crate::error::{ KbveError };
+
+use crate::response::{ GenericResponse };
+
+use crate::spellbook_generate_ulid_bytes;
+
+use crate::utility::{
+	convert_ulid_string_to_bytes,
+	convert_ulid_bytes_to_string,
+	generate_secure_token,
+	hash_secure_token,
+	sanitize_string_limit,
+};
+
+use jsonwebtoken::TokenData;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+//	Every issued key starts with this prefix so leaked keys are easy to spot in logs and scanners.
+pub const API_KEY_PREFIX: &str = "kbve_";
+pub const API_KEY_LIMIT: i64 = 10;
+pub const API_KEY_LABEL_MAX: usize = 64;
+pub const API_KEY_PERMISSION_MAX: usize = 64;
+
+#[derive(Deserialize)]
+pub struct ApiKeyCreationRequest {
+	pub label: String,
+	#[serde(default)]
+	pub permissions: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeySummary {
+	pub ulid: String,
+	pub label: String,
+	pub permissions: Vec<String>,
+}
+
+impl ApiKeySummary {
+	fn from_row(row: Apikey) -> Option<Self> {
+		Some(ApiKeySummary {
+			ulid: convert_ulid_bytes_to_string(&row.ulid).ok()?,
+			label: row.label,
+			permissions: api_key_permissions_from_column(&row.permissions),
+		})
+	}
+}
+
+//	?	[Permissions]
+
+//	Permissions are stored in `apikey.permissions` as a comma separated list, e.g. `character:read,character:write`.
+pub fn api_key_permissions_from_column(column: &str) -> Vec<String> {
+	column
+		.split(',')
+		.map(|permission| permission.trim())
+		.filter(|permission| !permission.is_empty())
+		.map(String::from)
+		.collect()
+}
+
+//	A key can only be granted scopes that its creator currently holds.
+pub fn api_key_permissions_to_column(
+	permissions: &[String],
+	owner: &Scopes
+) -> Result<String, KbveError> {
+	let mut clean: Vec<String> = Vec::with_capacity(permissions.len());
+
+	for permission in permissions {
+		let permission = permission.trim().to_lowercase();
+
+		if
+			permission.is_empty() ||
+			permission.len() > API_KEY_PERMISSION_MAX ||
+			!is_known_scope(&permission)
+		{
+			return Err(KbveError::Validation("invalid_permission"));
+		}
+
+		if !owner.allows(&permission) {
+			return Err(KbveError::Validation("permission_not_held"));
+		}
+
+		if !clean.contains(&permission) {
+			clean.push(permission);
+		}
+	}
+
+	let column = clean.join(",");
+
+	if column.len() > 255 {
+		return Err(KbveError::Validation("too_many_permissions"));
+	}
+
+	Ok(column)
+}
+
+//	?	[Hazardous]
+
+/**
+	Checks the key limit and inserts the key in one transaction.
+
+	The user row is locked first, like the character slot check, so two creations racing
+	for the last key run one after the other and the second sees the first key in its count.
+**/
+pub async fn hazardous_blocking_create_api_key(
+	clean_ulid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_permissions: String,
+	clean_keyhash: String,
+	clean_label: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn
+			.transaction::<bool, KbveError, _>(|conn| {
+				if
+					db_for_update!(
+						users::table
+							.filter(users::userid.eq(&dirty_userid))
+							.select(users::id)
+					)
+						.first::<RowId>(conn)
+						.optional()?
+						.is_none()
+				{
+					return Err(KbveError::NotFound("user_not_found"));
+				}
+
+				let used = apikey::table
+					.filter(apikey::userid.eq(&dirty_userid))
+					.count()
+					.get_result::<i64>(conn)?;
+
+				if used >= API_KEY_LIMIT {
+					return Err(KbveError::Conflict("api_key_limit"));
+				}
+
+				insert_into(apikey::table)
+					.values((
+						apikey::ulid.eq(&clean_ulid),
+						apikey::userid.eq(&dirty_userid),
+						apikey::permissions.eq(&clean_permissions),
+						apikey::keyhash.eq(&clean_keyhash),
+						apikey::label.eq(&clean_label),
+					))
+					.execute(conn)?;
+
+				Ok(true)
+			})
+	}).await
+}
+
+pub async fn hazardous_blocking_get_api_keys_by_userid(
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Vec<Apikey>, KbveError> {
+	run_blocking(pool, move |conn| {
+		apikey::table
+			.filter(apikey::userid.eq(dirty_userid))
+			.order(apikey::id.asc())
+			.load::<Apikey>(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Scoped to the owner so one user can never revoke another user's key.
+pub async fn hazardous_blocking_revoke_api_key(
+	clean_ulid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		delete(
+			apikey::table
+				.filter(apikey::ulid.eq(clean_ulid))
+				.filter(apikey::userid.eq(dirty_userid))
+		)
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Resolves a presented key hash to its owner, used by `session::middleware_api_key`.
+pub async fn hazardous_blocking_api_key_owner_by_hash(
+	clean_keyhash: String,
+	pool: Arc<Pool>
+) -> Result<TokenAPIKey, KbveError> {
+	run_blocking(pool, move |conn| {
+		let key = apikey::table
+			.filter(apikey::keyhash.eq(clean_keyhash))
+			.first::<Apikey>(conn)
+			.optional()?
+			.ok_or(KbveError::Unauthorized("invalid_api_key"))?;
+
+		let (username, role) = users::table
+			.filter(users::userid.eq(&key.userid))
+			.select((users::username, users::role))
+			.first::<(String, i32)>(conn)
+			.optional()?
+			.ok_or(KbveError::Unauthorized("invalid_api_key"))?;
+
+		let keyid = convert_ulid_bytes_to_string(&key.ulid).map_err(|_|
+			KbveError::Internal("invalid_ulid")
+		)?;
+		let userid = convert_ulid_bytes_to_string(&key.userid).map_err(|_|
+			KbveError::Internal("invalid_ulid")
+		)?;
+
+		Ok(TokenAPIKey {
+			keyid,
+			userid,
+			username,
+			role,
+			permissions: api_key_permissions_from_column(&key.permissions),
+		})
+	}).await
+}
+
+//	?	[Handlers]
+
+fn api_key_owner_bytes(privatedata: &TokenData<TokenJWT>) -> Result<Vec<u8>, KbveError> {
+	convert_ulid_string_to_bytes(&privatedata.claims.userid).map_err(|_|
+		KbveError::Validation("invalid_ulid")
+	)
+}
+
+//	The plain key is returned exactly once, only its SHA-256 digest is stored.
+pub async fn api_key_creation_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Extension(scopes): Extension<Scopes>,
+	Json(payload): Json<ApiKeyCreationRequest>
+) -> impl IntoResponse {
+	let byte_ulid = match api_key_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let label = sanitize_string_limit(payload.label.trim());
+
+	if label.is_empty() || label.chars().count() > API_KEY_LABEL_MAX {
+		return KbveError::Validation("invalid_label").into_response();
+	}
+
+	let permissions = match api_key_permissions_to_column(
+		&payload.permissions,
+		&scopes
+	) {
+		Ok(permissions) => permissions,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let clean_ulid = spellbook_generate_ulid_bytes!();
+	let keyid = match convert_ulid_bytes_to_string(&clean_ulid) {
+		Ok(keyid) => keyid,
+		Err(_) => {
+			return KbveError::Internal("invalid_ulid").into_response();
+		}
+	};
+
+	let secret = format!("{}{}", API_KEY_PREFIX, generate_secure_token());
+
+	if
+		let Err(e) = hazardous_blocking_create_api_key(
+			clean_ulid,
+			byte_ulid,
+			permissions.clone(),
+			hash_secure_token(&secret),
+			label.clone(),
+			state.db_pool.clone()
+		).await
+	{
+		return e.into_response();
+	}
+
+	GenericResponse::new(
+		json!({
+			"ulid": keyid,
+			"label": label,
+			"permissions": api_key_permissions_from_column(&permissions),
+			"key": secret,
+		}),
+		json!("API key created, store it now as it will not be shown again."),
+		StatusCode::CREATED
+	).into_response()
+}
+
+pub async fn api_key_list_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>
+) -> impl IntoResponse {
+	let byte_ulid = match api_key_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_get_api_keys_by_userid(
+			byte_ulid,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(keys) => {
+			let keys: Vec<ApiKeySummary> = keys
+				.into_iter()
+				.filter_map(ApiKeySummary::from_row)
+				.collect();
+
+			GenericResponse::new(
+				json!({"apikeys": keys}),
+				json!("API keys retrieved successfully."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn api_key_revoke_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path(keyid): Path<String>
+) -> impl IntoResponse {
+	let byte_ulid = match api_key_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let key_ulid = match convert_ulid_string_to_bytes(keyid.trim()) {
+		Ok(bytes) => bytes,
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_revoke_api_key(
+			key_ulid,
+			byte_ulid,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) =>
+			GenericResponse::new(
+				json!({"ulid": keyid.trim()}),
+				json!("API key revoked."),
+				StatusCode::OK
+			).into_response(),
+		Ok(false) => KbveError::NotFound("api_key_not_found").into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+//	Lets service callers confirm which account and permissions their key resolves to.
+pub async fn api_key_whoami_handler(
+	Extension(claims): Extension<TokenAPIKey>
+) -> impl IntoResponse {
+	GenericResponse::new(
+		json!({"apikey": claims}),
+		json!("API key is valid."),
+		StatusCode::OK
+	).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::session::{ Role };
+
+	fn permissions(list: &[&str]) -> Vec<String> {
+		list.iter().map(|permission| permission.to_string()).collect()
+	}
+
+	#[test]
+	fn column_keeps_held_scopes_once_and_lowercased() {
+		let player = Scopes::for_role(Role::Player);
+
+		assert_eq!(
+			api_key_permissions_to_column(
+				&permissions(&[" Character:Read ", "character:read", "profile:read"]),
+				&player
+			).unwrap(),
+			"character:read,profile:read"
+		);
+		assert_eq!(api_key_permissions_to_column(&[], &player).unwrap(), "");
+	}
+
+	#[test]
+	fn column_refuses_unknown_or_empty_scopes() {
+		let admin = Scopes::for_role(Role::Admin);
+
+		for permission in ["", "character:fly", "admin:", "nothing:*", "x".repeat(65).as_str()] {
+			assert_eq!(
+				api_key_permissions_to_column(&permissions(&[permission]), &admin).unwrap_err().code(),
+				"invalid_permission",
+				"{:?}",
+				permission
+			);
+		}
+	}
+
+	#[test]
+	fn column_refuses_scopes_the_owner_does_not_hold() {
+		let player = Scopes::for_role(Role::Player);
+
+		for permission in ["admin:read", "character:experience", "character:*", "*"] {
+			assert_eq!(
+				api_key_permissions_to_column(&permissions(&[permission]), &player).unwrap_err().code(),
+				"permission_not_held",
+				"{:?}",
+				permission
+			);
+		}
+
+		let admin = Scopes::for_role(Role::Admin);
+		assert_eq!(
+			api_key_permissions_to_column(&permissions(&["*", "admin:globals"]), &admin).unwrap(),
+			"*,admin:globals"
+		);
+	}
+
+	#[test]
+	fn column_round_trips_and_is_capped_by_the_owner_role() {
+		let moderator = Scopes::for_role(Role::Moderator);
+		let column = api_key_permissions_to_column(
+			&permissions(&["admin:read", "character:write"]),
+			&moderator
+		).unwrap();
+
+		assert_eq!(
+			api_key_permissions_from_column(&column),
+			permissions(&["admin:read", "character:write"])
+		);
+
+		//	The owner was demoted after issuing, the key loses what the new role lacks.
+		let key = Scopes::for_api_key(Role::Player, &api_key_permissions_from_column(&column));
+		assert!(key.allows("character:write"));
+		assert!(!key.allows("admin:read"));
+	}
+}
@@ -0,0 +1,3 @@
+pub mod apikey_handler;
+
+pub use apikey_handler::*;
\ No newline at end of file
@@ -2,183 +2,191 @@ use std::sync::{ Arc };
 
 use axum::{
 	http::StatusCode,
-	extract::{ Extension, Json },
+	extract::{ Extension, Json, Path, Query },
 	response::IntoResponse,
 };
 
 use diesel::prelude::*;
-use diesel::insert_into;
-
-use tokio::task;
+use diesel::{ insert_into, update, delete };
 
 use chrono::Utc;
 
-use crate::db::{ Pool };
+use crate::db::{ DbConnection, Pool, RowId, db_for_update, run_blocking };
 
-use crate::schema::{ characters };
+use crate::schema::{ character_items, characters, settings, users };
 
 use crate::models::{ Character };
 
-use crate::session::{ KbveState, TokenJWT };
+use crate::session::{ KbveState, Role, TokenJWT, hazardous_blocking_role_by_userid };
+
+use crate::error::{ KbveError };
 
 use crate::response::{ GenericResponse };
 
-use crate::{ spellbook_pool_conn, spellbook_generate_ulid_bytes };
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
+use crate::entity::{
+	CharacterRules,
+	CoreStats,
+	Vitals,
+	XpCurve,
+	CHARACTER_SLOTS_BONUS_KEY,
+	CHARACTER_SLOTS_LIMIT,
+	hazardous_blocking_setting_delete,
+	hazardous_blocking_setting_upsert,
+};
 
-use crate::utility::{ convert_ulid_string_to_bytes };
+use crate::spellbook_generate_ulid_bytes;
+
+use crate::utility::{ convert_ulid_string_to_bytes, convert_ulid_bytes_to_string };
 
 use jedi::builder::ValidatorBuilder;
 
 use jsonwebtoken::TokenData;
 
-use serde::Deserialize;
+use serde::{ Serialize, Deserialize };
 use serde_json::json;
 
 #[derive(Deserialize)]
 pub struct CharacterCreationRequest {
 	pub name: String,
 	pub description: String,
+	//	Missing picks the default class.
+	#[serde(default)]
+	pub class: Option<String>,
 }
 
-pub async fn hazardous_blocking_check_user_character_limit(
-	dirty_userid: Vec<u8>,
-	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let result = task
-		::spawn_blocking(move || {
-			let mut conn = spellbook_pool_conn!(pool);
+//	Slot limit of a user, the role limit plus any bonus granted through their settings.
+fn hazardous_character_slot_limit(
+	conn: &mut DbConnection,
+	dirty_userid: &[u8],
+	rules: &CharacterRules
+) -> Result<Option<u32>, diesel::result::Error> {
+	let Some(role) = db_for_update!(
+		users::table.filter(users::userid.eq(dirty_userid)).select(users::role)
+	)
+		.first::<i32>(conn)
+		.optional()? else {
+		return Ok(None);
+	};
 
-			// Count the number of characters for the given userid
-			let character_count = characters::table
-				.filter(characters::userid.eq(dirty_userid))
-				.count()
-				.get_result::<i64>(&mut conn);
-
-			match character_count {
-				Ok(count) => {
-					if count >= 3 {
-						// If the user already has 3 or more characters, return an error
-						Ok(false)
-					} else {
-						// If the user has less than 3 characters, they can create more
-						Ok(true)
-					}
-				}
-				Err(_) => Err("db_error"),
-			}
-		}).await
-		.expect("spawn_blocking failed");
+	let bonus = settings::table
+		.filter(settings::userid.eq(dirty_userid))
+		.filter(settings::key.eq(CHARACTER_SLOTS_BONUS_KEY))
+		.select(settings::value)
+		.first::<String>(conn)
+		.optional()?
+		.and_then(|value| value.parse::<u32>().ok())
+		.unwrap_or(0);
 
-	result
+	Ok(Some(rules.slot_limit(Role::from_i32(role), bonus)))
 }
 
 pub async fn hazardous_blocking_boolean_character_name_slot_open(
 	dirty_name: String,
 	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let result = task
-		::spawn_blocking(move || {
-			let mut conn = spellbook_pool_conn!(pool);
-
-			characters::table
-				.filter(characters::name.eq(dirty_name))
-				.select(characters::cid)
-				.first::<Vec<u8>>(&mut conn)
-				.map(|_| false)
-				.or_else(|err| {
-					match err {
-						diesel::result::Error::NotFound => Ok(true),
-						_ => Err("db_error"),
-					}
-				})
-		}).await
-		.expect("spawn_blocking failed");
-
-	result
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		characters::table
+			.filter(characters::name.eq(dirty_name))
+			.select(characters::cid)
+			.first::<Vec<u8>>(conn)
+			.optional()
+			.map(|taken| taken.is_none())
+			.map_err(KbveError::from)
+	}).await
 }
 
 pub async fn hazardous_blocking_character_viewer_from_name(
 	character_name: String,
 	pool: Arc<Pool>
-) -> Result<Character, &'static str> {
-	let result = task
-		::spawn_blocking(move || {
-			let mut conn = spellbook_pool_conn!(pool);
-
-			match
-				characters::table
-					.filter(characters::name.eq(character_name))
-					.first::<Character>(&mut conn)
-			{
-				Ok(character) => Ok(character),
-				Err(diesel::result::Error::NotFound) =>
-					Err("Character was not found"),
-				Err(_) => Err("Database Error"),
-			}
-		}).await
-		.expect("spawn_blocking failed");
-
-	result
+) -> Result<Character, KbveError> {
+	run_blocking(pool, move |conn| {
+		characters::table
+			.filter(characters::name.eq(character_name))
+			.first::<Character>(conn)
+			.optional()?
+			.ok_or(KbveError::NotFound("character_not_found"))
+	}).await
 }
 
+/**
+	Checks the slot limit and inserts the character in one transaction, returns the new cid.
+
+	The user row is locked first, so two creations racing for the last slot run one after
+	the other and the second sees the first character in its count.
+**/
 pub async fn hazardous_blocking_create_character_from_user(
 	dirty_name: String,
 	dirty_description: String,
+	class_stats: CoreStats,
 	dirty_user_id: Vec<u8>,
+	rules: CharacterRules,
 	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let result = task
-		::spawn_blocking(move || {
-			let mut conn = spellbook_pool_conn!(pool);
-
-			let clean_cid = spellbook_generate_ulid_bytes!();
-
-			insert_into(characters::table)
-				.values((
-					characters::id.eq(0),
-					characters::cid.eq(clean_cid),
-					characters::userid.eq(dirty_user_id),
-					characters::hp.eq(100),
-					characters::mp.eq(100),
-					characters::ep.eq(100),
-					characters::health.eq(100),
-					characters::mana.eq(100),
-					characters::energy.eq(100),
-					characters::armour.eq(1),
-					characters::agility.eq(1),
-					characters::strength.eq(1),
-					characters::intelligence.eq(1),
-					characters::name.eq(dirty_name),
-					characters::description.eq(dirty_description),
-					characters::experience.eq(0),
-					characters::reputation.eq(0),
-					characters::faith.eq(1),
-				))
-				.execute(&mut conn)
-				.map(|_| true)
-				.map_err(|_| "Failed to insert character into database")
-		}).await
-		.expect("spawn_blocking failed");
-
-	result
+) -> Result<Vec<u8>, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn
+			.transaction::<Vec<u8>, KbveError, _>(|conn| {
+				let Some(limit) = hazardous_character_slot_limit(
+					conn,
+					&dirty_user_id,
+					&rules
+				)? else {
+					return Err(KbveError::NotFound("user_not_found"));
+				};
+
+				let used = characters::table
+					.filter(characters::userid.eq(&dirty_user_id))
+					.count()
+					.get_result::<i64>(conn)?;
+
+				if used >= (limit as i64) {
+					return Err(KbveError::Conflict("slots_full"));
+				}
+
+				let clean_cid = spellbook_generate_ulid_bytes!();
+				let (max_health, max_mana, max_energy) = Vitals::maxima(&class_stats, 1);
+
+				insert_into(characters::table)
+					.values((
+						characters::cid.eq(&clean_cid),
+						characters::userid.eq(&dirty_user_id),
+						characters::hp.eq(max_health),
+						characters::mp.eq(max_mana),
+						characters::ep.eq(max_energy),
+						characters::health.eq(max_health),
+						characters::mana.eq(max_mana),
+						characters::energy.eq(max_energy),
+						characters::armour.eq(class_stats.armour),
+						characters::agility.eq(class_stats.agility),
+						characters::strength.eq(class_stats.strength),
+						characters::intelligence.eq(class_stats.intelligence),
+						characters::name.eq(&dirty_name),
+						characters::description.eq(&dirty_description),
+						characters::experience.eq(0),
+						characters::reputation.eq(0),
+						characters::faith.eq(1),
+						characters::stat_points.eq(0),
+						characters::updated_at.eq(Utc::now().naive_utc()),
+					))
+					.execute(conn)?;
+
+				Ok(clean_cid)
+			})
+			.map_err(character_name_taken)
+	}).await
 }
 
 pub async fn hazardous_blocking_get_characters_by_userid(
 	dirty_userid: Vec<u8>,
 	pool: Arc<Pool>
-) -> Result<Vec<Character>, &'static str> {
-	let result = task
-		::spawn_blocking(move || {
-			let mut conn = spellbook_pool_conn!(pool);
-
-			characters::table
-				.filter(characters::userid.eq(dirty_userid))
-				.load::<Character>(&mut conn)
-				.map_err(|_| "db_error")
-		}).await
-		.expect("spawn_blocking failed");
-
-	result
+) -> Result<Vec<Character>, KbveError> {
+	run_blocking(pool, move |conn| {
+		characters::table
+			.filter(characters::userid.eq(dirty_userid))
+			.load::<Character>(conn)
+			.map_err(KbveError::from)
+	}).await
 }
 
 //	Prepare -> authorized check through middleware
@@ -186,19 +194,6 @@ pub async fn authorized_character_data_to_json(
 	Extension(state): Extension<Arc<KbveState>>,
 	Extension(mut privatedata): Extension<TokenData<TokenJWT>>
 ) -> impl IntoResponse {
-	let mut conn = match state.db_pool.get() {
-		Ok(conn) => conn,
-		Err(e) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!("Failed to acquire database connection"),
-				e.to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
-			);
-			return error_response.into_response();
-		}
-	};
-
 	let user_id = match
 		ValidatorBuilder::<String, String>
 			::new()
@@ -220,14 +215,8 @@ pub async fn authorized_character_data_to_json(
 
 	let byte_ulid = match convert_ulid_string_to_bytes(&user_id) {
 		Ok(bytes) => bytes,
-		Err(e) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!({"error": "Failed to convert user_id to byte Ulid"}),
-				"ULID error or other error occurred".to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
-			);
-			return error_response.into_response();
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
 		}
 	};
 
@@ -245,16 +234,7 @@ pub async fn authorized_character_data_to_json(
             let message = json!("Characters retrieved successfully.");
             GenericResponse::new(data, message, StatusCode::OK).into_response()
         },
-        Err(e) => {
-            // If there was an error, return an error response
-            let error_message = format!("Error fetching characters: {}", e);
-            GenericResponse::error(
-                json!({}),
-                json!("Failed to retrieve characters"),
-                error_message,
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ).into_response()
-        }
+        Err(e) => e.into_response(),
     }
 }
 
@@ -263,21 +243,9 @@ pub async fn authorized_character_data_to_json(
 pub async fn character_creation_handler(
 	Extension(state): Extension<Arc<KbveState>>,
 	Extension(mut privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
 	Json(payload): Json<CharacterCreationRequest>
 ) -> impl IntoResponse {
-	let mut conn = match state.db_pool.get() {
-		Ok(conn) => conn,
-		Err(e) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!("Failed to acquire database connection"),
-				e.to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
-			);
-			return error_response.into_response();
-		}
-	};
-
 	let user_id = match
 		ValidatorBuilder::<String, String>
 			::new()
@@ -297,13 +265,9 @@ pub async fn character_creation_handler(
 		}
 	};
 
-	let name = match
-		ValidatorBuilder::<String, String>
-			::new()
-			.clean_or_fail()
-			.username()
-			.validate(payload.name)
-	{
+	let rules = CharacterRules::from_config();
+
+	let name = match rules.name_validator().validate(payload.name) {
 		Ok(name) => name,
 		Err(validation_error) => {
 			let error_response = GenericResponse::error(
@@ -335,9 +299,14 @@ pub async fn character_creation_handler(
 		}
 	};
 
-	// TODO - Integrate the General Input Regex for the Description.
+	let (class_name, class_stats) = match rules.class(payload.class.as_deref()) {
+		Some(class) => class,
+		None => {
+			return KbveError::Validation("unknown_class").into_response();
+		}
+	};
 
-	let _is_slot_open = match
+	match
 		hazardous_blocking_boolean_character_name_slot_open(
 			name.clone(),
 			state.db_pool.clone()
@@ -347,98 +316,527 @@ pub async fn character_creation_handler(
 			// The slot is open, proceed with character creation or other logic
 		}
 		Ok(false) => {
-			// The name is taken, handle accordingly (e.g., return an error response)
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!({"error": "Character name is already taken"}),
-				"Character name is not available".to_string(),
-				StatusCode::BAD_REQUEST
-			);
-			return error_response.into_response();
+			return KbveError::Conflict("name_taken").into_response();
 		}
-		Err(_) => {
-			// Handle database or other errors
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!({"error": "Failed to check character name availability"}),
-				"Database error or other error occurred".to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
-			);
-			return error_response.into_response();
+		Err(e) => {
+			return e.into_response();
 		}
-	};
+	}
 
 	let byte_ulid = match convert_ulid_string_to_bytes(&user_id) {
 		Ok(bytes) => bytes,
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
+		}
+	};
+
+	let clean_cid = match
+		hazardous_blocking_create_character_from_user(
+			name.clone(),
+			description.clone(),
+			class_stats,
+			byte_ulid.clone(),
+			rules,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(clean_cid) => clean_cid,
 		Err(e) => {
-			let error_response = GenericResponse::error(
+			return e.into_response();
+		}
+	};
+
+	audit_record(
+		AuditEvent::CharacterCreate { name: name.clone() },
+		AuditOutcome::Success,
+		Some(&user_id),
+		&audit
+	);
+
+	let success_response = GenericResponse::new(
+		json!({
+			"character_id": convert_ulid_bytes_to_string(&clean_cid).unwrap_or_default(),
+			"class": class_name,
+		}),
+		json!(
+			format!(
+				"Character {} created successfully, Name: {}, Description {},",
+				user_id,
+				name,
+				description
+			)
+		),
+		StatusCode::CREATED
+	);
+	success_response.into_response()
+}
+
+//	?	[Management]
+
+pub const CHARACTER_PAGE_SIZE: i64 = 20;
+
+#[derive(Deserialize)]
+pub struct CharacterUpdateRequest {
+	pub description: String,
+}
+
+#[derive(Deserialize)]
+pub struct CharacterSlotsGrantRequest {
+	pub bonus: u32,
+}
+
+#[derive(Deserialize)]
+pub struct CharacterRenameRequest {
+	pub name: String,
+}
+
+#[derive(Deserialize, Default)]
+pub struct CharacterListQuery {
+	pub page: Option<i64>,
+	pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterSort {
+	Name,
+	Experience,
+	Reputation,
+	Newest,
+}
+
+impl CharacterSort {
+	pub fn from_query(sort: Option<&str>) -> Result<Self, KbveError> {
+		match sort.map(|sort| sort.trim().to_lowercase()).as_deref() {
+			None | Some("") | Some("name") => Ok(CharacterSort::Name),
+			Some("experience") => Ok(CharacterSort::Experience),
+			Some("reputation") => Ok(CharacterSort::Reputation),
+			Some("newest") => Ok(CharacterSort::Newest),
+			_ => Err(KbveError::Validation("invalid_sort")),
+		}
+	}
+}
+
+//	What the public roster shows, the owner and the row id stay private.
+#[derive(Serialize)]
+pub struct CharacterSummary {
+	pub cid: String,
+	pub name: String,
+	pub description: String,
+	pub experience: i32,
+	pub level: u32,
+	pub reputation: i32,
+	pub faith: i32,
+}
+
+impl CharacterSummary {
+	fn from_row(row: Character, curve: &XpCurve) -> Option<Self> {
+		Some(CharacterSummary {
+			cid: convert_ulid_bytes_to_string(&row.cid).ok()?,
+			name: row.name,
+			description: row.description,
+			level: curve.level_for_experience(row.experience as i64),
+			experience: row.experience,
+			reputation: row.reputation,
+			faith: row.faith,
+		})
+	}
+}
+
+//	Every owner scoped write returns `false` when no character matched both `cid` and `userid`.
+pub async fn hazardous_blocking_update_character_description(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_description: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		update(
+			characters::table
+				.filter(characters::cid.eq(clean_cid))
+				.filter(characters::userid.eq(dirty_userid))
+		)
+			.set(characters::description.eq(clean_description))
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	The unique `name_idx` settles two renames racing for the same name.
+pub async fn hazardous_blocking_rename_character(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_name: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		update(
+			characters::table
+				.filter(characters::cid.eq(clean_cid))
+				.filter(characters::userid.eq(dirty_userid))
+		)
+			.set(characters::name.eq(clean_name))
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(|err| character_name_taken(KbveError::from(err)))
+	}).await
+}
+
+pub async fn hazardous_blocking_delete_character(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		//	The inventory goes with the character, it is keyed to the cid.
+		conn
+			.transaction::<bool, diesel::result::Error, _>(|conn| {
+				let Some(id) = db_for_update!(
+					characters::table
+						.filter(characters::cid.eq(&clean_cid))
+						.filter(characters::userid.eq(dirty_userid))
+						.select(characters::id)
+				)
+					.first::<RowId>(conn)
+					.optional()? else {
+					return Ok(false);
+				};
+
+				delete(
+					character_items::table.filter(character_items::cid.eq(&clean_cid))
+				).execute(conn)?;
+
+				delete(characters::table.filter(characters::id.eq(id))).execute(conn)?;
+
+				Ok(true)
+			})
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_list_characters(
+	sort: CharacterSort,
+	page: i64,
+	pool: Arc<Pool>
+) -> Result<(Vec<Character>, i64), KbveError> {
+	run_blocking(pool, move |conn| {
+		let total = characters::table.count().get_result::<i64>(conn)?;
+
+		let query = characters::table.into_boxed();
+
+		//	`id` breaks ties so pages never overlap.
+		let query = match sort {
+			CharacterSort::Name => query.order(characters::name.asc()),
+			CharacterSort::Experience =>
+				query.order((characters::experience.desc(), characters::id.asc())),
+			CharacterSort::Reputation =>
+				query.order((characters::reputation.desc(), characters::id.asc())),
+			CharacterSort::Newest => query.order(characters::id.desc()),
+		};
+
+		let rows = query
+			.limit(CHARACTER_PAGE_SIZE)
+			.offset((page - 1).saturating_mul(CHARACTER_PAGE_SIZE))
+			.load::<Character>(conn)?;
+
+		Ok((rows, total))
+	}).await
+}
+
+//	The unique `name_idx` answers a name lost to a concurrent create or rename.
+fn character_name_taken(error: KbveError) -> KbveError {
+	match error {
+		KbveError::Conflict(_) => KbveError::Conflict("name_taken"),
+		error => error,
+	}
+}
+
+//	Resolves the caller's userid and the `cid` path segment to bytes.
+fn character_owner_and_cid(
+	privatedata: &TokenData<TokenJWT>,
+	cid: &str
+) -> Result<(Vec<u8>, Vec<u8>), KbveError> {
+	let owner = convert_ulid_string_to_bytes(&privatedata.claims.userid).map_err(|_|
+		KbveError::Validation("invalid_ulid")
+	)?;
+
+	let cid = convert_ulid_string_to_bytes(cid.trim()).map_err(|_|
+		KbveError::Validation("invalid_ulid")
+	)?;
+
+	Ok((owner, cid))
+}
+
+pub async fn character_update_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(cid): Path<String>,
+	Json(payload): Json<CharacterUpdateRequest>
+) -> impl IntoResponse {
+	let (owner, clean_cid) = match character_owner_and_cid(&privatedata, &cid) {
+		Ok(ids) => ids,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let description = match
+		ValidatorBuilder::<String, String>
+			::new()
+			.clean_or_fail()
+			.general_input()
+			.validate(payload.description)
+	{
+		Ok(description) => description,
+		Err(validation_error) => {
+			return GenericResponse::error(
 				json!({}),
-				json!({"error": "Failed to convert user_id to byte Ulid"}),
-				"ULID error or other error occurred".to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
+				json!({"error": "Character Description Validation failed", "details": validation_error}),
+				validation_error.join(", "),
+				StatusCode::BAD_REQUEST
+			).into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_update_character_description(
+			clean_cid,
+			owner,
+			description.clone(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) => {
+			audit_record(
+				AuditEvent::CharacterUpdate { cid: cid.trim().to_string() },
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
 			);
-			return error_response.into_response();
+
+			GenericResponse::new(
+				json!({"cid": cid.trim(), "description": description}),
+				json!("Character updated."),
+				StatusCode::OK
+			).into_response()
+		}
+		Ok(false) => KbveError::NotFound("character_not_found").into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn character_rename_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(cid): Path<String>,
+	Json(payload): Json<CharacterRenameRequest>
+) -> impl IntoResponse {
+	let (owner, clean_cid) = match character_owner_and_cid(&privatedata, &cid) {
+		Ok(ids) => ids,
+		Err(e) => {
+			return e.into_response();
 		}
 	};
 
-	let check_character_limit = hazardous_blocking_check_user_character_limit(
-		byte_ulid.clone(),
-		state.db_pool.clone()
-	).await;
+	let name = match CharacterRules::from_config().name_validator().validate(payload.name) {
+		Ok(name) => name,
+		Err(validation_error) => {
+			return GenericResponse::error(
+				json!({}),
+				json!({"error": "Character Name Validation failed", "details": validation_error}),
+				validation_error.join(", "),
+				StatusCode::BAD_REQUEST
+			).into_response();
+		}
+	};
 
-	match check_character_limit {
+	match
+		hazardous_blocking_boolean_character_name_slot_open(
+			name.clone(),
+			state.db_pool.clone()
+		).await
+	{
 		Ok(true) => {}
 		Ok(false) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!({"error": "Hard limit of 3 Characters per account"}),
-				"Hard limit of 3 Characters per account".to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
-			);
-			return error_response.into_response();
+			return KbveError::Conflict("name_taken").into_response();
 		}
 		Err(e) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!("Failed to acquire database connection"),
-				e.to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
+			return e.into_response();
+		}
+	}
+
+	match
+		hazardous_blocking_rename_character(
+			clean_cid,
+			owner,
+			name.clone(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) => {
+			audit_record(
+				AuditEvent::CharacterRename { cid: cid.trim().to_string(), name: name.clone() },
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
 			);
-			return error_response.into_response();
+
+			GenericResponse::new(
+				json!({"cid": cid.trim(), "name": name}),
+				json!("Character renamed."),
+				StatusCode::OK
+			).into_response()
 		}
+		Ok(false) => KbveError::NotFound("character_not_found").into_response(),
+		Err(e) => e.into_response(),
 	}
+}
 
-	let creation_result = hazardous_blocking_create_character_from_user(
-		name.clone(),
-		description.clone(),
-		byte_ulid.clone(),
-		state.db_pool.clone()
-	).await;
+pub async fn character_delete_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(cid): Path<String>
+) -> impl IntoResponse {
+	let (owner, clean_cid) = match character_owner_and_cid(&privatedata, &cid) {
+		Ok(ids) => ids,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
 
-	match creation_result {
-		Ok(_) => {}
-		Err(error_message) => {
-			let error_response = GenericResponse::error(
-				json!({}),
-				json!({"error": "Failed to create character within the database"}),
-				"Character creation has failed!".to_string(),
-				StatusCode::INTERNAL_SERVER_ERROR
+	match hazardous_blocking_delete_character(clean_cid, owner, state.db_pool.clone()).await {
+		Ok(true) => {
+			audit_record(
+				AuditEvent::CharacterDelete { cid: cid.trim().to_string() },
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
 			);
-			return error_response.into_response();
+
+			GenericResponse::new(
+				json!({"cid": cid.trim()}),
+				json!("Character deleted."),
+				StatusCode::OK
+			).into_response()
 		}
+		Ok(false) => KbveError::NotFound("character_not_found").into_response(),
+		Err(e) => e.into_response(),
 	}
+}
 
-	let success_response = GenericResponse::new(
-		json!({"character_id": "some_character_id"}), // Example success data
-		json!(
-			format!(
-				"Character {} created successfully, Name: {}, Description {},",
-				user_id,
-				name,
-				description
-			)
-		),
-		StatusCode::CREATED
+//	Public roster, `page` starts at 1 and `sort` is `name`, `experience`, `reputation` or `newest`.
+pub async fn character_list_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Query(query): Query<CharacterListQuery>
+) -> impl IntoResponse {
+	let sort = match CharacterSort::from_query(query.sort.as_deref()) {
+		Ok(sort) => sort,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let page = query.page.unwrap_or(1).max(1);
+
+	match hazardous_blocking_list_characters(sort, page, state.db_pool.clone()).await {
+		Ok((rows, total)) => {
+			let curve = XpCurve::from_config();
+			let characters: Vec<CharacterSummary> = rows
+				.into_iter()
+				.filter_map(|row| CharacterSummary::from_row(row, &curve))
+				.collect();
+
+			GenericResponse::new(
+				json!({
+					"characters": characters,
+					"page": page,
+					"per_page": CHARACTER_PAGE_SIZE,
+					"total": total,
+				}),
+				json!("Characters retrieved successfully."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+//	Public, lets a client offer the classes before creation.
+pub async fn character_classes_handler() -> impl IntoResponse {
+	let rules = CharacterRules::from_config();
+
+	GenericResponse::new(
+		json!({
+			"classes": rules.classes,
+			"default": rules.default_class,
+		}),
+		json!("Character classes."),
+		StatusCode::OK
+	).into_response()
+}
+
+//	Extra slots on top of the role limit, a bonus of 0 removes the grant.
+pub async fn admin_character_slots_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(userid): Path<String>,
+	Json(payload): Json<CharacterSlotsGrantRequest>
+) -> impl IntoResponse {
+	let target = match convert_ulid_string_to_bytes(userid.trim()) {
+		Ok(target) => target,
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
+		}
+	};
+
+	if payload.bonus > CHARACTER_SLOTS_LIMIT {
+		return KbveError::Validation("invalid_bonus").into_response();
+	}
+
+	let role = match hazardous_blocking_role_by_userid(target.clone(), state.db_pool.clone()).await {
+		Ok(role) => role,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let stored = if payload.bonus == 0 {
+		hazardous_blocking_setting_delete(
+			target,
+			CHARACTER_SLOTS_BONUS_KEY.to_string(),
+			state.db_pool.clone()
+		).await
+	} else {
+		hazardous_blocking_setting_upsert(
+			target,
+			CHARACTER_SLOTS_BONUS_KEY.to_string(),
+			payload.bonus.to_string(),
+			state.db_pool.clone()
+		).await
+	};
+
+	if let Err(e) = stored {
+		return e.into_response();
+	}
+
+	audit_record(
+		AuditEvent::CharacterSlotsGrant { userid: userid.trim().to_string(), bonus: payload.bonus },
+		AuditOutcome::Success,
+		Some(&privatedata.claims.userid),
+		&audit
 	);
-	success_response.into_response()
+
+	GenericResponse::new(
+		json!({
+			"userid": userid.trim(),
+			"bonus": payload.bonus,
+			"limit": CharacterRules::from_config().slot_limit(role, payload.bonus),
+		}),
+		json!("Character slots updated."),
+		StatusCode::OK
+	).into_response()
 }
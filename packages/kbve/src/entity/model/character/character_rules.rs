@@ -0,0 +1,289 @@
+use std::collections::{ BTreeMap };
+
+use jedi::builder::ValidatorBuilder;
+
+use crate::session::{ Role };
+
+use crate::entity::{ CoreStats };
+
+//	?	[Rules]
+
+/**
+	Character creation rules, read from the config so they change without a redeploy.
+
+	Slots: `character_slots_player`, `character_slots_supporter`, `character_slots_moderator`
+	and `character_slots_admin` set the limit per `users.role`. A single user can be granted
+	extra slots on top through the `character_slots_bonus` setting, which only admins write.
+
+	Classes: the starting core stats picked at creation. `character_class_<name>` adds or
+	replaces a class as `armour,agility,strength,intelligence`, `character_class_default`
+	names the one used when the request picks none.
+
+	Names: `character_reserved_names` and `character_blocked_words` are comma separated and
+	extend the built in lists. Names are folded (lowercase, common digit swaps undone, only
+	letters kept) before the check, reserved names must match whole, blocked words anywhere.
+	A reserved name is also caught with its digits dropped instead of swapped.
+**/
+
+pub const CHARACTER_SLOTS_LIMIT: u32 = 50;
+pub const CHARACTER_SLOTS_BONUS_KEY: &str = "character_slots_bonus";
+pub const CHARACTER_CLASS_STAT_MAX: i32 = 20;
+pub const CHARACTER_CLASS_NAME_MAX: usize = 32;
+
+//	Player, supporter, moderator, admin, in `Role` order.
+pub const CHARACTER_SLOTS_DEFAULT: [u32; 4] = [3, 4, 5, 10];
+
+pub const CHARACTER_CLASS_DEFAULT: &str = "adventurer";
+
+//	`(name, armour, agility, strength, intelligence)`, every class starts with the same total.
+pub const CHARACTER_CLASSES: &[(&str, i32, i32, i32, i32)] = &[
+	("adventurer", 1, 1, 1, 1),
+	("warrior", 2, 0, 2, 0),
+	("rogue", 0, 3, 1, 0),
+	("mage", 0, 1, 0, 3),
+];
+
+pub const CHARACTER_RESERVED_NAMES: &[&str] = &[
+	"admin",
+	"administrator",
+	"moderator",
+	"mod",
+	"kbve",
+	"system",
+	"support",
+	"staff",
+	"root",
+	"null",
+	"undefined",
+	"server",
+];
+
+pub const CHARACTER_BLOCKED_WORDS: &[&str] = &["fuck", "shit", "cunt", "bitch", "whore", "nazi"];
+
+const CHARACTER_SLOT_KEYS: [&str; 4] = [
+	"character_slots_player",
+	"character_slots_supporter",
+	"character_slots_moderator",
+	"character_slots_admin",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterRules {
+	pub slots: [u32; 4],
+	pub classes: BTreeMap<String, CoreStats>,
+	pub default_class: String,
+	pub reserved_names: Vec<String>,
+	pub blocked_words: Vec<String>,
+}
+
+impl Default for CharacterRules {
+	fn default() -> Self {
+		CharacterRules {
+			slots: CHARACTER_SLOTS_DEFAULT,
+			classes: CHARACTER_CLASSES.iter()
+				.map(|(name, armour, agility, strength, intelligence)| {
+					(
+						name.to_string(),
+						CoreStats {
+							armour: *armour,
+							agility: *agility,
+							strength: *strength,
+							intelligence: *intelligence,
+						},
+					)
+				})
+				.collect(),
+			default_class: CHARACTER_CLASS_DEFAULT.to_string(),
+			reserved_names: CHARACTER_RESERVED_NAMES.iter()
+				.map(|name| character_name_fold(name))
+				.collect(),
+			blocked_words: CHARACTER_BLOCKED_WORDS.iter()
+				.map(|word| character_name_fold(word))
+				.collect(),
+		}
+	}
+}
+
+//	Lowercase, `0 1 3 4 5 7 @ $` read as `o i e a s t a s`, everything but letters dropped.
+pub fn character_name_fold(name: &str) -> String {
+	name.chars()
+		.map(|c| {
+			match c.to_ascii_lowercase() {
+				'0' => 'o',
+				'1' => 'i',
+				'3' => 'e',
+				'4' | '@' => 'a',
+				'5' | '$' => 's',
+				'7' => 't',
+				c => c,
+			}
+		})
+		.filter(|c| c.is_ascii_lowercase())
+		.collect()
+}
+
+fn character_class_parse(value: &str) -> Result<CoreStats, String> {
+	let parts: Vec<i32> = value
+		.split(',')
+		.map(|part| part.trim().parse::<i32>())
+		.collect::<Result<_, _>>()
+		.map_err(|_| format!("expected armour,agility,strength,intelligence, got {}", value))?;
+
+	let [armour, agility, strength, intelligence] = parts[..] else {
+		return Err(format!("expected four stats, got {}", value));
+	};
+
+	if
+		parts.iter().any(|stat| !(0..=CHARACTER_CLASS_STAT_MAX).contains(stat)) ||
+		parts.iter().sum::<i32>() < 1
+	{
+		return Err(
+			format!("stats must be 0 to {} and not all 0, got {}", CHARACTER_CLASS_STAT_MAX, value)
+		);
+	}
+
+	Ok(CoreStats { armour, agility, strength, intelligence })
+}
+
+impl CharacterRules {
+	//	Builds the rules from merged config values, every invalid key is reported as `(key, reason)`.
+	pub fn from_values(values: &BTreeMap<String, String>) -> Result<Self, Vec<(String, String)>> {
+		let mut rules = CharacterRules::default();
+		let mut invalid: Vec<(String, String)> = Vec::new();
+
+		let get = |key: &str| {
+			values
+				.get(key)
+				.map(|value| value.trim())
+				.filter(|value| !value.is_empty())
+		};
+
+		for (index, key) in CHARACTER_SLOT_KEYS.iter().enumerate() {
+			if let Some(value) = get(key) {
+				match value.parse::<u32>() {
+					Ok(slots) if (1..=CHARACTER_SLOTS_LIMIT).contains(&slots) => {
+						rules.slots[index] = slots;
+					}
+					_ =>
+						invalid.push((
+							key.to_string(),
+							format!("expected 1 to {}, got {}", CHARACTER_SLOTS_LIMIT, value),
+						)),
+				}
+			}
+		}
+
+		for (key, value) in values.range("character_class_".to_string()..) {
+			let Some(name) = key.strip_prefix("character_class_") else {
+				break;
+			};
+
+			if name == "default" || value.trim().is_empty() {
+				continue;
+			}
+
+			if
+				name.len() > CHARACTER_CLASS_NAME_MAX ||
+				!name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+			{
+				invalid.push((key.clone(), "class names are lowercase a-z, 0-9 and _".to_string()));
+				continue;
+			}
+
+			match character_class_parse(value.trim()) {
+				Ok(stats) => {
+					rules.classes.insert(name.to_string(), stats);
+				}
+				Err(reason) => invalid.push((key.clone(), reason)),
+			}
+		}
+
+		if let Some(value) = get("character_class_default") {
+			if rules.classes.contains_key(value) {
+				rules.default_class = value.to_string();
+			} else {
+				invalid.push((
+					"character_class_default".to_string(),
+					format!("no class named {}", value),
+				));
+			}
+		}
+
+		let list = |key: &str| -> Vec<String> {
+			get(key)
+				.map(|value| {
+					value
+						.split(',')
+						.map(character_name_fold)
+						.filter(|entry| !entry.is_empty())
+						.collect()
+				})
+				.unwrap_or_default()
+		};
+
+		rules.reserved_names.extend(list("character_reserved_names"));
+		rules.blocked_words.extend(list("character_blocked_words"));
+
+		if invalid.is_empty() {
+			Ok(rules)
+		} else {
+			Err(invalid)
+		}
+	}
+
+	//	Registered through `entity_config_register`, rejects a config the rules can not be built from.
+	pub fn config_check(values: &BTreeMap<String, String>) -> Vec<(String, String)> {
+		CharacterRules::from_values(values).err().unwrap_or_default()
+	}
+
+	//	The current rules, the config is validated on load so the defaults only cover a missing config.
+	pub fn from_config() -> Self {
+		crate::config
+			::kbve_config()
+			.and_then(|config| CharacterRules::from_values(config.values()).ok())
+			.unwrap_or_default()
+	}
+
+	pub fn slot_limit(&self, role: Role, bonus: u32) -> u32 {
+		self.slots[role as usize].saturating_add(bonus).min(CHARACTER_SLOTS_LIMIT)
+	}
+
+	//	`None` picks the default class.
+	pub fn class(&self, name: Option<&str>) -> Option<(String, CoreStats)> {
+		let name = name
+			.map(|name| name.trim().to_lowercase())
+			.filter(|name| !name.is_empty())
+			.unwrap_or_else(|| self.default_class.clone());
+
+		self.classes.get(&name).map(|stats| (name, *stats))
+	}
+
+	//	Shared by creation and rename, the username format first and the name lists after.
+	pub fn name_validator(&self) -> ValidatorBuilder<String, String> {
+		let mut validator = ValidatorBuilder::<String, String>::new();
+		validator.clean_or_fail().username();
+
+		let reserved = self.reserved_names.clone();
+		let blocked = self.blocked_words.clone();
+
+		validator.add_rule(move |name: String| {
+			let folded = character_name_fold(&name);
+			//	Catches a reserved name with digits tacked on, `Admin99`.
+			let letters: String = name
+				.chars()
+				.filter(|c| c.is_ascii_alphabetic())
+				.map(|c| c.to_ascii_lowercase())
+				.collect();
+
+			if reserved.contains(&folded) || reserved.contains(&letters) {
+				Err("Character name is reserved".to_string())
+			} else if blocked.iter().any(|word| folded.contains(word.as_str())) {
+				Err("Character name is not allowed".to_string())
+			} else {
+				Ok(name)
+			}
+		});
+
+		validator
+	}
+}
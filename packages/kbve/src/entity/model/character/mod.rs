@@ -1,3 +1,5 @@
 pub mod character_handler;
+pub mod character_rules;
 
-pub use character_handler::*;
\ No newline at end of file
+pub use character_handler::*;
+pub use character_rules::*;
\ No newline at end of file
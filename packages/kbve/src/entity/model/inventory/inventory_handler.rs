@@ -0,0 +1,713 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path, Query },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::{ insert_into, update, delete };
+
+use crate::db::{ DbConnection, Pool, RowId, db_for_update, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ character_items, characters, items };
+
+use crate::models::{ Character, CharacterItem, Item };
+
+use crate::session::{ KbveState, TokenJWT, TokenAPIKey };
+
+use crate::response::{ GenericResponse };
+
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
+use crate::entity::{
+	CoreStats,
+	InventoryEntry,
+	ItemSlot,
+	INVENTORY_QUANTITY_MAX,
+	INVENTORY_SIZE_MAX,
+	hazardous_equipment_bonus,
+	hazardous_inventory_entries,
+};
+
+use crate::spellbook_generate_ulid_bytes;
+
+use crate::utility::{ convert_ulid_string_to_bytes, convert_ulid_bytes_to_string };
+
+use jsonwebtoken::TokenData;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+#[derive(Deserialize)]
+pub struct InventoryDropQuery {
+	//	Missing drops the whole stack.
+	pub quantity: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct InventoryTransferRequest {
+	pub to: String,
+	//	Missing moves the whole stack.
+	pub quantity: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct InventoryGrantRequest {
+	pub item: String,
+	#[serde(default = "inventory_grant_default")]
+	pub quantity: i32,
+}
+
+fn inventory_grant_default() -> i32 {
+	1
+}
+
+#[derive(Serialize)]
+pub struct InventoryView {
+	pub items: Vec<InventoryEntry>,
+	//	Summed modifiers of the equipped items.
+	pub bonus: CoreStats,
+	//	Base stats plus `bonus`, what the vitals are derived from.
+	pub effective: CoreStats,
+}
+
+//	?	[Hazardous]
+
+//	A rule failure is an error too, so the transaction rolls back whatever it already wrote.
+fn inventory_transaction<T>(
+	conn: &mut DbConnection,
+	work: impl FnOnce(&mut DbConnection) -> Result<T, KbveError>
+) -> Result<T, KbveError> {
+	conn.transaction(work)
+}
+
+//	Every inventory write locks the character row first, so writes to one character never interleave.
+fn inventory_lock_character(
+	conn: &mut DbConnection,
+	clean_cid: &[u8],
+	owner: Option<&[u8]>
+) -> Result<(), KbveError> {
+	let query = characters::table.filter(characters::cid.eq(clean_cid)).select(characters::id);
+
+	let locked = match owner {
+		Some(owner) =>
+			db_for_update!(query.filter(characters::userid.eq(owner)))
+				.first::<RowId>(conn)
+				.optional()?,
+		None => db_for_update!(query).first::<RowId>(conn).optional()?,
+	};
+
+	locked.map(|_| ()).ok_or(KbveError::NotFound("character_not_found"))
+}
+
+fn inventory_instance(
+	conn: &mut DbConnection,
+	clean_ulid: &[u8],
+	clean_cid: &[u8]
+) -> Result<CharacterItem, KbveError> {
+	db_for_update!(
+		character_items::table
+			.filter(character_items::ulid.eq(clean_ulid))
+			.filter(character_items::cid.eq(clean_cid))
+	)
+		.first::<CharacterItem>(conn)
+		.optional()?
+		.ok_or(KbveError::NotFound("item_not_found"))
+}
+
+fn inventory_definition(
+	conn: &mut DbConnection,
+	clean_itemid: &[u8]
+) -> Result<Item, KbveError> {
+	items::table
+		.filter(items::ulid.eq(clean_itemid))
+		.first::<Item>(conn)
+		.optional()?
+		.ok_or(KbveError::NotFound("item_not_found"))
+}
+
+fn inventory_held(conn: &mut DbConnection, clean_cid: &[u8]) -> Result<i64, KbveError> {
+	Ok(
+		character_items::table
+			.filter(character_items::cid.eq(clean_cid))
+			.count()
+			.get_result::<i64>(conn)?
+	)
+}
+
+fn inventory_insert(
+	conn: &mut DbConnection,
+	clean_cid: &[u8],
+	clean_itemid: &[u8],
+	quantity: i32,
+	slot: Option<ItemSlot>
+) -> Result<(), KbveError> {
+	insert_into(character_items::table)
+		.values((
+			character_items::ulid.eq(spellbook_generate_ulid_bytes!()),
+			character_items::cid.eq(clean_cid),
+			character_items::itemid.eq(clean_itemid),
+			character_items::quantity.eq(quantity),
+			character_items::slot.eq(slot.map(|slot| slot.as_str())),
+		))
+		.execute(conn)?;
+
+	Ok(())
+}
+
+//	Tops up the bag stacks of the item first, the rest goes into new stacks.
+fn inventory_add(
+	conn: &mut DbConnection,
+	clean_cid: &[u8],
+	item: &Item,
+	quantity: i32
+) -> Result<(), KbveError> {
+	let limit = item.stack_limit();
+	let mut remaining = quantity;
+
+	if limit > 1 {
+		let stacks = character_items::table
+			.filter(character_items::cid.eq(clean_cid))
+			.filter(character_items::itemid.eq(&item.ulid))
+			.filter(character_items::slot.is_null())
+			.filter(character_items::quantity.lt(limit))
+			.order(character_items::id.asc())
+			.load::<CharacterItem>(conn)?;
+
+		for stack in stacks {
+			if remaining == 0 {
+				break;
+			}
+
+			let take = (limit - stack.quantity).min(remaining);
+
+			update(character_items::table.filter(character_items::id.eq(stack.id)))
+				.set(character_items::quantity.eq(stack.quantity + take))
+				.execute(conn)?;
+
+			remaining -= take;
+		}
+	}
+
+	if remaining == 0 {
+		return Ok(());
+	}
+
+	let needed = ((remaining + limit - 1) / limit) as i64;
+
+	if inventory_held(conn, clean_cid)? + needed > INVENTORY_SIZE_MAX {
+		return Err(KbveError::Conflict("inventory_full"));
+	}
+
+	while remaining > 0 {
+		let take = remaining.min(limit);
+		inventory_insert(conn, clean_cid, &item.ulid, take, None)?;
+		remaining -= take;
+	}
+
+	Ok(())
+}
+
+pub async fn hazardous_blocking_inventory(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<InventoryView, KbveError> {
+	run_blocking(pool, move |conn| {
+		let character = characters::table
+			.filter(characters::cid.eq(&clean_cid))
+			.filter(characters::userid.eq(dirty_userid))
+			.first::<Character>(conn)
+			.optional()?
+			.ok_or(KbveError::NotFound("character_not_found"))?;
+
+		let entries = hazardous_inventory_entries(conn, &clean_cid)?;
+		let bonus = hazardous_equipment_bonus(conn, &clean_cid)?;
+
+		Ok(InventoryView {
+			items: entries,
+			bonus,
+			effective: CoreStats::effective(&character, bonus),
+		})
+	}).await
+}
+
+//	Only what the character wears, no owner check, the character sheet is public.
+pub async fn hazardous_blocking_inventory_equipped(
+	clean_cid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Vec<InventoryEntry>, KbveError> {
+	run_blocking(pool, move |conn| {
+		hazardous_inventory_entries(conn, &clean_cid)
+			.map(|entries| {
+				entries
+					.into_iter()
+					.filter(|entry| entry.equipped.is_some())
+					.collect()
+			})
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Swaps out whatever was worn in the slot, a stack gives up one item to be worn.
+pub async fn hazardous_blocking_inventory_equip(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<(), KbveError> {
+	run_blocking(pool, move |conn| {
+		inventory_transaction(conn, |conn| {
+			inventory_lock_character(conn, &clean_cid, Some(&dirty_userid))?;
+
+			let instance = inventory_instance(conn, &clean_ulid, &clean_cid)?;
+			let item = inventory_definition(conn, &instance.itemid)?;
+
+			let slot = ItemSlot::from_column(&item.slot).ok_or(
+				KbveError::Validation("not_equippable")
+			)?;
+
+			if instance.slot.as_deref() == Some(slot.as_str()) {
+				return Ok(());
+			}
+
+			update(
+				character_items::table
+					.filter(character_items::cid.eq(&clean_cid))
+					.filter(character_items::slot.eq(slot.as_str()))
+			)
+				.set(character_items::slot.eq(None::<String>))
+				.execute(conn)?;
+
+			if instance.quantity > 1 {
+				if inventory_held(conn, &clean_cid)? >= INVENTORY_SIZE_MAX {
+					return Err(KbveError::Conflict("inventory_full"));
+				}
+
+				update(character_items::table.filter(character_items::id.eq(instance.id)))
+					.set(character_items::quantity.eq(instance.quantity - 1))
+					.execute(conn)?;
+
+				inventory_insert(conn, &clean_cid, &instance.itemid, 1, Some(slot))?;
+			} else {
+				update(character_items::table.filter(character_items::id.eq(instance.id)))
+					.set(character_items::slot.eq(slot.as_str()))
+					.execute(conn)?;
+			}
+
+			Ok(())
+		})
+	}).await
+}
+
+pub async fn hazardous_blocking_inventory_unequip(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<(), KbveError> {
+	run_blocking(pool, move |conn| {
+		inventory_transaction(conn, |conn| {
+			inventory_lock_character(conn, &clean_cid, Some(&dirty_userid))?;
+
+			let instance = inventory_instance(conn, &clean_ulid, &clean_cid)?;
+
+			if instance.slot.is_some() {
+				update(character_items::table.filter(character_items::id.eq(instance.id)))
+					.set(character_items::slot.eq(None::<String>))
+					.execute(conn)?;
+			}
+
+			Ok(())
+		})
+	}).await
+}
+
+//	`None` or a quantity covering the whole stack removes the instance.
+pub async fn hazardous_blocking_inventory_drop(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_ulid: Vec<u8>,
+	quantity: Option<i32>,
+	pool: Arc<Pool>
+) -> Result<(), KbveError> {
+	run_blocking(pool, move |conn| {
+		inventory_transaction(conn, |conn| {
+			inventory_lock_character(conn, &clean_cid, Some(&dirty_userid))?;
+
+			let instance = inventory_instance(conn, &clean_ulid, &clean_cid)?;
+
+			match quantity {
+				Some(quantity) if quantity < instance.quantity => {
+					update(character_items::table.filter(character_items::id.eq(instance.id)))
+						.set(character_items::quantity.eq(instance.quantity - quantity))
+						.execute(conn)?;
+				}
+				_ => {
+					delete(
+						character_items::table.filter(character_items::id.eq(instance.id))
+					).execute(conn)?;
+				}
+			}
+
+			Ok(())
+		})
+	}).await
+}
+
+//	Both characters must belong to `dirty_userid`, returns the item ulid and the quantity moved.
+pub async fn hazardous_blocking_inventory_transfer(
+	clean_cid: Vec<u8>,
+	clean_target: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	clean_ulid: Vec<u8>,
+	quantity: Option<i32>,
+	pool: Arc<Pool>
+) -> Result<(Vec<u8>, i32), KbveError> {
+	run_blocking(pool, move |conn| {
+		if clean_cid == clean_target {
+			return Err(KbveError::Validation("same_character"));
+		}
+
+		inventory_transaction(conn, |conn| {
+			//	Both rows in one statement, locked in index order.
+			let locked = db_for_update!(
+				characters::table
+					.filter(characters::cid.eq_any([&clean_cid, &clean_target]))
+					.filter(characters::userid.eq(&dirty_userid))
+					.select(characters::id)
+					.order(characters::id.asc())
+			).load::<RowId>(conn)?;
+
+			if locked.len() != 2 {
+				return Err(KbveError::NotFound("character_not_found"));
+			}
+
+			let instance = inventory_instance(conn, &clean_ulid, &clean_cid)?;
+			let moving = quantity.unwrap_or(instance.quantity);
+
+			if moving > instance.quantity {
+				return Err(KbveError::Conflict("not_enough_items"));
+			}
+
+			let item = inventory_definition(conn, &instance.itemid)?;
+
+			if moving == instance.quantity {
+				delete(
+					character_items::table.filter(character_items::id.eq(instance.id))
+				).execute(conn)?;
+			} else {
+				update(character_items::table.filter(character_items::id.eq(instance.id)))
+					.set(character_items::quantity.eq(instance.quantity - moving))
+					.execute(conn)?;
+			}
+
+			inventory_add(conn, &clean_target, &item, moving)?;
+
+			Ok((item.ulid, moving))
+		})
+	}).await
+}
+
+//	Service grant, no owner check.
+pub async fn hazardous_blocking_inventory_grant(
+	clean_cid: Vec<u8>,
+	clean_itemid: Vec<u8>,
+	quantity: i32,
+	pool: Arc<Pool>
+) -> Result<(), KbveError> {
+	run_blocking(pool, move |conn| {
+		inventory_transaction(conn, |conn| {
+			inventory_lock_character(conn, &clean_cid, None)?;
+
+			let item = inventory_definition(conn, &clean_itemid)?;
+
+			inventory_add(conn, &clean_cid, &item, quantity)
+		})
+	}).await
+}
+
+//	?	[Handlers]
+
+fn inventory_ulid(value: &str) -> Result<Vec<u8>, KbveError> {
+	convert_ulid_string_to_bytes(value.trim()).map_err(|_| KbveError::Validation("invalid_ulid"))
+}
+
+fn inventory_quantity(quantity: Option<i32>) -> Result<Option<i32>, KbveError> {
+	match quantity {
+		Some(quantity) if !(1..=INVENTORY_QUANTITY_MAX).contains(&quantity) =>
+			Err(KbveError::Validation("invalid_quantity")),
+		_ => Ok(quantity),
+	}
+}
+
+//	The character id, the item instance id and the owner, all as bytes.
+struct InventoryTarget {
+	clean_cid: Vec<u8>,
+	clean_ulid: Vec<u8>,
+	owner: Vec<u8>,
+}
+
+fn inventory_target(
+	cid: &str,
+	ulid: Option<&str>,
+	privatedata: &TokenData<TokenJWT>
+) -> Result<InventoryTarget, KbveError> {
+	let clean_cid = inventory_ulid(cid)?;
+	let clean_ulid = match ulid {
+		Some(ulid) => inventory_ulid(ulid)?,
+		None => Vec::new(),
+	};
+	let owner = inventory_ulid(&privatedata.claims.userid)?;
+
+	Ok(InventoryTarget { clean_cid, clean_ulid, owner })
+}
+
+//	Every player write answers with the inventory as it is afterwards.
+async fn inventory_response(
+	state: &KbveState,
+	cid: &str,
+	clean_cid: Vec<u8>,
+	owner: Vec<u8>,
+	message: &str
+) -> axum::response::Response {
+	match hazardous_blocking_inventory(clean_cid, owner, state.db_pool.clone()).await {
+		Ok(view) =>
+			GenericResponse::new(
+				json!({ "cid": cid.trim(), "inventory": view }),
+				json!(message),
+				StatusCode::OK
+			).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn inventory_list_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path(cid): Path<String>
+) -> impl IntoResponse {
+	let InventoryTarget { clean_cid, owner, .. } = match
+		inventory_target(&cid, None, &privatedata)
+	{
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	inventory_response(&state, &cid, clean_cid, owner, "Character inventory.").await
+}
+
+pub async fn inventory_equip_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path((cid, ulid)): Path<(String, String)>
+) -> impl IntoResponse {
+	let InventoryTarget { clean_cid, clean_ulid, owner } = match
+		inventory_target(&cid, Some(&ulid), &privatedata)
+	{
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_inventory_equip(
+			clean_cid.clone(),
+			owner.clone(),
+			clean_ulid,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(()) => inventory_response(&state, &cid, clean_cid, owner, "Item equipped.").await,
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn inventory_unequip_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path((cid, ulid)): Path<(String, String)>
+) -> impl IntoResponse {
+	let InventoryTarget { clean_cid, clean_ulid, owner } = match
+		inventory_target(&cid, Some(&ulid), &privatedata)
+	{
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_inventory_unequip(
+			clean_cid.clone(),
+			owner.clone(),
+			clean_ulid,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(()) => inventory_response(&state, &cid, clean_cid, owner, "Item unequipped.").await,
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn inventory_drop_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path((cid, ulid)): Path<(String, String)>,
+	Query(query): Query<InventoryDropQuery>
+) -> impl IntoResponse {
+	let InventoryTarget { clean_cid, clean_ulid, owner } = match
+		inventory_target(&cid, Some(&ulid), &privatedata)
+	{
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let quantity = match inventory_quantity(query.quantity) {
+		Ok(quantity) => quantity,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_inventory_drop(
+			clean_cid.clone(),
+			owner.clone(),
+			clean_ulid,
+			quantity,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(()) => inventory_response(&state, &cid, clean_cid, owner, "Item dropped.").await,
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn inventory_transfer_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path((cid, ulid)): Path<(String, String)>,
+	Json(payload): Json<InventoryTransferRequest>
+) -> impl IntoResponse {
+	let InventoryTarget { clean_cid, clean_ulid, owner } = match
+		inventory_target(&cid, Some(&ulid), &privatedata)
+	{
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let clean_target = match inventory_ulid(&payload.to) {
+		Ok(target) => target,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let quantity = match inventory_quantity(payload.quantity) {
+		Ok(quantity) => quantity,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_inventory_transfer(
+			clean_cid.clone(),
+			clean_target,
+			owner.clone(),
+			clean_ulid,
+			quantity,
+			state.db_pool.clone()
+		).await
+	{
+		Ok((itemid, moved)) => {
+			audit_record(
+				AuditEvent::ItemTransfer {
+					from: cid.trim().to_string(),
+					to: payload.to.trim().to_string(),
+					item: convert_ulid_bytes_to_string(&itemid).unwrap_or_default(),
+					quantity: moved,
+				},
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
+			);
+
+			inventory_response(&state, &cid, clean_cid, owner, "Item transferred.").await
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+//	Service endpoint behind an API key holding `character:items`, works on any character.
+pub async fn inventory_grant_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(claims): Extension<TokenAPIKey>,
+	audit: AuditContext,
+	Path(cid): Path<String>,
+	Json(payload): Json<InventoryGrantRequest>
+) -> impl IntoResponse {
+	let clean_cid = match inventory_ulid(&cid) {
+		Ok(cid) => cid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let clean_itemid = match inventory_ulid(&payload.item) {
+		Ok(itemid) => itemid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if let Err(e) = inventory_quantity(Some(payload.quantity)) {
+		return e.into_response();
+	}
+
+	match
+		hazardous_blocking_inventory_grant(
+			clean_cid,
+			clean_itemid,
+			payload.quantity,
+			state.db_pool.clone()
+		).await
+	{
+		Ok(()) => {
+			audit_record(
+				AuditEvent::ItemGrant {
+					cid: cid.trim().to_string(),
+					item: payload.item.trim().to_string(),
+					quantity: payload.quantity,
+				},
+				AuditOutcome::Success,
+				Some(&claims.userid),
+				&audit
+			);
+
+			GenericResponse::new(
+				json!({
+					"cid": cid.trim(),
+					"item": payload.item.trim(),
+					"quantity": payload.quantity,
+				}),
+				json!("Item granted."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
@@ -0,0 +1,179 @@
+use diesel::prelude::*;
+
+use serde::{ Serialize, Deserialize };
+
+use crate::schema::{ character_items, items };
+
+use crate::db::{ DbConnection };
+
+use crate::models::{ CharacterItem, Item };
+
+use crate::entity::{ CoreStats };
+
+use crate::utility::{ convert_ulid_bytes_to_string };
+
+//	?	[Items]
+
+/**
+	`items` holds the definitions, `character_items` the instances a character carries.
+
+	A definition with an empty `slot` can only sit in the bag, otherwise it names the
+	`ItemSlot` it is worn in. `max_stack` caps how many share one instance, equippable items
+	are worn one at a time and a stack is split when one of them is equipped. The core stat
+	columns on a definition are modifiers, added to the owner's stats while the item is worn.
+
+	An instance with a `slot` is equipped. The unique `(cid, slot)` index keeps a slot to one
+	item, bag instances have a NULL slot and are not limited by it.
+**/
+
+//	Instances one character may carry, stacks count once.
+pub const INVENTORY_SIZE_MAX: i64 = 100;
+//	Largest quantity one grant or transfer may move.
+pub const INVENTORY_QUANTITY_MAX: i32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSlot {
+	Head,
+	Neck,
+	Chest,
+	Hands,
+	Legs,
+	Feet,
+	Ring,
+	MainHand,
+	OffHand,
+}
+
+impl ItemSlot {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ItemSlot::Head => "head",
+			ItemSlot::Neck => "neck",
+			ItemSlot::Chest => "chest",
+			ItemSlot::Hands => "hands",
+			ItemSlot::Legs => "legs",
+			ItemSlot::Feet => "feet",
+			ItemSlot::Ring => "ring",
+			ItemSlot::MainHand => "main_hand",
+			ItemSlot::OffHand => "off_hand",
+		}
+	}
+
+	//	`None` for the empty slot of bag only items and for anything unknown.
+	pub fn from_column(slot: &str) -> Option<Self> {
+		match slot {
+			"head" => Some(ItemSlot::Head),
+			"neck" => Some(ItemSlot::Neck),
+			"chest" => Some(ItemSlot::Chest),
+			"hands" => Some(ItemSlot::Hands),
+			"legs" => Some(ItemSlot::Legs),
+			"feet" => Some(ItemSlot::Feet),
+			"ring" => Some(ItemSlot::Ring),
+			"main_hand" => Some(ItemSlot::MainHand),
+			"off_hand" => Some(ItemSlot::OffHand),
+			_ => None,
+		}
+	}
+}
+
+impl Item {
+	pub fn modifiers(&self) -> CoreStats {
+		CoreStats {
+			armour: self.armour,
+			agility: self.agility,
+			strength: self.strength,
+			intelligence: self.intelligence,
+		}
+	}
+
+	pub fn stack_limit(&self) -> i32 {
+		self.max_stack.max(1)
+	}
+}
+
+#[derive(Serialize)]
+pub struct InventoryEntry {
+	pub ulid: String,
+	pub itemid: String,
+	pub name: String,
+	pub description: String,
+	//	Where the definition can be worn, `None` for bag only items.
+	pub equip_slot: Option<ItemSlot>,
+	//	Where this instance is worn right now.
+	pub equipped: Option<ItemSlot>,
+	pub quantity: i32,
+	pub max_stack: i32,
+	pub modifiers: CoreStats,
+}
+
+impl InventoryEntry {
+	pub fn from_rows(instance: &CharacterItem, item: &Item) -> Option<Self> {
+		Some(InventoryEntry {
+			ulid: convert_ulid_bytes_to_string(&instance.ulid).ok()?,
+			itemid: convert_ulid_bytes_to_string(&item.ulid).ok()?,
+			name: item.name.clone(),
+			description: item.description.clone(),
+			equip_slot: ItemSlot::from_column(&item.slot),
+			equipped: instance.slot.as_deref().and_then(ItemSlot::from_column),
+			quantity: instance.quantity,
+			max_stack: item.stack_limit(),
+			modifiers: item.modifiers(),
+		})
+	}
+}
+
+//	?	[Queries]
+
+//	Every instance of a character with its definition, oldest first.
+pub fn hazardous_inventory_entries(
+	conn: &mut DbConnection,
+	clean_cid: &[u8]
+) -> Result<Vec<InventoryEntry>, diesel::result::Error> {
+	let instances = character_items::table
+		.filter(character_items::cid.eq(clean_cid))
+		.order(character_items::id.asc())
+		.load::<CharacterItem>(conn)?;
+
+	let itemids: Vec<&Vec<u8>> = instances
+		.iter()
+		.map(|instance| &instance.itemid)
+		.collect();
+
+	let definitions = items::table.filter(items::ulid.eq_any(itemids)).load::<Item>(conn)?;
+
+	Ok(
+		instances
+			.iter()
+			.filter_map(|instance| {
+				let item = definitions.iter().find(|item| item.ulid == instance.itemid)?;
+				InventoryEntry::from_rows(instance, item)
+			})
+			.collect()
+	)
+}
+
+//	Sum of the modifiers of everything the character wears.
+pub fn hazardous_equipment_bonus(
+	conn: &mut DbConnection,
+	clean_cid: &[u8]
+) -> Result<CoreStats, diesel::result::Error> {
+	let worn: Vec<Vec<u8>> = character_items::table
+		.filter(character_items::cid.eq(clean_cid))
+		.filter(character_items::slot.is_not_null())
+		.select(character_items::itemid)
+		.load::<Vec<u8>>(conn)?;
+
+	if worn.is_empty() {
+		return Ok(CoreStats::default());
+	}
+
+	let definitions = items::table.filter(items::ulid.eq_any(&worn)).load::<Item>(conn)?;
+
+	Ok(
+		worn
+			.iter()
+			.filter_map(|itemid| definitions.iter().find(|item| &item.ulid == itemid))
+			.fold(CoreStats::default(), |bonus, item| bonus.plus(item.modifiers()))
+	)
+}
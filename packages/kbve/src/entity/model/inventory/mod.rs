@@ -0,0 +1,5 @@
+pub mod inventory_item;
+pub mod inventory_handler;
+
+pub use inventory_item::*;
+pub use inventory_handler::*;
\ No newline at end of file
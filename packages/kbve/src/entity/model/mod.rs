@@ -1,2 +1,22 @@
 pub mod character;
-pub use character::*;
\ No newline at end of file
+pub mod api_keys;
+pub mod oauth;
+pub mod admin_globals;
+pub mod user_settings;
+pub mod progression;
+pub mod vitals;
+pub mod inventory;
+
+pub use character::*;
+pub use api_keys::*;
+pub use oauth::*;
+pub use admin_globals::*;
+pub use user_settings::*;
+pub use progression::*;
+pub use vitals::*;
+pub use inventory::*;
+//	Adds the config checks of the game rules, call it before `config_init`.
+pub fn entity_config_register() {
+	crate::config::config_register_check("progression", XpCurve::config_check);
+	crate::config::config_register_check("character", CharacterRules::config_check);
+}
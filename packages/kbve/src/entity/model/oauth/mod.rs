@@ -0,0 +1,5 @@
+pub mod oauth_provider;
+pub mod oauth_handler;
+
+pub use oauth_provider::*;
+pub use oauth_handler::*;
\ No newline at end of file
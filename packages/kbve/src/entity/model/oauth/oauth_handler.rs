@@ -0,0 +1,499 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::{ StatusCode, HeaderValue, header },
+	extract::{ Extension, Path, Query },
+	response::{ IntoResponse, Redirect, Response },
+};
+
+use chrono::Utc;
+
+use diesel::prelude::*;
+use diesel::insert_into;
+
+use rand_core::{ OsRng, RngCore };
+
+use crate::db::{ Pool, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ auth, oauth_accounts, profile, users };
+
+use crate::models::{ OauthAccount };
+
+use crate::runes::{ TokenRune };
+
+use crate::authentication::{
+	SessionClient,
+	AUTH_STATUS_UNVERIFIED,
+	AUTH_STATUS_VERIFIED,
+	auth_login_complete,
+	auth_login_mfa_pending,
+	two_factor_enabled,
+};
+
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
+use crate::entity::{
+	OAuthIdentity,
+	OAuthProvider,
+	oauth_exchange_code,
+	oauth_state_begin,
+	oauth_state_take,
+};
+
+use crate::{
+	spellbook_error,
+	spellbook_get_global,
+	spellbook_generate_ulid_bytes,
+};
+
+use crate::utility::{
+	convert_ulid_bytes_to_string,
+	convert_ulid_string_to_bytes,
+	hash_secure_token,
+	sanitize_email,
+};
+
+use jsonwebtoken::TokenData;
+
+use serde::Deserialize;
+
+pub const OAUTH_SUBJECT_MAX: usize = 255;
+pub const OAUTH_USERNAME_MAX: usize = 24;
+pub const OAUTH_USERNAME_ATTEMPTS: usize = 5;
+
+#[derive(Deserialize)]
+pub struct OAuthCallbackQuery {
+	pub code: Option<String>,
+	pub state: Option<String>,
+	pub error: Option<String>,
+}
+
+//	?	[Usernames]
+
+//	Usernames are ascii alphanumeric and at least six characters, see `sanitize_username`.
+fn oauth_username_base(identity: &OAuthIdentity, provider: &str) -> String {
+	let base: String = identity.username
+		.as_deref()
+		.unwrap_or("")
+		.chars()
+		.filter(|c| c.is_ascii_alphanumeric())
+		.take(OAUTH_USERNAME_MAX)
+		.collect();
+
+	if base.len() >= 6 {
+		return base;
+	}
+
+	let provider: String = provider
+		.chars()
+		.filter(|c| c.is_ascii_alphanumeric())
+		.collect();
+
+	format!("{}{}user", base, provider)
+}
+
+fn oauth_username_candidate(base: &str, attempt: usize) -> String {
+	match attempt {
+		0 => base.to_string(),
+		_ => format!("{}{:04}", base, OsRng.next_u32() % 10_000),
+	}
+}
+
+//	Accounts without a usable provider email get an address that can never receive mail.
+fn oauth_placeholder_email(provider: &str, subject: &str) -> String {
+	format!("{}.{}@oauth.invalid", provider, &hash_secure_token(subject)[..16])
+}
+
+//	?	[Hazardous]
+
+pub async fn hazardous_blocking_oauth_account_by_subject(
+	clean_provider: String,
+	clean_subject: String,
+	pool: Arc<Pool>
+) -> Result<Option<OauthAccount>, KbveError> {
+	run_blocking(pool, move |conn| {
+		oauth_accounts::table
+			.filter(oauth_accounts::provider.eq(clean_provider))
+			.filter(oauth_accounts::subject.eq(clean_subject))
+			.first::<OauthAccount>(conn)
+			.optional()
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_oauth_account_touch(
+	clean_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		diesel
+			::update(oauth_accounts::table.filter(oauth_accounts::ulid.eq(clean_ulid)))
+			.set(oauth_accounts::last_login_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_oauth_account_link(
+	dirty_userid: Vec<u8>,
+	clean_provider: String,
+	clean_subject: String,
+	clean_email: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+
+		insert_into(oauth_accounts::table)
+			.values((
+				oauth_accounts::ulid.eq(spellbook_generate_ulid_bytes!()),
+				oauth_accounts::userid.eq(dirty_userid),
+				oauth_accounts::provider.eq(clean_provider),
+				oauth_accounts::subject.eq(clean_subject),
+				oauth_accounts::email.eq(clean_email),
+				oauth_accounts::created_at.eq(now),
+				oauth_accounts::last_login_at.eq(now),
+			))
+			.execute(conn)
+			.map(|_| true)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Creates the users, auth, profile and oauth_accounts rows of a new player in one transaction.
+//	The auth hash is not a valid Argon2 hash, so the account can only sign in through the provider.
+pub async fn hazardous_blocking_oauth_create_user(
+	clean_provider: String,
+	identity: OAuthIdentity,
+	pool: Arc<Pool>
+) -> Result<Vec<u8>, KbveError> {
+	run_blocking(pool, move |conn| {
+		let provider_email = identity.email
+			.as_deref()
+			.and_then(|email| sanitize_email(email).ok());
+
+		let (clean_email, status) = match provider_email {
+			Some(email) if identity.email_verified => (email, AUTH_STATUS_VERIFIED),
+			Some(email) => (email, AUTH_STATUS_UNVERIFIED),
+			None =>
+				(
+					oauth_placeholder_email(&clean_provider, &identity.subject),
+					AUTH_STATUS_UNVERIFIED,
+				),
+		};
+
+		let username_base = oauth_username_base(&identity, &clean_provider);
+
+		conn.transaction::<Vec<u8>, KbveError, _>(
+			|conn| {
+				let email_taken = auth::table
+					.filter(auth::email.eq(&clean_email))
+					.select(auth::userid)
+					.first::<Vec<u8>>(conn)
+					.optional()?
+					.is_some();
+
+				if email_taken {
+					return Err(KbveError::Conflict("email-exists"));
+				}
+
+				let mut clean_username = None;
+
+				for attempt in 0..OAUTH_USERNAME_ATTEMPTS {
+					let candidate = oauth_username_candidate(&username_base, attempt);
+
+					let taken = users::table
+						.filter(users::username.eq(&candidate))
+						.select(users::userid)
+						.first::<Vec<u8>>(conn)
+						.optional()?
+						.is_some();
+
+					if !taken {
+						clean_username = Some(candidate);
+						break;
+					}
+				}
+
+				let Some(clean_username) = clean_username else {
+					return Err(KbveError::Conflict("username-exists"));
+				};
+
+				let clean_user_ulid = spellbook_generate_ulid_bytes!();
+				let now = Utc::now().naive_utc();
+
+				insert_into(users::table)
+					.values((
+						users::userid.eq(&clean_user_ulid),
+						users::username.eq(&clean_username),
+						users::role.eq(0),
+						users::reputation.eq(0),
+						users::exp.eq(0),
+						users::created_at.eq(now),
+					))
+					.execute(conn)?;
+
+				insert_into(auth::table)
+					.values((
+						auth::ulid.eq(spellbook_generate_ulid_bytes!()),
+						auth::userid.eq(&clean_user_ulid),
+						auth::email.eq(&clean_email),
+						auth::hash.eq("0"),
+						auth::salt.eq("0"),
+						auth::password_reset_token.eq("0"),
+						auth::password_reset_expiry.eq(now),
+						auth::verification_token.eq("0"),
+						auth::verification_expiry.eq(now),
+						auth::status.eq(status),
+						auth::last_login_at.eq(now),
+						auth::failed_login_attempts.eq(0),
+						auth::lockout_until.eq(now),
+						auth::two_factor_secret.eq("0"),
+						auth::recovery_codes.eq("0"),
+					))
+					.execute(conn)?;
+
+				insert_into(profile::table)
+					.values((
+						profile::ulid.eq(spellbook_generate_ulid_bytes!()),
+						profile::userid.eq(&clean_user_ulid),
+						profile::name.eq(&clean_username),
+						profile::bio.eq("default"),
+						profile::unsplash.eq("0"),
+						profile::github.eq("0"),
+						profile::instagram.eq("0"),
+						profile::discord.eq("0"),
+					))
+					.execute(conn)?;
+
+				insert_into(oauth_accounts::table)
+					.values((
+						oauth_accounts::ulid.eq(spellbook_generate_ulid_bytes!()),
+						oauth_accounts::userid.eq(&clean_user_ulid),
+						oauth_accounts::provider.eq(&clean_provider),
+						oauth_accounts::subject.eq(&identity.subject),
+						oauth_accounts::email.eq(identity.email.as_deref().unwrap_or("")),
+						oauth_accounts::created_at.eq(now),
+						oauth_accounts::last_login_at.eq(now),
+					))
+					.execute(conn)?;
+
+				Ok(clean_user_ulid)
+			}
+		)
+	}).await
+}
+
+//	?	[Routes]
+
+//	GET /auth/oauth/:provider - Redirects to the provider. Behind an optional `AuthLayer`,
+//	a signed in caller links the external account to themselves instead of signing in.
+pub async fn oauth_authorize_handler(
+	Path(provider_name): Path<String>,
+	Extension(privatedata): Extension<Option<TokenData<TokenRune>>>
+) -> impl IntoResponse {
+	let provider = match OAuthProvider::from_globals(&provider_name) {
+		Ok(provider) => provider,
+		Err(e) => {
+			return spellbook_error!(StatusCode::NOT_FOUND, e);
+		}
+	};
+
+	let link_userid = match privatedata {
+		Some(privatedata) =>
+			match convert_ulid_string_to_bytes(&privatedata.claims.userid) {
+				Ok(userid) => Some(userid),
+				Err(_) => {
+					return spellbook_error!(StatusCode::UNAUTHORIZED, "invalid_ulid");
+				}
+			}
+		None => None,
+	};
+
+	let (state, pending) = oauth_state_begin(&provider.name, link_userid);
+
+	match provider.authorize_redirect(&pending, &state) {
+		Ok(url) => Redirect::to(&url).into_response(),
+		Err(e) => spellbook_error!(StatusCode::INTERNAL_SERVER_ERROR, e),
+	}
+}
+
+//	GET /auth/oauth/:provider/callback - Finishes the login and issues the usual session.
+//	With the `oauth_complete_url` global set, the browser is sent there with the cookies.
+pub async fn oauth_callback_handler(
+	Extension(pool): Extension<Arc<Pool>>,
+	Path(provider_name): Path<String>,
+	Query(query): Query<OAuthCallbackQuery>,
+	audit: AuditContext
+) -> impl IntoResponse {
+	if query.error.is_some() {
+		return spellbook_error!(StatusCode::UNAUTHORIZED, "oauth_denied");
+	}
+
+	let (Some(code), Some(state)) = (query.code, query.state) else {
+		return spellbook_error!(StatusCode::BAD_REQUEST, "invalid_callback");
+	};
+
+	let provider = match OAuthProvider::from_globals(&provider_name) {
+		Ok(provider) => provider,
+		Err(e) => {
+			return spellbook_error!(StatusCode::NOT_FOUND, e);
+		}
+	};
+
+	let pending = match oauth_state_take(&state, &provider.name) {
+		Ok(pending) => pending,
+		Err(e) => {
+			return spellbook_error!(StatusCode::BAD_REQUEST, e);
+		}
+	};
+
+	let identity = match oauth_exchange_code(&provider, &pending, &code).await {
+		Ok(identity) => identity,
+		Err(e) => {
+			return spellbook_error!(StatusCode::BAD_GATEWAY, e);
+		}
+	};
+
+	if identity.subject.is_empty() || identity.subject.len() > OAUTH_SUBJECT_MAX {
+		return spellbook_error!(StatusCode::BAD_GATEWAY, "invalid_subject");
+	}
+
+	let linked = match
+		hazardous_blocking_oauth_account_by_subject(
+			provider.name.clone(),
+			identity.subject.clone(),
+			pool.clone()
+		).await
+	{
+		Ok(linked) => linked,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let clean_user_ulid = match (linked, pending.link_userid) {
+		//	[#] Known account, linking it again to its own owner is a plain sign in.
+		(Some(account), link_userid) => {
+			if link_userid.is_some_and(|userid| userid != account.userid) {
+				return spellbook_error!(StatusCode::CONFLICT, "oauth-linked-elsewhere");
+			}
+
+			if
+				let Err(e) = hazardous_blocking_oauth_account_touch(
+					account.ulid,
+					pool.clone()
+				).await
+			{
+				return e.into_response();
+			}
+
+			account.userid
+		}
+		//	[&] Signed in caller, link the external account to them.
+		(None, Some(userid)) => {
+			if
+				let Err(e) = hazardous_blocking_oauth_account_link(
+					userid.clone(),
+					provider.name.clone(),
+					identity.subject.clone(),
+					identity.email.clone().unwrap_or_default(),
+					pool.clone()
+				).await
+			{
+				return e.into_response();
+			}
+
+			userid
+		}
+		//	[&] First sign in, create the player.
+		(None, None) =>
+			match
+				hazardous_blocking_oauth_create_user(
+					provider.name.clone(),
+					identity,
+					pool.clone()
+				).await
+			{
+				Ok(userid) => userid,
+				Err(e) => {
+					return e.into_response();
+				}
+			}
+	};
+
+	let account = match
+		crate::guild::hazardous_two_factor_fetch_by_userid(
+			clean_user_ulid.clone(),
+			pool.clone()
+		).await
+	{
+		Ok(account) => account,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let userid_ulid_string = match convert_ulid_bytes_to_string(&clean_user_ulid) {
+		Ok(userid) => userid,
+		Err(_) => {
+			return spellbook_error!(StatusCode::INTERNAL_SERVER_ERROR, "invalid_ulid");
+		}
+	};
+
+	let Some(jwt_secret) = crate::entity::session::keyring_jwt_secret() else {
+		return spellbook_error!(StatusCode::UNAUTHORIZED, "invalid_jwt");
+	};
+
+	if account.lockout_until > Utc::now().naive_utc() {
+		audit_record(
+			AuditEvent::LoginFailed { reason: "account_locked".to_string() },
+			AuditOutcome::Denied,
+			Some(&userid_ulid_string),
+			&audit
+		);
+		return spellbook_error!(StatusCode::FORBIDDEN, "account_locked");
+	}
+
+	//	[!] Two Factor - The provider stands in for the password, not for the second factor.
+	if two_factor_enabled(&account.two_factor_secret) {
+		return auth_login_mfa_pending(&userid_ulid_string, &jwt_secret);
+	}
+
+	let response = auth_login_complete(
+		pool,
+		clean_user_ulid,
+		userid_ulid_string.clone(),
+		account.email,
+		account.username,
+		jwt_secret,
+		SessionClient::from_context(&audit)
+	).await;
+
+	audit_record(
+		AuditEvent::Login { method: format!("oauth:{}", provider.name) },
+		AuditOutcome::from_status(response.status()),
+		Some(&userid_ulid_string),
+		&audit
+	);
+
+	oauth_complete_redirect(response)
+}
+
+fn oauth_complete_redirect(mut response: Response) -> Response {
+	let complete_url = spellbook_get_global!("oauth_complete_url", "missing").ok();
+
+	let location = match complete_url.and_then(|url| HeaderValue::from_str(&url).ok()) {
+		Some(location) if response.status() == StatusCode::OK => location,
+		_ => {
+			return response;
+		}
+	};
+
+	*response.status_mut() = StatusCode::SEE_OTHER;
+	response.headers_mut().insert(header::LOCATION, location);
+	response
+}
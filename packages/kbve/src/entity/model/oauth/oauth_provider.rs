@@ -0,0 +1,550 @@
+use std::time::Duration;
+
+use base64::{ engine::general_purpose::URL_SAFE_NO_PAD, Engine };
+
+use dashmap::DashMap;
+
+use jsonwebtoken::{ DecodingKey, Validation };
+
+use once_cell::sync::Lazy;
+
+use reqwest::{ Client, Url };
+
+use serde::Deserialize;
+
+use sha2::{ Digest, Sha256 };
+
+use crate::utility::{ generate_secure_token };
+
+//	?	[Providers]
+
+/**
+	OAuth2 authorization code + PKCE providers, configured from the `globals` table.
+
+	A provider `<name>` is enabled once `oauth_<name>_client_id` is set, the other keys are
+	`oauth_<name>_client_secret`, `oauth_<name>_authorize_url`, `oauth_<name>_token_url`,
+	`oauth_<name>_redirect_uri` and optionally `oauth_<name>_userinfo_url`,
+	`oauth_<name>_scope`, `oauth_<name>_issuer` and `oauth_<name>_subject_field`.
+
+	OIDC providers return an `id_token`, its `aud`, `iss`, `exp` and `nonce` are checked. The
+	token comes straight from the token endpoint over TLS, which OIDC Core 3.1.3.7 accepts in
+	place of the signature check. Plain OAuth2 providers (GitHub, Discord) are read from the
+	userinfo endpoint instead, `subject_field` names the stable id there (`id` for both).
+
+	Every URL comes from the globals, so a local mock OIDC provider can stand in for tests.
+**/
+
+pub const OAUTH_PROVIDER_NAME_MAX: usize = 32;
+pub const OAUTH_SCOPE_DEFAULT: &str = "openid email profile";
+pub const OAUTH_SUBJECT_FIELD_DEFAULT: &str = "sub";
+pub const OAUTH_HTTP_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone)]
+pub struct OAuthProvider {
+	pub name: String,
+	pub client_id: String,
+	pub client_secret: String,
+	pub authorize_url: String,
+	pub token_url: String,
+	pub userinfo_url: Option<String>,
+	pub redirect_uri: String,
+	pub scope: String,
+	pub issuer: Option<String>,
+	pub subject_field: String,
+}
+
+fn oauth_global(provider: &str, key: &str) -> Option<String> {
+	crate::config
+		::kbve_config()
+		.and_then(|config| {
+			config.get(&format!("oauth_{}_{}", provider, key)).map(String::from)
+		})
+}
+
+pub fn oauth_provider_name_is_valid(name: &str) -> bool {
+	!name.is_empty() &&
+		name.len() <= OAUTH_PROVIDER_NAME_MAX &&
+		name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+impl OAuthProvider {
+	pub fn from_globals(name: &str) -> Result<Self, &'static str> {
+		if !oauth_provider_name_is_valid(name) {
+			return Err("unknown_provider");
+		}
+
+		let client_id = oauth_global(name, "client_id").ok_or("unknown_provider")?;
+
+		Ok(OAuthProvider {
+			name: name.to_string(),
+			client_id,
+			client_secret: oauth_global(name, "client_secret").ok_or(
+				"provider_misconfigured"
+			)?,
+			authorize_url: oauth_global(name, "authorize_url").ok_or(
+				"provider_misconfigured"
+			)?,
+			token_url: oauth_global(name, "token_url").ok_or(
+				"provider_misconfigured"
+			)?,
+			userinfo_url: oauth_global(name, "userinfo_url"),
+			redirect_uri: oauth_global(name, "redirect_uri").ok_or(
+				"provider_misconfigured"
+			)?,
+			scope: oauth_global(name, "scope").unwrap_or_else(||
+				OAUTH_SCOPE_DEFAULT.to_string()
+			),
+			issuer: oauth_global(name, "issuer"),
+			subject_field: oauth_global(name, "subject_field").unwrap_or_else(||
+				OAUTH_SUBJECT_FIELD_DEFAULT.to_string()
+			),
+		})
+	}
+
+	pub fn authorize_redirect(&self, pending: &OAuthPending, state: &str) -> Result<String, &'static str> {
+		Url::parse_with_params(
+			&self.authorize_url,
+			&[
+				("response_type", "code"),
+				("client_id", self.client_id.as_str()),
+				("redirect_uri", self.redirect_uri.as_str()),
+				("scope", self.scope.as_str()),
+				("state", state),
+				("nonce", pending.nonce.as_str()),
+				("code_challenge", oauth_pkce_challenge(&pending.code_verifier).as_str()),
+				("code_challenge_method", "S256"),
+			]
+		)
+			.map(|url| url.to_string())
+			.map_err(|_| "provider_misconfigured")
+	}
+}
+
+//	?	[PKCE]
+
+//	S256 challenge, `BASE64URL(SHA256(code_verifier))`, the verifier is 64 hex characters.
+pub fn oauth_pkce_challenge(code_verifier: &str) -> String {
+	URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+//	?	[State Store]
+
+//	How long a started login may take before its state is thrown away.
+pub const OAUTH_STATE_TTL_SECONDS: i64 = 600;
+
+//	Once the store grows past this, expired entries are pruned on the next insert.
+pub const OAUTH_STATE_MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct OAuthPending {
+	pub provider: String,
+	pub code_verifier: String,
+	pub nonce: String,
+	//	Set when a signed in user started the flow, the external account is linked to them.
+	pub link_userid: Option<Vec<u8>>,
+	pub created_at: i64,
+}
+
+//	state -> pending login, entries are single use.
+static OAUTH_STATE_STORE: Lazy<DashMap<String, OAuthPending>> = Lazy::new(DashMap::new);
+
+//	Starts a login, returns the `state` that the provider hands back to the callback.
+pub fn oauth_state_begin(provider: &str, link_userid: Option<Vec<u8>>) -> (String, OAuthPending) {
+	let now = chrono::Utc::now().timestamp();
+
+	if OAUTH_STATE_STORE.len() >= OAUTH_STATE_MAX_ENTRIES {
+		OAUTH_STATE_STORE.retain(
+			|_, pending| now - pending.created_at < OAUTH_STATE_TTL_SECONDS
+		);
+	}
+
+	let state = generate_secure_token();
+	let pending = OAuthPending {
+		provider: provider.to_string(),
+		code_verifier: generate_secure_token(),
+		nonce: generate_secure_token(),
+		link_userid,
+		created_at: now,
+	};
+
+	OAUTH_STATE_STORE.insert(state.clone(), pending.clone());
+
+	(state, pending)
+}
+
+pub fn oauth_state_take(state: &str, provider: &str) -> Result<OAuthPending, &'static str> {
+	let (_, pending) = OAUTH_STATE_STORE.remove(state).ok_or("invalid_state")?;
+
+	if
+		pending.provider != provider ||
+		chrono::Utc::now().timestamp() - pending.created_at >= OAUTH_STATE_TTL_SECONDS
+	{
+		return Err("invalid_state");
+	}
+
+	Ok(pending)
+}
+
+//	?	[Exchange]
+
+static OAUTH_HTTP: Lazy<Client> = Lazy::new(|| {
+	Client::builder()
+		.timeout(Duration::from_secs(OAUTH_HTTP_TIMEOUT_SECONDS))
+		.user_agent("kbve-oauth")
+		.build()
+		.unwrap_or_default()
+});
+
+#[derive(Debug, Deserialize)]
+struct OAuthTokenResponse {
+	access_token: String,
+	#[serde(default)]
+	id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OAuthIdTokenClaims {
+	sub: String,
+	#[serde(default)]
+	nonce: Option<String>,
+	#[serde(default)]
+	email: Option<String>,
+	#[serde(default)]
+	email_verified: Option<bool>,
+	#[serde(default)]
+	preferred_username: Option<String>,
+	#[serde(default)]
+	name: Option<String>,
+}
+
+//	The external account behind a completed login.
+#[derive(Debug, Clone)]
+pub struct OAuthIdentity {
+	pub subject: String,
+	pub email: Option<String>,
+	pub email_verified: bool,
+	pub username: Option<String>,
+}
+
+pub async fn oauth_exchange_code(
+	provider: &OAuthProvider,
+	pending: &OAuthPending,
+	code: &str
+) -> Result<OAuthIdentity, &'static str> {
+	let response = OAUTH_HTTP.post(&provider.token_url)
+		.header(reqwest::header::ACCEPT, "application/json")
+		.form(
+			&[
+				("grant_type", "authorization_code"),
+				("code", code),
+				("redirect_uri", provider.redirect_uri.as_str()),
+				("client_id", provider.client_id.as_str()),
+				("client_secret", provider.client_secret.as_str()),
+				("code_verifier", pending.code_verifier.as_str()),
+			]
+		)
+		.send().await
+		.map_err(|_| "provider_unreachable")?;
+
+	if !response.status().is_success() {
+		return Err("code_exchange_failed");
+	}
+
+	let tokens = response
+		.json::<OAuthTokenResponse>().await
+		.map_err(|_| "code_exchange_failed")?;
+
+	match tokens.id_token {
+		Some(id_token) => oauth_identity_from_id_token(provider, pending, &id_token),
+		None => oauth_identity_from_userinfo(provider, &tokens.access_token).await,
+	}
+}
+
+fn oauth_identity_from_id_token(
+	provider: &OAuthProvider,
+	pending: &OAuthPending,
+	id_token: &str
+) -> Result<OAuthIdentity, &'static str> {
+	let mut validation = Validation::default();
+	validation.insecure_disable_signature_validation();
+	validation.set_audience(&[provider.client_id.as_str()]);
+	if let Some(issuer) = &provider.issuer {
+		validation.set_issuer(&[issuer.as_str()]);
+	}
+
+	let claims = jsonwebtoken
+		::decode::<OAuthIdTokenClaims>(id_token, &DecodingKey::from_secret(&[]), &validation)
+		.map_err(|_| "invalid_id_token")?.claims;
+
+	if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+		return Err("invalid_nonce");
+	}
+
+	Ok(OAuthIdentity {
+		subject: claims.sub,
+		email: claims.email,
+		email_verified: claims.email_verified.unwrap_or(false),
+		username: claims.preferred_username.or(claims.name),
+	})
+}
+
+async fn oauth_identity_from_userinfo(
+	provider: &OAuthProvider,
+	access_token: &str
+) -> Result<OAuthIdentity, &'static str> {
+	let userinfo_url = provider.userinfo_url.as_ref().ok_or("provider_misconfigured")?;
+
+	let response = OAUTH_HTTP.get(userinfo_url)
+		.bearer_auth(access_token)
+		.header(reqwest::header::ACCEPT, "application/json")
+		.send().await
+		.map_err(|_| "provider_unreachable")?;
+
+	if !response.status().is_success() {
+		return Err("userinfo_failed");
+	}
+
+	let userinfo = response
+		.json::<serde_json::Value>().await
+		.map_err(|_| "userinfo_failed")?;
+
+	let read = |field: &str| -> Option<String> {
+		match userinfo.get(field)? {
+			serde_json::Value::String(value) if !value.is_empty() => Some(value.clone()),
+			serde_json::Value::Number(value) => Some(value.to_string()),
+			_ => None,
+		}
+	};
+
+	Ok(OAuthIdentity {
+		subject: read(&provider.subject_field).ok_or("userinfo_failed")?,
+		email: read("email"),
+		email_verified: matches!(
+			userinfo.get("email_verified").or_else(|| userinfo.get("verified")),
+			Some(serde_json::Value::Bool(true))
+		),
+		username: read("preferred_username")
+			.or_else(|| read("login"))
+			.or_else(|| read("username"))
+			.or_else(|| read("name")),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use axum::{ extract::State, http::{ HeaderMap, StatusCode }, routing::{ get, post } };
+	use axum::{ Form, Json, Router };
+	use jsonwebtoken::{ EncodingKey, Header };
+	use std::collections::HashMap;
+	use std::net::{ SocketAddr, TcpListener };
+
+	#[derive(Clone)]
+	struct MockProvider {
+		//	The S256 challenge the login was started with, the token endpoint checks it.
+		challenge: String,
+		//	`None` answers like a plain OAuth2 provider, without an `id_token`.
+		id_token: Option<String>,
+	}
+
+	async fn mock_token(
+		State(mock): State<MockProvider>,
+		Form(form): Form<HashMap<String, String>>
+	) -> Result<Json<serde_json::Value>, StatusCode> {
+		let verifier = form.get("code_verifier").ok_or(StatusCode::BAD_REQUEST)?;
+
+		if
+			form.get("code").map(String::as_str) != Some("mock-code") ||
+			oauth_pkce_challenge(verifier) != mock.challenge
+		{
+			return Err(StatusCode::BAD_REQUEST);
+		}
+
+		Ok(Json(serde_json::json!({ "access_token": "mock-access", "id_token": mock.id_token })))
+	}
+
+	async fn mock_userinfo(headers: HeaderMap) -> Result<Json<serde_json::Value>, StatusCode> {
+		match headers.get("authorization").and_then(|value| value.to_str().ok()) {
+			Some("Bearer mock-access") =>
+				Ok(
+					Json(
+						serde_json::json!({
+							"id": 4242,
+							"login": "octo",
+							"email": "octo@kbve.com",
+							"verified": true,
+						})
+					)
+				),
+			_ => Err(StatusCode::UNAUTHORIZED),
+		}
+	}
+
+	//	Local OIDC provider on a random port, returns its base URL.
+	fn mock_provider(mock: MockProvider) -> String {
+		let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+		let base = format!("http://{}", listener.local_addr().unwrap());
+		let app = Router::new()
+			.route("/token", post(mock_token))
+			.route("/userinfo", get(mock_userinfo))
+			.with_state(mock);
+
+		tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+		base
+	}
+
+	fn provider(base: &str) -> OAuthProvider {
+		OAuthProvider {
+			name: "mock".to_string(),
+			client_id: "kbve-client".to_string(),
+			client_secret: "kbve-secret".to_string(),
+			authorize_url: format!("{}/authorize", base),
+			token_url: format!("{}/token", base),
+			userinfo_url: Some(format!("{}/userinfo", base)),
+			redirect_uri: "https://kbve.com/auth/oauth/mock/callback".to_string(),
+			scope: OAUTH_SCOPE_DEFAULT.to_string(),
+			issuer: Some("https://issuer.mock".to_string()),
+			subject_field: "id".to_string(),
+		}
+	}
+
+	fn id_token(audience: &str, nonce: &str) -> String {
+		jsonwebtoken
+			::encode(
+				&Header::default(),
+				&serde_json::json!({
+					"iss": "https://issuer.mock",
+					"aud": audience,
+					"sub": "mock-subject",
+					"exp": chrono::Utc::now().timestamp() + 300,
+					"nonce": nonce,
+					"email": "player@kbve.com",
+					"email_verified": true,
+					"preferred_username": "player",
+				}),
+				&EncodingKey::from_secret(b"provider-key")
+			)
+			.unwrap()
+	}
+
+	#[test]
+	fn pkce_challenge_matches_rfc_7636() {
+		assert_eq!(
+			oauth_pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+			"E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+		);
+	}
+
+	#[test]
+	fn state_is_single_use_and_bound_to_the_provider() {
+		let (state, pending) = oauth_state_begin("mock", Some(vec![7u8; 16]));
+
+		let taken = oauth_state_take(&state, "mock").unwrap();
+		assert_eq!(taken.nonce, pending.nonce);
+		assert_eq!(taken.code_verifier, pending.code_verifier);
+		assert_eq!(taken.link_userid, Some(vec![7u8; 16]));
+		assert_eq!(oauth_state_take(&state, "mock").unwrap_err(), "invalid_state");
+
+		let (state, _) = oauth_state_begin("mock", None);
+		assert_eq!(oauth_state_take(&state, "other").unwrap_err(), "invalid_state");
+		assert_eq!(oauth_state_take(&state, "mock").unwrap_err(), "invalid_state");
+	}
+
+	#[test]
+	fn expired_state_is_refused() {
+		let (state, _) = oauth_state_begin("mock", None);
+		OAUTH_STATE_STORE.alter(&state, |_, mut pending| {
+			pending.created_at -= OAUTH_STATE_TTL_SECONDS;
+			pending
+		});
+
+		assert_eq!(oauth_state_take(&state, "mock").unwrap_err(), "invalid_state");
+	}
+
+	#[test]
+	fn authorize_redirect_carries_state_nonce_and_challenge() {
+		let (state, pending) = oauth_state_begin("mock", None);
+		let redirect = Url::parse(&provider("https://mock").authorize_redirect(&pending, &state).unwrap())
+			.unwrap();
+		let params: HashMap<String, String> = redirect.query_pairs().into_owned().collect();
+
+		assert_eq!(params["state"], state);
+		assert_eq!(params["nonce"], pending.nonce);
+		assert_eq!(params["code_challenge"], oauth_pkce_challenge(&pending.code_verifier));
+		assert_eq!(params["code_challenge_method"], "S256");
+		assert_eq!(params["client_id"], "kbve-client");
+	}
+
+	#[tokio::test]
+	async fn exchange_reads_the_oidc_id_token() {
+		let (state, pending) = oauth_state_begin("mock", None);
+		let pending_back = oauth_state_take(&state, "mock").unwrap();
+		let base = mock_provider(MockProvider {
+			challenge: oauth_pkce_challenge(&pending.code_verifier),
+			id_token: Some(id_token("kbve-client", &pending.nonce)),
+		});
+
+		let identity = oauth_exchange_code(&provider(&base), &pending_back, "mock-code").await.unwrap();
+
+		assert_eq!(identity.subject, "mock-subject");
+		assert_eq!(identity.email.as_deref(), Some("player@kbve.com"));
+		assert!(identity.email_verified);
+		assert_eq!(identity.username.as_deref(), Some("player"));
+	}
+
+	#[tokio::test]
+	async fn exchange_refuses_a_foreign_nonce_or_audience() {
+		let (_, pending) = oauth_state_begin("mock", None);
+		let challenge = oauth_pkce_challenge(&pending.code_verifier);
+
+		let base = mock_provider(MockProvider {
+			challenge: challenge.clone(),
+			id_token: Some(id_token("kbve-client", "replayed-nonce")),
+		});
+		assert_eq!(
+			oauth_exchange_code(&provider(&base), &pending, "mock-code").await.unwrap_err(),
+			"invalid_nonce"
+		);
+
+		let base = mock_provider(MockProvider {
+			challenge,
+			id_token: Some(id_token("someone-else", &pending.nonce)),
+		});
+		assert_eq!(
+			oauth_exchange_code(&provider(&base), &pending, "mock-code").await.unwrap_err(),
+			"invalid_id_token"
+		);
+	}
+
+	#[tokio::test]
+	async fn exchange_fails_without_the_matching_verifier() {
+		let (_, pending) = oauth_state_begin("mock", None);
+		let (_, other) = oauth_state_begin("mock", None);
+		let base = mock_provider(MockProvider {
+			challenge: oauth_pkce_challenge(&other.code_verifier),
+			id_token: Some(id_token("kbve-client", &pending.nonce)),
+		});
+
+		assert_eq!(
+			oauth_exchange_code(&provider(&base), &pending, "mock-code").await.unwrap_err(),
+			"code_exchange_failed"
+		);
+	}
+
+	#[tokio::test]
+	async fn exchange_falls_back_to_userinfo() {
+		let (_, pending) = oauth_state_begin("mock", None);
+		let base = mock_provider(MockProvider {
+			challenge: oauth_pkce_challenge(&pending.code_verifier),
+			id_token: None,
+		});
+
+		let identity = oauth_exchange_code(&provider(&base), &pending, "mock-code").await.unwrap();
+
+		assert_eq!(identity.subject, "4242");
+		assert_eq!(identity.username.as_deref(), Some("octo"));
+		assert!(identity.email_verified);
+	}
+}
@@ -0,0 +1,5 @@
+pub mod progression_curve;
+pub mod progression_handler;
+
+pub use progression_curve::*;
+pub use progression_handler::*;
\ No newline at end of file
@@ -0,0 +1,171 @@
+use std::collections::{ BTreeMap };
+
+//	?	[Curve]
+
+/**
+	Levels are derived from `characters.experience`, they are never stored.
+
+	Reaching level `n + 1` from level `n` costs `base * growth^(n - 1)` experience, so with
+	the defaults level 2 needs 100, level 3 another 150, level 4 another 225. Every level
+	gained hands out `points_per_level` unspent stat points.
+
+	The curve is read from the config: `progression_xp_base`, `progression_xp_growth`,
+	`progression_max_level` and `progression_points_per_level`. Points are paid out when the
+	experience is granted, reshaping the curve later does not take back or add points.
+**/
+
+pub const PROGRESSION_XP_BASE_DEFAULT: u64 = 100;
+pub const PROGRESSION_XP_GROWTH_DEFAULT: f64 = 1.5;
+pub const PROGRESSION_MAX_LEVEL_DEFAULT: u32 = 100;
+pub const PROGRESSION_POINTS_PER_LEVEL_DEFAULT: i32 = 3;
+
+pub const PROGRESSION_MAX_LEVEL_LIMIT: u32 = 1000;
+pub const PROGRESSION_POINTS_PER_LEVEL_LIMIT: i32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XpCurve {
+	pub base: u64,
+	pub growth: f64,
+	pub max_level: u32,
+	pub points_per_level: i32,
+}
+
+impl Default for XpCurve {
+	fn default() -> Self {
+		XpCurve {
+			base: PROGRESSION_XP_BASE_DEFAULT,
+			growth: PROGRESSION_XP_GROWTH_DEFAULT,
+			max_level: PROGRESSION_MAX_LEVEL_DEFAULT,
+			points_per_level: PROGRESSION_POINTS_PER_LEVEL_DEFAULT,
+		}
+	}
+}
+
+impl XpCurve {
+	//	Builds the curve from config lookups, every invalid key is reported as `(key, reason)`.
+	pub fn from_lookup(
+		lookup: impl Fn(&str) -> Option<String>
+	) -> Result<Self, Vec<(String, String)>> {
+		let mut curve = XpCurve::default();
+		let mut invalid: Vec<(String, String)> = Vec::new();
+
+		if let Some(value) = lookup("progression_xp_base") {
+			match value.trim().parse::<u64>() {
+				Ok(base) if base > 0 => {
+					curve.base = base;
+				}
+				_ =>
+					invalid.push((
+						"progression_xp_base".to_string(),
+						format!("expected a positive integer, got {}", value),
+					)),
+			}
+		}
+
+		if let Some(value) = lookup("progression_xp_growth") {
+			match value.trim().parse::<f64>() {
+				Ok(growth) if growth.is_finite() && growth >= 1.0 => {
+					curve.growth = growth;
+				}
+				_ =>
+					invalid.push((
+						"progression_xp_growth".to_string(),
+						format!("expected a number of at least 1, got {}", value),
+					)),
+			}
+		}
+
+		if let Some(value) = lookup("progression_max_level") {
+			match value.trim().parse::<u32>() {
+				Ok(max_level) if (1..=PROGRESSION_MAX_LEVEL_LIMIT).contains(&max_level) => {
+					curve.max_level = max_level;
+				}
+				_ =>
+					invalid.push((
+						"progression_max_level".to_string(),
+						format!("expected 1 to {}, got {}", PROGRESSION_MAX_LEVEL_LIMIT, value),
+					)),
+			}
+		}
+
+		if let Some(value) = lookup("progression_points_per_level") {
+			match value.trim().parse::<i32>() {
+				Ok(points) if (0..=PROGRESSION_POINTS_PER_LEVEL_LIMIT).contains(&points) => {
+					curve.points_per_level = points;
+				}
+				_ =>
+					invalid.push((
+						"progression_points_per_level".to_string(),
+						format!("expected 0 to {}, got {}", PROGRESSION_POINTS_PER_LEVEL_LIMIT, value),
+					)),
+			}
+		}
+
+		if invalid.is_empty() {
+			Ok(curve)
+		} else {
+			Err(invalid)
+		}
+	}
+
+	//	Registered through `entity_config_register`, rejects a config the curve can not be built from.
+	pub fn config_check(values: &BTreeMap<String, String>) -> Vec<(String, String)> {
+		XpCurve::from_lookup(|key| values.get(key).filter(|value| !value.is_empty()).cloned())
+			.err()
+			.unwrap_or_default()
+	}
+
+	//	The current curve, the config is validated on load so the defaults only cover a missing config.
+	pub fn from_config() -> Self {
+		let config = crate::config::kbve_config();
+
+		XpCurve::from_lookup(|key| {
+			config
+				.as_ref()
+				.and_then(|config| config.get(key))
+				.map(String::from)
+		}).unwrap_or_default()
+	}
+
+	//	Experience needed to go from `level` to `level + 1`.
+	fn step(&self, level: u32) -> i64 {
+		let cost = (self.base as f64) * self.growth.powi((level as i32) - 1);
+
+		if cost >= (i64::MAX as f64) {
+			i64::MAX
+		} else {
+			cost.floor() as i64
+		}
+	}
+
+	//	Total experience at which `level` is reached, level 1 starts at 0.
+	pub fn experience_for_level(&self, level: u32) -> i64 {
+		(1..level.min(self.max_level)).fold(0i64, |total, level| total.saturating_add(self.step(level)))
+	}
+
+	pub fn level_for_experience(&self, experience: i64) -> u32 {
+		let mut level = 1;
+		let mut threshold = 0i64;
+
+		while level < self.max_level {
+			threshold = threshold.saturating_add(self.step(level));
+
+			if experience < threshold {
+				break;
+			}
+
+			level += 1;
+		}
+
+		level
+	}
+
+	//	Stat points earned by moving from `from` to `to` experience.
+	pub fn points_between(&self, from: i64, to: i64) -> i32 {
+		let gained = self
+			.level_for_experience(to)
+			.saturating_sub(self.level_for_experience(from));
+
+		(gained as i32).saturating_mul(self.points_per_level)
+	}
+}
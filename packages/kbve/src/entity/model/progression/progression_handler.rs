@@ -0,0 +1,351 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::update;
+
+use crate::db::{ DbConnection, Pool, db_for_update, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ characters };
+
+use crate::models::{ Character };
+
+use crate::session::{ KbveState, TokenJWT, TokenAPIKey };
+
+use crate::response::{ GenericResponse };
+
+use crate::audit::{ AuditContext, AuditEvent, AuditOutcome, audit_record };
+
+use crate::entity::{ XpCurve };
+
+use crate::utility::{ convert_ulid_string_to_bytes, sanitize_string_limit };
+
+use jsonwebtoken::TokenData;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+//	Largest single grant, keeps a runaway service from maxing a character out in one call.
+pub const PROGRESSION_GRANT_MAX: i64 = 1_000_000;
+//	No base stat can be raised past this.
+pub const PROGRESSION_STAT_MAX: i32 = 999;
+pub const PROGRESSION_REASON_MAX: usize = 64;
+
+#[derive(Deserialize)]
+pub struct GrantExperienceRequest {
+	pub amount: i64,
+	#[serde(default)]
+	pub reason: String,
+}
+
+//	Points to add to each stat, missing stats stay as they are.
+#[derive(Deserialize, Default)]
+pub struct StatAllocationRequest {
+	#[serde(default)]
+	pub strength: i32,
+	#[serde(default)]
+	pub agility: i32,
+	#[serde(default)]
+	pub intelligence: i32,
+	#[serde(default)]
+	pub armour: i32,
+}
+
+impl StatAllocationRequest {
+	//	A positive number of points, no stat may be negative.
+	pub fn total(&self) -> Result<i32, KbveError> {
+		let parts = [self.strength, self.agility, self.intelligence, self.armour];
+
+		if parts.iter().any(|points| *points < 0) {
+			return Err(KbveError::Validation("invalid_allocation"));
+		}
+
+		parts
+			.iter()
+			.try_fold(0i32, |total, points| total.checked_add(*points))
+			.filter(|total| *total > 0)
+			.ok_or(KbveError::Validation("invalid_allocation"))
+	}
+}
+
+#[derive(Serialize)]
+pub struct ProgressionSnapshot {
+	pub experience: i32,
+	pub level: u32,
+	//	`None` once the character is at the maximum level.
+	pub next_level_experience: Option<i64>,
+	pub stat_points: i32,
+	pub strength: i32,
+	pub agility: i32,
+	pub intelligence: i32,
+	pub armour: i32,
+}
+
+impl ProgressionSnapshot {
+	pub fn from_character(character: &Character, curve: &XpCurve) -> Self {
+		let level = curve.level_for_experience(character.experience as i64);
+
+		ProgressionSnapshot {
+			experience: character.experience,
+			level,
+			next_level_experience: if level < curve.max_level {
+				Some(curve.experience_for_level(level + 1))
+			} else {
+				None
+			},
+			stat_points: character.stat_points,
+			strength: character.strength,
+			agility: character.agility,
+			intelligence: character.intelligence,
+			armour: character.armour,
+		}
+	}
+}
+
+//	?	[Hazardous]
+
+//	Row locked for the transaction, so concurrent grants and allocations apply one after another.
+fn hazardous_character_for_update(
+	conn: &mut DbConnection,
+	clean_cid: &[u8],
+	owner: Option<&[u8]>
+) -> Result<Option<Character>, diesel::result::Error> {
+	let query = characters::table.filter(characters::cid.eq(clean_cid));
+
+	match owner {
+		Some(owner) =>
+			db_for_update!(query.filter(characters::userid.eq(owner)))
+				.first::<Character>(conn)
+				.optional(),
+		None => db_for_update!(query).first::<Character>(conn).optional(),
+	}
+}
+
+//	Adds the experience and pays out the stat points of every level crossed.
+pub async fn hazardous_blocking_grant_experience(
+	clean_cid: Vec<u8>,
+	amount: i64,
+	curve: XpCurve,
+	pool: Arc<Pool>
+) -> Result<(Character, u32), KbveError> {
+	run_blocking(pool, move |conn| {
+		conn
+			.transaction::<(Character, u32), KbveError, _>(
+				|conn| {
+					let Some(mut character) = hazardous_character_for_update(
+						conn,
+						&clean_cid,
+						None
+					)? else {
+						return Err(KbveError::NotFound("character_not_found"));
+					};
+
+					let before = character.experience as i64;
+					let after = before.saturating_add(amount).min(i32::MAX as i64);
+
+					let levels_gained = curve
+						.level_for_experience(after)
+						.saturating_sub(curve.level_for_experience(before));
+
+					character.experience = after as i32;
+					character.stat_points = character.stat_points.saturating_add(
+						curve.points_between(before, after)
+					);
+
+					update(characters::table.filter(characters::id.eq(character.id)))
+						.set((
+							characters::experience.eq(character.experience),
+							characters::stat_points.eq(character.stat_points),
+						))
+						.execute(conn)?;
+
+					Ok((character, levels_gained))
+				}
+			)
+	}).await
+}
+
+pub async fn hazardous_blocking_allocate_stats(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	allocation: StatAllocationRequest,
+	pool: Arc<Pool>
+) -> Result<Character, KbveError> {
+	run_blocking(pool, move |conn| {
+		let total = allocation.total()?;
+
+		conn
+			.transaction::<Character, KbveError, _>(|conn| {
+				let Some(mut character) = hazardous_character_for_update(
+					conn,
+					&clean_cid,
+					Some(&dirty_userid)
+				)? else {
+					return Err(KbveError::NotFound("character_not_found"));
+				};
+
+				if total > character.stat_points {
+					return Err(KbveError::Conflict("not_enough_points"));
+				}
+
+				let raise = |stat: i32, points: i32| -> Option<i32> {
+					stat.checked_add(points).filter(|value| *value <= PROGRESSION_STAT_MAX)
+				};
+
+				let (Some(strength), Some(agility), Some(intelligence), Some(armour)) = (
+					raise(character.strength, allocation.strength),
+					raise(character.agility, allocation.agility),
+					raise(character.intelligence, allocation.intelligence),
+					raise(character.armour, allocation.armour),
+				) else {
+					return Err(KbveError::Validation("stat_limit"));
+				};
+
+				character.strength = strength;
+				character.agility = agility;
+				character.intelligence = intelligence;
+				character.armour = armour;
+				character.stat_points -= total;
+
+				update(characters::table.filter(characters::id.eq(character.id)))
+					.set((
+						characters::strength.eq(character.strength),
+						characters::agility.eq(character.agility),
+						characters::intelligence.eq(character.intelligence),
+						characters::armour.eq(character.armour),
+						characters::stat_points.eq(character.stat_points),
+					))
+					.execute(conn)?;
+
+				Ok(character)
+			})
+	}).await
+}
+
+//	?	[Handlers]
+
+fn progression_cid(cid: &str) -> Result<Vec<u8>, KbveError> {
+	convert_ulid_string_to_bytes(cid.trim()).map_err(|_| KbveError::Validation("invalid_ulid"))
+}
+
+//	Service endpoint behind an API key holding `character:experience`, works on any character.
+pub async fn grant_experience_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(claims): Extension<TokenAPIKey>,
+	audit: AuditContext,
+	Path(cid): Path<String>,
+	Json(payload): Json<GrantExperienceRequest>
+) -> impl IntoResponse {
+	let clean_cid = match progression_cid(&cid) {
+		Ok(cid) => cid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if payload.amount < 1 || payload.amount > PROGRESSION_GRANT_MAX {
+		return KbveError::Validation("invalid_amount").into_response();
+	}
+
+	let reason: String = sanitize_string_limit(payload.reason.trim())
+		.chars()
+		.take(PROGRESSION_REASON_MAX)
+		.collect();
+
+	let curve = XpCurve::from_config();
+
+	match
+		hazardous_blocking_grant_experience(
+			clean_cid,
+			payload.amount,
+			curve,
+			state.db_pool.clone()
+		).await
+	{
+		Ok((character, levels_gained)) => {
+			audit_record(
+				AuditEvent::ExperienceGrant {
+					cid: cid.trim().to_string(),
+					amount: payload.amount,
+					reason,
+				},
+				AuditOutcome::Success,
+				Some(&claims.userid),
+				&audit
+			);
+
+			GenericResponse::new(
+				json!({
+					"cid": cid.trim(),
+					"levels_gained": levels_gained,
+					"progression": ProgressionSnapshot::from_character(&character, &curve),
+				}),
+				json!("Experience granted."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn stat_allocation_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	audit: AuditContext,
+	Path(cid): Path<String>,
+	Json(payload): Json<StatAllocationRequest>
+) -> impl IntoResponse {
+	let clean_cid = match progression_cid(&cid) {
+		Ok(cid) => cid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let owner = match convert_ulid_string_to_bytes(&privatedata.claims.userid) {
+		Ok(owner) => owner,
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
+		}
+	};
+
+	let points = match payload.total() {
+		Ok(points) => points,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_allocate_stats(clean_cid, owner, payload, state.db_pool.clone()).await
+	{
+		Ok(character) => {
+			audit_record(
+				AuditEvent::StatAllocate { cid: cid.trim().to_string(), points },
+				AuditOutcome::Success,
+				Some(&privatedata.claims.userid),
+				&audit
+			);
+
+			GenericResponse::new(
+				json!({
+					"cid": cid.trim(),
+					"progression": ProgressionSnapshot::from_character(
+						&character,
+						&XpCurve::from_config()
+					),
+				}),
+				json!("Stat points allocated."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
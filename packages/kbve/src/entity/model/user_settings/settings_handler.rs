@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::{ insert_into, update, delete };
+
+use crate::db::{ Pool, RowId, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ settings };
+
+use crate::session::{ KbveState, TokenJWT };
+
+use crate::response::{ GenericResponse };
+
+use crate::spellbook_generate_ulid_bytes;
+
+use crate::utility::{ convert_ulid_string_to_bytes };
+
+use jedi::builder::ValidatorBuilder;
+
+use jsonwebtoken::TokenData;
+
+use serde::Deserialize;
+use serde_json::json;
+
+/**
+	Per user preferences kept server side in the `settings` table, one row per (user, key).
+
+	Only keys listed in `SETTINGS_ALLOWLIST` are accepted, each value runs through the
+	`ValidatorBuilder` built by its `SettingKind` before it is stored.
+**/
+
+pub const SETTING_VALUE_MAX: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+	//	One of a fixed set of lowercase values.
+	Choice(&'static [&'static str]),
+	//	`true` or `false`.
+	Toggle,
+	//	`#rrggbb`.
+	Color,
+	//	Language tag, e.g. `en` or `pt-BR`.
+	Language,
+	//	IANA zone name, e.g. `Europe/Paris`.
+	Timezone,
+	//	Short free text, letters, digits, spaces and `!.?`.
+	Text,
+}
+
+pub const SETTINGS_ALLOWLIST: &[(&str, SettingKind)] = &[
+	("theme", SettingKind::Choice(&["light", "dark", "system"])),
+	("language", SettingKind::Language),
+	("timezone", SettingKind::Timezone),
+	("accent_color", SettingKind::Color),
+	("reduced_motion", SettingKind::Toggle),
+	("notifications_email", SettingKind::Toggle),
+	("notifications_push", SettingKind::Toggle),
+	("status_message", SettingKind::Text),
+];
+
+pub fn setting_kind(key: &str) -> Option<SettingKind> {
+	SETTINGS_ALLOWLIST.iter()
+		.find(|(allowed, _)| *allowed == key)
+		.map(|(_, kind)| *kind)
+}
+
+impl SettingKind {
+	pub fn validator(&self) -> ValidatorBuilder<String, String> {
+		let mut validator = ValidatorBuilder::<String, String>::new();
+		validator.clean_or_fail();
+
+		match *self {
+			SettingKind::Choice(choices) => {
+				validator.add_rule(move |value: String| {
+					let value = value.trim().to_lowercase();
+					if choices.contains(&value.as_str()) {
+						Ok(value)
+					} else {
+						Err(format!("Expected one of {}", choices.join(", ")))
+					}
+				});
+			}
+			SettingKind::Toggle => {
+				validator.add_rule(|value: String| {
+					match value.trim().to_lowercase().as_str() {
+						"true" => Ok("true".to_string()),
+						"false" => Ok("false".to_string()),
+						_ => Err("Expected true or false".to_string()),
+					}
+				});
+			}
+			SettingKind::Color => {
+				validator.add_rule(|value: String| Ok(value.trim().to_string()));
+				validator.hex();
+			}
+			SettingKind::Language => {
+				validator.add_rule(|value: String| {
+					let value = value.trim().to_string();
+					let mut parts = value.split('-');
+					let language_ok = parts
+						.next()
+						.map(|language| {
+							(2..=3).contains(&language.len()) &&
+								language.chars().all(|c| c.is_ascii_lowercase())
+						})
+						.unwrap_or(false);
+					let region_ok = parts.all(|region| {
+						(2..=8).contains(&region.len()) &&
+							region.chars().all(|c| c.is_ascii_alphanumeric())
+					});
+
+					if language_ok && region_ok && value.len() <= 35 {
+						Ok(value)
+					} else {
+						Err("Invalid language tag".to_string())
+					}
+				});
+			}
+			SettingKind::Timezone => {
+				validator.add_rule(|value: String| {
+					let value = value.trim().to_string();
+					if
+						!value.is_empty() &&
+						value.len() <= 64 &&
+						value.chars().all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+					{
+						Ok(value)
+					} else {
+						Err("Invalid timezone".to_string())
+					}
+				});
+			}
+			SettingKind::Text => {
+				validator.general_input();
+			}
+		}
+
+		validator
+	}
+}
+
+#[derive(Deserialize)]
+pub struct SettingUpdateRequest {
+	pub value: String,
+}
+
+//	?	[Hazardous]
+
+pub async fn hazardous_blocking_settings_by_userid(
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Vec<(String, String)>, KbveError> {
+	run_blocking(pool, move |conn| {
+		settings::table
+			.filter(settings::userid.eq(dirty_userid))
+			.select((settings::key, settings::value))
+			.load::<(String, String)>(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_setting_upsert(
+	dirty_userid: Vec<u8>,
+	clean_key: String,
+	clean_value: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn
+			.transaction::<bool, diesel::result::Error, _>(|conn| {
+				let existing = settings::table
+					.filter(settings::userid.eq(&dirty_userid))
+					.filter(settings::key.eq(&clean_key))
+					.select(settings::id)
+					.first::<RowId>(conn)
+					.optional()?;
+
+				match existing {
+					Some(id) => {
+						update(settings::table.filter(settings::id.eq(id)))
+							.set(settings::value.eq(&clean_value))
+							.execute(conn)?;
+						Ok(false)
+					}
+					None => {
+						insert_into(settings::table)
+							.values((
+								settings::ulid.eq(spellbook_generate_ulid_bytes!()),
+								settings::userid.eq(&dirty_userid),
+								settings::key.eq(&clean_key),
+								settings::value.eq(&clean_value),
+							))
+							.execute(conn)?;
+						Ok(true)
+					}
+				}
+			})
+			.map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_blocking_setting_delete(
+	dirty_userid: Vec<u8>,
+	clean_key: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		delete(
+			settings::table
+				.filter(settings::userid.eq(dirty_userid))
+				.filter(settings::key.eq(clean_key))
+		)
+			.execute(conn)
+			.map(|rows| rows > 0)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	?	[Handlers]
+
+fn setting_owner_bytes(privatedata: &TokenData<TokenJWT>) -> Result<Vec<u8>, KbveError> {
+	convert_ulid_string_to_bytes(&privatedata.claims.userid).map_err(|_|
+		KbveError::Validation("invalid_ulid")
+	)
+}
+
+//	Keys outside `SETTINGS_ALLOWLIST` answer `unknown_setting`.
+fn setting_key_or_error(key: &str) -> Result<(String, SettingKind), KbveError> {
+	let key = key.trim().to_lowercase();
+
+	match setting_kind(&key) {
+		Some(kind) => Ok((key, kind)),
+		None => Err(KbveError::Validation("unknown_setting")),
+	}
+}
+
+//	Keys that left the allowlist are kept in the table but no longer returned.
+pub async fn settings_list_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>
+) -> impl IntoResponse {
+	let byte_ulid = match setting_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match hazardous_blocking_settings_by_userid(byte_ulid, state.db_pool.clone()).await {
+		Ok(rows) => {
+			let settings: BTreeMap<String, String> = rows
+				.into_iter()
+				.filter(|(key, _)| setting_kind(key).is_some())
+				.collect();
+
+			GenericResponse::new(
+				json!({"settings": settings}),
+				json!("Settings retrieved successfully."),
+				StatusCode::OK
+			).into_response()
+		}
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn settings_put_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path(key): Path<String>,
+	Json(payload): Json<SettingUpdateRequest>
+) -> impl IntoResponse {
+	let byte_ulid = match setting_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let (clean_key, kind) = match setting_key_or_error(&key) {
+		Ok(key) => key,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	if payload.value.chars().count() > SETTING_VALUE_MAX {
+		return KbveError::Validation("invalid_value").into_response();
+	}
+
+	let clean_value = match kind.validator().validate(payload.value) {
+		Ok(value) => value,
+		Err(validation_error) => {
+			return GenericResponse::error(
+				json!({}),
+				json!({"error": "Invalid setting value", "details": validation_error}),
+				validation_error.join(", "),
+				StatusCode::BAD_REQUEST
+			).into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_setting_upsert(
+			byte_ulid,
+			clean_key.clone(),
+			clean_value.clone(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(created) =>
+			GenericResponse::new(
+				json!({"key": clean_key, "value": clean_value}),
+				json!("Setting stored."),
+				if created { StatusCode::CREATED } else { StatusCode::OK }
+			).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+pub async fn settings_delete_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path(key): Path<String>
+) -> impl IntoResponse {
+	let byte_ulid = match setting_owner_bytes(&privatedata) {
+		Ok(bytes) => bytes,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let (clean_key, _) = match setting_key_or_error(&key) {
+		Ok(key) => key,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_setting_delete(
+			byte_ulid,
+			clean_key.clone(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(true) =>
+			GenericResponse::new(
+				json!({"key": clean_key}),
+				json!("Setting removed."),
+				StatusCode::OK
+			).into_response(),
+		Ok(false) => KbveError::NotFound("setting_not_found").into_response(),
+		Err(e) => e.into_response(),
+	}
+}
@@ -0,0 +1,5 @@
+pub mod vitals_engine;
+pub mod vitals_handler;
+
+pub use vitals_engine::*;
+pub use vitals_handler::*;
\ No newline at end of file
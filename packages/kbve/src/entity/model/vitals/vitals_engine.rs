@@ -0,0 +1,244 @@
+use chrono::{ Duration, NaiveDateTime };
+
+use serde::{ Serialize, Deserialize };
+
+use crate::models::{ Character };
+
+use crate::entity::{ XpCurve };
+
+use crate::error::{ KbveError };
+
+//	?	[Vitals]
+
+/**
+	Maxima are derived from the core stats and the level, current values regenerate lazily.
+
+	`max_health = 80 + 10 * strength + 10 * armour + 15 * (level - 1)`
+	`max_mana   = 90 + 10 * intelligence + 10 * (level - 1)`
+	`max_energy = 90 + 10 * agility + 10 * (level - 1)`
+
+	A fresh character (all stats 1, level 1) lands on the 100/100/100 it is created with.
+	Regeneration is a whole number per second: health `1 + strength / 10`, mana
+	`1 + intelligence / 10`, energy `2 + agility / 10`. All of it reads the effective stats,
+	the base stats plus whatever the equipped items add.
+
+	Nothing ticks in the background. `characters.updated_at` marks when the current values were
+	last settled, every read settles them against the clock and every write stores the settled
+	values. The `hp/mp/ep` columns are a cache of the maxima, refreshed on every write.
+**/
+
+pub const VITALS_HEALTH_BASE: i64 = 80;
+pub const VITALS_MANA_BASE: i64 = 90;
+pub const VITALS_ENERGY_BASE: i64 = 90;
+pub const VITALS_PER_STAT: i64 = 10;
+pub const VITALS_HEALTH_PER_LEVEL: i64 = 15;
+pub const VITALS_RESOURCE_PER_LEVEL: i64 = 10;
+
+//	Largest single damage, heal or spend.
+pub const VITALS_AMOUNT_MAX: i32 = 1_000_000;
+
+//	The four core stats, used for base values, item modifiers and their sum alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+pub struct CoreStats {
+	pub armour: i32,
+	pub agility: i32,
+	pub strength: i32,
+	pub intelligence: i32,
+}
+
+impl CoreStats {
+	pub fn from_character(character: &Character) -> Self {
+		CoreStats {
+			armour: character.armour,
+			agility: character.agility,
+			strength: character.strength,
+			intelligence: character.intelligence,
+		}
+	}
+
+	pub fn plus(self, other: CoreStats) -> Self {
+		CoreStats {
+			armour: self.armour.saturating_add(other.armour),
+			agility: self.agility.saturating_add(other.agility),
+			strength: self.strength.saturating_add(other.strength),
+			intelligence: self.intelligence.saturating_add(other.intelligence),
+		}
+	}
+
+	//	Negative modifiers can take a stat down to 0, never below.
+	pub fn floored(self) -> Self {
+		CoreStats {
+			armour: self.armour.max(0),
+			agility: self.agility.max(0),
+			strength: self.strength.max(0),
+			intelligence: self.intelligence.max(0),
+		}
+	}
+
+	//	Base stats plus the equipment `bonus`.
+	pub fn effective(character: &Character, bonus: CoreStats) -> Self {
+		CoreStats::from_character(character).plus(bonus).floored()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VitalResource {
+	#[default]
+	Health,
+	Mana,
+	Energy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum VitalsAction {
+	//	Takes health down, stops at 0.
+	Damage {
+		amount: i32,
+	},
+	//	Restores any resource, stops at its maximum.
+	Heal {
+		#[serde(default)]
+		resource: VitalResource,
+		amount: i32,
+	},
+	//	Pays a cost, fails instead of going below 0.
+	Spend {
+		resource: VitalResource,
+		amount: i32,
+	},
+}
+
+impl VitalsAction {
+	pub fn amount(&self) -> i32 {
+		match self {
+			VitalsAction::Damage { amount } => *amount,
+			VitalsAction::Heal { amount, .. } => *amount,
+			VitalsAction::Spend { amount, .. } => *amount,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Vitals {
+	pub health: i32,
+	pub mana: i32,
+	pub energy: i32,
+	pub max_health: i32,
+	pub max_mana: i32,
+	pub max_energy: i32,
+	#[serde(skip)]
+	pub updated_at: NaiveDateTime,
+}
+
+fn vitals_clamp(value: i64) -> i32 {
+	value.clamp(0, i32::MAX as i64) as i32
+}
+
+impl Vitals {
+	pub fn maxima(stats: &CoreStats, level: u32) -> (i32, i32, i32) {
+		let levels = (level.max(1) as i64) - 1;
+
+		(
+			vitals_clamp(
+				VITALS_HEALTH_BASE +
+					VITALS_PER_STAT * (stats.strength as i64) +
+					VITALS_PER_STAT * (stats.armour as i64) +
+					VITALS_HEALTH_PER_LEVEL * levels
+			),
+			vitals_clamp(
+				VITALS_MANA_BASE +
+					VITALS_PER_STAT * (stats.intelligence as i64) +
+					VITALS_RESOURCE_PER_LEVEL * levels
+			),
+			vitals_clamp(
+				VITALS_ENERGY_BASE +
+					VITALS_PER_STAT * (stats.agility as i64) +
+					VITALS_RESOURCE_PER_LEVEL * levels
+			),
+		)
+	}
+
+	pub fn regen_per_second(stats: &CoreStats) -> (i64, i64, i64) {
+		(
+			1 + (stats.strength.max(0) as i64) / 10,
+			1 + (stats.intelligence.max(0) as i64) / 10,
+			2 + (stats.agility.max(0) as i64) / 10,
+		)
+	}
+
+	//	Current values at `now`, only whole seconds are consumed so no regeneration is lost.
+	pub fn settle(
+		character: &Character,
+		stats: &CoreStats,
+		curve: &XpCurve,
+		now: NaiveDateTime
+	) -> Self {
+		let level = curve.level_for_experience(character.experience as i64);
+		let (max_health, max_mana, max_energy) = Vitals::maxima(stats, level);
+		let (health_regen, mana_regen, energy_regen) = Vitals::regen_per_second(stats);
+
+		//	A clock behind the stored stamp regenerates nothing rather than draining.
+		let elapsed = (now - character.updated_at).num_seconds().max(0);
+
+		let regen = |current: i32, per_second: i64, max: i32| -> i32 {
+			vitals_clamp(
+				(current as i64).saturating_add(per_second.saturating_mul(elapsed))
+			).min(max)
+		};
+
+		Vitals {
+			health: regen(character.health, health_regen, max_health),
+			mana: regen(character.mana, mana_regen, max_mana),
+			energy: regen(character.energy, energy_regen, max_energy),
+			max_health,
+			max_mana,
+			max_energy,
+			updated_at: character.updated_at + Duration::seconds(elapsed),
+		}
+	}
+
+	fn resource(&mut self, resource: VitalResource) -> (&mut i32, i32) {
+		match resource {
+			VitalResource::Health => (&mut self.health, self.max_health),
+			VitalResource::Mana => (&mut self.mana, self.max_mana),
+			VitalResource::Energy => (&mut self.energy, self.max_energy),
+		}
+	}
+
+	pub fn apply(&mut self, action: VitalsAction) -> Result<(), KbveError> {
+		let amount = action.amount();
+
+		if !(1..=VITALS_AMOUNT_MAX).contains(&amount) {
+			return Err(KbveError::Validation("invalid_amount"));
+		}
+
+		match action {
+			VitalsAction::Damage { .. } => {
+				self.health = (self.health - amount).max(0);
+			}
+			VitalsAction::Heal { resource, .. } => {
+				let (current, max) = self.resource(resource);
+				*current = current.saturating_add(amount).min(max);
+			}
+			VitalsAction::Spend { resource, .. } => {
+				let (current, _) = self.resource(resource);
+
+				if *current < amount {
+					return Err(
+						KbveError::Conflict(match resource {
+							VitalResource::Health => "not_enough_health",
+							VitalResource::Mana => "not_enough_mana",
+							VitalResource::Energy => "not_enough_energy",
+						})
+					);
+				}
+
+				*current -= amount;
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,189 @@
+use std::sync::{ Arc };
+
+use axum::{
+	http::StatusCode,
+	extract::{ Extension, Json, Path },
+	response::IntoResponse,
+};
+
+use diesel::prelude::*;
+use diesel::update;
+
+use chrono::Utc;
+
+use crate::db::{ Pool, run_blocking };
+
+use crate::error::{ KbveError };
+
+use crate::schema::{ characters };
+
+use crate::models::{ Character };
+
+use crate::session::{ KbveState, TokenJWT };
+
+use crate::response::{ GenericResponse };
+
+use crate::entity::{ CoreStats, Vitals, VitalsAction, XpCurve, hazardous_equipment_bonus };
+
+use crate::utility::{ convert_ulid_string_to_bytes };
+
+use jsonwebtoken::TokenData;
+
+use serde_json::json;
+
+//	A write that keeps losing the race gives up instead of spinning.
+pub const VITALS_CAS_ATTEMPTS: usize = 5;
+
+//	?	[Hazardous]
+
+//	Settled for display only, nothing is written back.
+pub async fn hazardous_blocking_vitals_for_owner(
+	clean_cid: Vec<u8>,
+	dirty_userid: Vec<u8>,
+	curve: XpCurve,
+	pool: Arc<Pool>
+) -> Result<Vitals, KbveError> {
+	run_blocking(pool, move |conn| {
+		let character = characters::table
+			.filter(characters::cid.eq(clean_cid))
+			.filter(characters::userid.eq(dirty_userid))
+			.first::<Character>(conn)
+			.optional()?
+			.ok_or(KbveError::NotFound("character_not_found"))?;
+
+		let bonus = hazardous_equipment_bonus(conn, &character.cid)?;
+		let stats = CoreStats::effective(&character, bonus);
+
+		Ok(Vitals::settle(&character, &stats, &curve, Utc::now().naive_utc()))
+	}).await
+}
+
+/**
+	Settles the vitals, applies the action and stores the result as a compare and swap.
+
+	The update only lands while `updated_at` and the current values are still the ones that
+	were read, a concurrent write makes it match no row and the whole step is retried on the
+	fresh row. `updated_at` alone is not enough, the column only holds whole seconds.
+**/
+pub async fn hazardous_blocking_vitals_apply(
+	clean_cid: Vec<u8>,
+	action: VitalsAction,
+	curve: XpCurve,
+	pool: Arc<Pool>
+) -> Result<Vitals, KbveError> {
+	run_blocking(pool, move |conn| {
+		for _ in 0..VITALS_CAS_ATTEMPTS {
+			let character = characters::table
+				.filter(characters::cid.eq(&clean_cid))
+				.first::<Character>(conn)
+				.optional()?
+				.ok_or(KbveError::NotFound("character_not_found"))?;
+
+			let bonus = hazardous_equipment_bonus(conn, &clean_cid)?;
+			let stats = CoreStats::effective(&character, bonus);
+
+			let mut vitals = Vitals::settle(&character, &stats, &curve, Utc::now().naive_utc());
+
+			vitals.apply(action)?;
+
+			let swapped = update(
+				characters::table
+					.filter(characters::id.eq(character.id))
+					.filter(characters::updated_at.eq(character.updated_at))
+					.filter(characters::health.eq(character.health))
+					.filter(characters::mana.eq(character.mana))
+					.filter(characters::energy.eq(character.energy))
+			)
+				.set((
+					characters::hp.eq(vitals.max_health),
+					characters::mp.eq(vitals.max_mana),
+					characters::ep.eq(vitals.max_energy),
+					characters::health.eq(vitals.health),
+					characters::mana.eq(vitals.mana),
+					characters::energy.eq(vitals.energy),
+					characters::updated_at.eq(vitals.updated_at),
+				))
+				.execute(conn)?;
+
+			if swapped > 0 {
+				return Ok(vitals);
+			}
+		}
+
+		Err(KbveError::Unavailable("vitals_contended"))
+	}).await
+}
+
+//	?	[Handlers]
+
+fn vitals_cid(cid: &str) -> Result<Vec<u8>, KbveError> {
+	convert_ulid_string_to_bytes(cid.trim()).map_err(|_| KbveError::Validation("invalid_ulid"))
+}
+
+pub async fn vitals_get_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Extension(privatedata): Extension<TokenData<TokenJWT>>,
+	Path(cid): Path<String>
+) -> impl IntoResponse {
+	let clean_cid = match vitals_cid(&cid) {
+		Ok(cid) => cid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	let owner = match convert_ulid_string_to_bytes(&privatedata.claims.userid) {
+		Ok(owner) => owner,
+		Err(_) => {
+			return KbveError::Validation("invalid_ulid").into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_vitals_for_owner(
+			clean_cid,
+			owner,
+			XpCurve::from_config(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(vitals) =>
+			GenericResponse::new(
+				json!({ "cid": cid.trim(), "vitals": vitals }),
+				json!("Character vitals."),
+				StatusCode::OK
+			).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
+
+//	Service endpoint behind an API key holding `character:vitals`, works on any character.
+pub async fn vitals_action_handler(
+	Extension(state): Extension<Arc<KbveState>>,
+	Path(cid): Path<String>,
+	Json(action): Json<VitalsAction>
+) -> impl IntoResponse {
+	let clean_cid = match vitals_cid(&cid) {
+		Ok(cid) => cid,
+		Err(e) => {
+			return e.into_response();
+		}
+	};
+
+	match
+		hazardous_blocking_vitals_apply(
+			clean_cid,
+			action,
+			XpCurve::from_config(),
+			state.db_pool.clone()
+		).await
+	{
+		Ok(vitals) =>
+			GenericResponse::new(
+				json!({ "cid": cid.trim(), "vitals": vitals }),
+				json!("Character vitals updated."),
+				StatusCode::OK
+			).into_response(),
+		Err(e) => e.into_response(),
+	}
+}
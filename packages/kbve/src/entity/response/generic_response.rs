@@ -1,6 +1,9 @@
 use serde::{ Serialize, Deserialize };
 
-use axum::{ http::{ StatusCode }, response::{ Json, IntoResponse, Response } };
+use axum::{
+	http::{ StatusCode, HeaderValue, header::HeaderName },
+	response::{ Json, IntoResponse, Response },
+};
 
 mod status_code_serde {
 	use super::StatusCode;
@@ -71,11 +74,21 @@ impl GenericResponse {
 	}
 }
 
+//	An error also carries its code in `x-kbve-shield`, so clients can branch without the body.
 impl IntoResponse for GenericResponse {
     fn into_response(self) -> Response {
         let status_code = self.status_code;
+        let shield = self.error
+            .as_deref()
+            .and_then(|error| HeaderValue::from_str(error).ok());
         let json_body = Json(self);
         //    let json_body = Json(self.clone()); - Shifting the clone out to be efficient
-        (status_code, json_body).into_response()
+        let mut response = (status_code, json_body).into_response();
+
+        if let Some(shield) = shield {
+            response.headers_mut().insert(HeaderName::from_static("x-kbve-shield"), shield);
+        }
+
+        response
     }
 }
@@ -0,0 +1,336 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ Context, Poll };
+
+use axum::{
+	http::{ Request, HeaderMap, header },
+	response::{ IntoResponse, Response },
+};
+
+use axum_extra::extract::cookie::CookieJar;
+
+use jsonwebtoken::{ TokenData, Validation };
+
+use serde::de::DeserializeOwned;
+
+use tower::{ Layer, Service };
+
+use crate::db::{ Pool };
+use crate::error::{ KbveError };
+use crate::runes::{ TokenRune };
+use crate::session::{
+	TokenJWT,
+	Scopes,
+	hazardous_scopes_for_userid,
+	jwt_decode_with,
+	keyring_jwt_secret,
+	session_jti_is_revoked,
+};
+
+//	?	[AuthLayer]
+
+/**
+	One tower `Layer` for every JWT protected route.
+
+	The token is taken from the first configured source that carries one (by default the
+	`token` cookie, then the `Authorization: Bearer` header), decoded through the session key
+	ring, checked against the revoked sessions and resolved to the caller's `Scopes`. A token
+	without a `jti` is refused, it is not bound to a session and could not be revoked.
+	On success the request carries `TokenData<C>` and `Scopes` as extensions.
+
+	In `AuthMode::Optional` requests without a token pass through anonymously, every request
+	then also carries an `Option<TokenData<C>>` extension. A token that is present but invalid
+	is still rejected, so a client holding an expired token knows to refresh it.
+**/
+
+pub const AUTH_COOKIE_DEFAULT: &str = "token";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+	Cookie(&'static str),
+	Bearer,
+	Header(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+	Required,
+	Optional,
+}
+
+//	Claims that can sit behind the `AuthLayer`.
+pub trait AuthClaims: DeserializeOwned + Clone + Send + Sync + 'static {
+	fn userid(&self) -> &str;
+	fn jti(&self) -> Option<&str>;
+}
+
+impl AuthClaims for TokenRune {
+	fn userid(&self) -> &str {
+		&self.userid
+	}
+
+	fn jti(&self) -> Option<&str> {
+		self.jti.as_deref()
+	}
+}
+
+impl AuthClaims for TokenJWT {
+	fn userid(&self) -> &str {
+		&self.userid
+	}
+
+	fn jti(&self) -> Option<&str> {
+		self.jti.as_deref()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+	pub sources: Vec<TokenSource>,
+	pub mode: AuthMode,
+	pub leeway: u64,
+	pub audience: Vec<String>,
+	pub issuer: Vec<String>,
+}
+
+impl Default for AuthConfig {
+	fn default() -> Self {
+		AuthConfig {
+			sources: vec![TokenSource::Cookie(AUTH_COOKIE_DEFAULT), TokenSource::Bearer],
+			mode: AuthMode::Required,
+			leeway: 60,
+			audience: Vec::new(),
+			issuer: Vec::new(),
+		}
+	}
+}
+
+impl AuthConfig {
+	fn token_from_headers(&self, headers: &HeaderMap) -> Option<String> {
+		self.sources.iter().find_map(|source| {
+			match source {
+				TokenSource::Cookie(name) =>
+					CookieJar::from_headers(headers)
+						.get(name)
+						.map(|cookie| cookie.value().to_string()),
+				TokenSource::Bearer =>
+					headers
+						.get(header::AUTHORIZATION)
+						.and_then(|auth_header| auth_header.to_str().ok())
+						.and_then(|auth_value| auth_value.strip_prefix("Bearer "))
+						.map(String::from),
+				TokenSource::Header(name) =>
+					headers
+						.get(*name)
+						.and_then(|header_value| header_value.to_str().ok())
+						.map(String::from),
+			}
+		}).filter(|token| !token.trim().is_empty())
+	}
+
+	fn configure(&self, validation: &mut Validation) {
+		validation.leeway = self.leeway;
+		if !self.audience.is_empty() {
+			validation.set_audience(&self.audience);
+		}
+		if !self.issuer.is_empty() {
+			validation.set_issuer(&self.issuer);
+		}
+	}
+}
+
+//	Decodes and checks the request's token, `Ok(None)` when the request carries none.
+//	A bad token is `Unauthorized`, a database that can not answer stays a `Database` error.
+pub async fn auth_authenticate<C: AuthClaims>(
+	config: &AuthConfig,
+	headers: &HeaderMap,
+	pool: Arc<Pool>
+) -> Result<Option<(TokenData<C>, Scopes)>, KbveError> {
+	let Some(token) = config.token_from_headers(headers) else {
+		return Ok(None);
+	};
+
+	let jwt_secret = keyring_jwt_secret().ok_or(KbveError::Unauthorized("invalid_jwt"))?;
+
+	let privatedata = jwt_decode_with::<C>(&token, &jwt_secret, |validation|
+		config.configure(validation)
+	).map_err(|_| KbveError::Unauthorized("invalid_jwt"))?;
+
+	//	Every login issues a session bound token, one without a `jti` could never be revoked.
+	let Some(jti) = privatedata.claims.jti() else {
+		return Err(KbveError::Unauthorized("session_required"));
+	};
+
+	if session_jti_is_revoked(jti, pool.clone()).await? {
+		return Err(KbveError::Unauthorized("session_revoked"));
+	}
+
+	let scopes = match hazardous_scopes_for_userid(privatedata.claims.userid(), pool).await {
+		Ok(scopes) => scopes,
+		Err(KbveError::NotFound(_) | KbveError::Validation(_)) => {
+			return Err(KbveError::Unauthorized("invalid_jwt"));
+		}
+		Err(e) => {
+			return Err(e);
+		}
+	};
+
+	Ok(Some((privatedata, scopes)))
+}
+
+//	Runs `auth_authenticate` and stores the outcome on the request, or builds the error response.
+pub async fn auth_authorize_request<C: AuthClaims, B>(
+	config: &AuthConfig,
+	pool: Arc<Pool>,
+	req: &mut Request<B>
+) -> Result<(), Response> {
+	let outcome = auth_authenticate::<C>(config, req.headers(), pool).await;
+
+	match (outcome, config.mode) {
+		(Ok(Some((privatedata, scopes))), mode) => {
+			if mode == AuthMode::Optional {
+				req.extensions_mut().insert(Some(privatedata.clone()));
+			}
+			req.extensions_mut().insert(privatedata);
+			req.extensions_mut().insert(scopes);
+			Ok(())
+		}
+		(Ok(None), AuthMode::Optional) => {
+			req.extensions_mut().insert(None::<TokenData<C>>);
+			Ok(())
+		}
+		(Ok(None), AuthMode::Required) =>
+			Err(KbveError::Unauthorized("invalid_jwt").into_response()),
+		(Err(error), _) => Err(error.into_response()),
+	}
+}
+
+pub struct AuthLayer<C> {
+	pool: Arc<Pool>,
+	config: AuthConfig,
+	claims: PhantomData<fn() -> C>,
+}
+
+impl<C: AuthClaims> AuthLayer<C> {
+	pub fn new(pool: Arc<Pool>) -> Self {
+		AuthLayer {
+			pool,
+			config: AuthConfig::default(),
+			claims: PhantomData,
+		}
+	}
+
+	//	Replaces the token sources, they are tried in order.
+	pub fn sources(mut self, sources: &[TokenSource]) -> Self {
+		self.config.sources = sources.to_vec();
+		self
+	}
+
+	pub fn source(mut self, source: TokenSource) -> Self {
+		self.config.sources.push(source);
+		self
+	}
+
+	pub fn optional(mut self) -> Self {
+		self.config.mode = AuthMode::Optional;
+		self
+	}
+
+	pub fn leeway(mut self, seconds: u64) -> Self {
+		self.config.leeway = seconds;
+		self
+	}
+
+	pub fn audience(mut self, audience: &[&str]) -> Self {
+		self.config.audience = audience
+			.iter()
+			.map(|aud| aud.to_string())
+			.collect();
+		self
+	}
+
+	pub fn issuer(mut self, issuer: &[&str]) -> Self {
+		self.config.issuer = issuer
+			.iter()
+			.map(|iss| iss.to_string())
+			.collect();
+		self
+	}
+}
+
+impl<C> Clone for AuthLayer<C> {
+	fn clone(&self) -> Self {
+		AuthLayer {
+			pool: self.pool.clone(),
+			config: self.config.clone(),
+			claims: PhantomData,
+		}
+	}
+}
+
+impl<S, C: AuthClaims> Layer<S> for AuthLayer<C> {
+	type Service = AuthService<S, C>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		AuthService {
+			inner,
+			pool: self.pool.clone(),
+			config: Arc::new(self.config.clone()),
+			claims: PhantomData,
+		}
+	}
+}
+
+pub struct AuthService<S, C> {
+	inner: S,
+	pool: Arc<Pool>,
+	config: Arc<AuthConfig>,
+	claims: PhantomData<fn() -> C>,
+}
+
+impl<S: Clone, C> Clone for AuthService<S, C> {
+	fn clone(&self) -> Self {
+		AuthService {
+			inner: self.inner.clone(),
+			pool: self.pool.clone(),
+			config: self.config.clone(),
+			claims: PhantomData,
+		}
+	}
+}
+
+impl<S, B, C> Service<Request<B>>
+	for AuthService<S, C>
+	where
+		S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+		S::Future: Send + 'static,
+		B: Send + 'static,
+		C: AuthClaims
+{
+	type Response = Response;
+	type Error = S::Error;
+	type Future = Pin<
+		Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>
+	>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut req: Request<B>) -> Self::Future {
+		//	The ready service goes into the future, its clone stays behind for the next call.
+		let clone = self.inner.clone();
+		let mut inner = std::mem::replace(&mut self.inner, clone);
+		let pool = self.pool.clone();
+		let config = self.config.clone();
+
+		Box::pin(async move {
+			match auth_authorize_request::<C, B>(&config, pool, &mut req).await {
+				Ok(()) => inner.call(req).await,
+				Err(response) => Ok(response),
+			}
+		})
+	}
+}
@@ -0,0 +1,610 @@
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, RwLock };
+
+use axum::{ response::IntoResponse, Json };
+
+use base64::{ engine::general_purpose::URL_SAFE_NO_PAD, Engine };
+
+use jsonwebtoken::{
+	Algorithm,
+	DecodingKey,
+	EncodingKey,
+	Header,
+	TokenData,
+	Validation,
+};
+
+use once_cell::sync::Lazy;
+
+use ring::rand::SystemRandom;
+use ring::signature::{ Ed25519KeyPair, KeyPair, RsaKeyPair };
+
+use serde::{ de::DeserializeOwned, Serialize };
+use serde_json::json;
+
+use ulid::Ulid;
+
+//	?	[KeyRing]
+
+/**
+	Asymmetric signing keys for the JWTs, selected by `kid`.
+
+	Keys live as PKCS#8 PEM files named `<kid>.<alg>.pem` in the directory given by the
+	`jwt_key_dir` global or the `KBVE_JWT_KEY_DIR` environment variable, where `<kid>` is a
+	ulid and `<alg>` is `eddsa` or `rs256`. The ulid timestamp is the key's creation time:
+	the newest key signs, and every key younger than two rotation periods still verifies,
+	so tokens signed with the previous key stay valid through a rotation.
+
+	Without a key directory the ring is empty and tokens fall back to HS256 over `jwt_secret`.
+**/
+
+pub const KEYRING_DIR_ENV: &str = "KBVE_JWT_KEY_DIR";
+pub const KEYRING_ROTATION_DAYS_DEFAULT: i64 = 30;
+pub const KEYRING_RELOAD_MINUTES: u64 = 10;
+
+pub struct RingKey {
+	pub kid: String,
+	pub algorithm: Algorithm,
+	pub created_at: i64,
+	encoding: EncodingKey,
+	decoding: DecodingKey,
+	jwk: serde_json::Value,
+}
+
+#[derive(Default)]
+pub struct KeyRing {
+	//	Sorted by kid, so the last key is the newest.
+	keys: Vec<Arc<RingKey>>,
+}
+
+static KEYRING: Lazy<RwLock<Arc<KeyRing>>> = Lazy::new(||
+	RwLock::new(Arc::new(KeyRing::default()))
+);
+
+impl KeyRing {
+	pub fn signing_key(&self) -> Option<&Arc<RingKey>> {
+		self.keys.last()
+	}
+
+	pub fn find(&self, kid: &str) -> Option<&Arc<RingKey>> {
+		self.keys.iter().find(|key| key.kid == kid)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.keys.is_empty()
+	}
+
+	pub fn jwks(&self) -> serde_json::Value {
+		json!({
+			"keys": self.keys.iter().map(|key| key.jwk.clone()).collect::<Vec<_>>()
+		})
+	}
+}
+
+pub fn keyring_current() -> Arc<KeyRing> {
+	KEYRING.read()
+		.map(|ring| ring.clone())
+		.unwrap_or_default()
+}
+
+fn keyring_replace(ring: KeyRing) {
+	if let Ok(mut current) = KEYRING.write() {
+		*current = Arc::new(ring);
+	}
+}
+
+pub fn keyring_dir() -> Option<PathBuf> {
+	crate::config
+		::kbve_config()
+		.and_then(|config| config.jwt_key_dir.clone())
+		.or_else(|| std::env::var(KEYRING_DIR_ENV).ok())
+		.filter(|dir| !dir.trim().is_empty())
+		.map(PathBuf::from)
+}
+
+pub fn keyring_rotation_days() -> i64 {
+	crate::config
+		::kbve_config()
+		.map(|config| config.jwt_key_rotation_days)
+		.filter(|days| *days > 0)
+		.unwrap_or(KEYRING_ROTATION_DAYS_DEFAULT)
+}
+
+//	The HS256 `jwt_secret` from the typed config, `None` before `config_init` or when it is empty.
+pub fn keyring_jwt_secret() -> Option<String> {
+	crate::config
+		::kbve_config()
+		.map(|config| config.jwt_secret.clone())
+		.filter(|secret| !secret.is_empty())
+}
+
+//	?	[Loading]
+
+fn ring_key_from_pem(
+	kid: &str,
+	algorithm: &str,
+	pem_bytes: &[u8]
+) -> Result<RingKey, String> {
+	let created_at = Ulid::from_string(kid)
+		.map_err(|_| format!("{} is not a ulid", kid))?
+		.timestamp_ms() as i64 / 1000;
+
+	let der = pem
+		::parse(pem_bytes)
+		.map_err(|e| format!("{}: invalid pem, {}", kid, e))?.contents;
+
+	match algorithm {
+		"eddsa" => {
+			let pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).map_err(|_|
+				format!("{}: invalid Ed25519 PKCS#8 key", kid)
+			)?;
+			let x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+
+			Ok(RingKey {
+				kid: kid.to_string(),
+				algorithm: Algorithm::EdDSA,
+				created_at,
+				encoding: EncodingKey::from_ed_der(&der),
+				decoding: DecodingKey::from_ed_components(&x).map_err(|e|
+					format!("{}: {}", kid, e)
+				)?,
+				jwk: json!({
+					"kty": "OKP",
+					"crv": "Ed25519",
+					"use": "sig",
+					"alg": "EdDSA",
+					"kid": kid,
+					"x": x,
+				}),
+			})
+		}
+		"rs256" => {
+			let pair = RsaKeyPair::from_pkcs8(&der)
+				.or_else(|_| RsaKeyPair::from_der(&der))
+				.map_err(|_| format!("{}: invalid RSA private key", kid))?;
+
+			let (n, e) = rsa_public_components(pair.public_key().as_ref()).ok_or_else(||
+				format!("{}: invalid RSA public key", kid)
+			)?;
+			let n = URL_SAFE_NO_PAD.encode(n);
+			let e = URL_SAFE_NO_PAD.encode(e);
+
+			Ok(RingKey {
+				kid: kid.to_string(),
+				algorithm: Algorithm::RS256,
+				created_at,
+				encoding: EncodingKey::from_rsa_pem(pem_bytes).map_err(|e|
+					format!("{}: {}", kid, e)
+				)?,
+				decoding: DecodingKey::from_rsa_components(&n, &e).map_err(|e|
+					format!("{}: {}", kid, e)
+				)?,
+				jwk: json!({
+					"kty": "RSA",
+					"use": "sig",
+					"alg": "RS256",
+					"kid": kid,
+					"n": n,
+					"e": e,
+				}),
+			})
+		}
+		other => Err(format!("{}: unsupported key algorithm {}", kid, other)),
+	}
+}
+
+//	Splits a DER `RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }`.
+fn rsa_public_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+	let blocks = simple_asn1::from_der(der).ok()?;
+
+	match blocks.first()? {
+		simple_asn1::ASN1Block::Sequence(_, items) =>
+			match (items.first()?, items.get(1)?) {
+				(
+					simple_asn1::ASN1Block::Integer(_, n),
+					simple_asn1::ASN1Block::Integer(_, e),
+				) => Some((n.to_bytes_be().1, e.to_bytes_be().1)),
+				_ => None,
+			}
+		_ => None,
+	}
+}
+
+//	Reads every `<kid>.<alg>.pem` in the directory, dropping keys past two rotation periods.
+pub fn keyring_load(dir: &Path, rotation_days: i64) -> Result<KeyRing, String> {
+	let entries = std::fs
+		::read_dir(dir)
+		.map_err(|e| format!("Failed to read key dir {}: {}", dir.display(), e))?;
+
+	let retire_before =
+		chrono::Utc::now().timestamp() - rotation_days * 2 * 24 * 60 * 60;
+
+	let mut keys: Vec<Arc<RingKey>> = Vec::new();
+
+	for entry in entries.flatten() {
+		let file_name = entry.file_name().to_string_lossy().to_string();
+
+		let mut parts = file_name.splitn(3, '.');
+		let (Some(kid), Some(algorithm), Some("pem")) = (
+			parts.next(),
+			parts.next(),
+			parts.next(),
+		) else {
+			continue;
+		};
+
+		let pem_bytes = std::fs
+			::read(entry.path())
+			.map_err(|e| format!("Failed to read {}: {}", file_name, e))?;
+
+		let key = ring_key_from_pem(kid, &algorithm.to_lowercase(), &pem_bytes)?;
+
+		if key.created_at >= retire_before {
+			keys.push(Arc::new(key));
+		}
+	}
+
+	keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+
+	Ok(KeyRing { keys })
+}
+
+//	Writes a fresh Ed25519 key into the directory, the ulid kid makes it the newest key.
+pub fn keyring_generate_ed25519(dir: &Path) -> Result<String, String> {
+	keyring_write_ed25519(dir, Ulid::new())
+}
+
+fn keyring_write_ed25519(dir: &Path, kid: Ulid) -> Result<String, String> {
+	let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_|
+		"Failed to generate Ed25519 key".to_string()
+	)?;
+
+	let kid = kid.to_string();
+	let path = dir.join(format!("{}.eddsa.pem", kid));
+
+	let encoded = pem::encode(
+		&(pem::Pem {
+			tag: "PRIVATE KEY".to_string(),
+			contents: document.as_ref().to_vec(),
+		})
+	);
+
+	std::fs
+		::write(&path, encoded)
+		.map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+	}
+
+	Ok(kid)
+}
+
+//	Loads the ring and generates a new signing key when the newest one is a rotation period old.
+pub fn keyring_refresh() -> Result<(), String> {
+	let Some(dir) = keyring_dir() else {
+		return Ok(());
+	};
+
+	let rotation_days = keyring_rotation_days();
+
+	let mut ring = keyring_load(&dir, rotation_days)?;
+
+	let rotate_before =
+		chrono::Utc::now().timestamp() - rotation_days * 24 * 60 * 60;
+
+	let due = ring
+		.signing_key()
+		.map(|key| key.created_at <= rotate_before)
+		.unwrap_or(true);
+
+	if due {
+		let kid = keyring_generate_ed25519(&dir)?;
+		tracing::info!("[KeyRing] rotated signing key -> {}", kid);
+		ring = keyring_load(&dir, rotation_days)?;
+	}
+
+	keyring_replace(ring);
+
+	Ok(())
+}
+
+//	Periodic reload, picks up keys rotated by other instances sharing the directory.
+//	Also refreshes as soon as a config reload touches one of the `jwt_key_*` keys.
+pub fn keyring_spawn_rotation() {
+	tokio::spawn(async {
+		let mut interval = tokio::time::interval(
+			std::time::Duration::from_secs(KEYRING_RELOAD_MINUTES * 60)
+		);
+		let mut config_changes = crate::config::config_subscribe();
+
+		loop {
+			tokio::select! {
+				_ = interval.tick() => {}
+				change = config_changes.recv() => {
+					match change {
+						Ok(change) if change.changed.iter().any(|key| key.starts_with("jwt_key_")) => {}
+						Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+							continue;
+						}
+						Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+							config_changes = crate::config::config_subscribe();
+							continue;
+						}
+					}
+				}
+			}
+
+			let result = tokio::task::spawn_blocking(keyring_refresh).await;
+
+			match result {
+				Ok(Ok(())) => {}
+				Ok(Err(e)) => tracing::error!("[KeyRing] refresh failed: {}", e),
+				Err(e) => tracing::error!("[KeyRing] refresh task failed: {}", e),
+			}
+		}
+	});
+}
+
+//	?	[Sign / Verify]
+
+pub fn jwt_encode<T: Serialize>(
+	claims: &T,
+	legacy_secret: &str
+) -> Result<String, &'static str> {
+	jwt_encode_ring(&keyring_current(), claims, legacy_secret)
+}
+
+fn jwt_encode_ring<T: Serialize>(
+	ring: &KeyRing,
+	claims: &T,
+	legacy_secret: &str
+) -> Result<String, &'static str> {
+	let (header, key) = match ring.signing_key() {
+		Some(key) => {
+			let mut header = Header::new(key.algorithm);
+			header.kid = Some(key.kid.clone());
+			(header, key.encoding.clone())
+		}
+		None => (Header::default(), EncodingKey::from_secret(legacy_secret.as_bytes())),
+	};
+
+	jsonwebtoken::encode(&header, claims, &key).map_err(|_| "jwt_encode_failed")
+}
+
+//	Tokens with a `kid` must match a key in the ring. Tokens without one are the HS256
+//	tokens signed with `jwt_secret`, still accepted unless `jwt_accept_hs256` is "false".
+pub fn jwt_decode<T: DeserializeOwned>(
+	token: &str,
+	legacy_secret: &str
+) -> Result<TokenData<T>, &'static str> {
+	jwt_decode_with(token, legacy_secret, |_| {})
+}
+
+//	Same as `jwt_decode`, `configure` can tighten the validation (leeway, audience, issuer).
+pub fn jwt_decode_with<T: DeserializeOwned>(
+	token: &str,
+	legacy_secret: &str,
+	configure: impl Fn(&mut Validation)
+) -> Result<TokenData<T>, &'static str> {
+	let accept_hs256 = crate::config
+		::kbve_config()
+		.map(|config| config.jwt_accept_hs256)
+		.unwrap_or(true);
+
+	jwt_decode_ring(&keyring_current(), accept_hs256, token, legacy_secret, configure)
+}
+
+fn jwt_decode_ring<T: DeserializeOwned>(
+	ring: &KeyRing,
+	accept_hs256: bool,
+	token: &str,
+	legacy_secret: &str,
+	configure: impl Fn(&mut Validation)
+) -> Result<TokenData<T>, &'static str> {
+	let header = jsonwebtoken::decode_header(token).map_err(|_| "invalid_jwt")?;
+
+	match header.kid {
+		Some(kid) => {
+			let key = ring.find(&kid).ok_or("unknown_kid")?;
+
+			if header.alg != key.algorithm {
+				return Err("invalid_jwt");
+			}
+
+			let mut validation = Validation::new(key.algorithm);
+			configure(&mut validation);
+
+			jsonwebtoken
+				::decode::<T>(token, &key.decoding, &validation)
+				.map_err(|_| "invalid_jwt")
+		}
+		None => {
+			if !accept_hs256 && !ring.is_empty() {
+				return Err("invalid_jwt");
+			}
+
+			let mut validation = Validation::new(Algorithm::HS256);
+			configure(&mut validation);
+
+			jsonwebtoken
+				::decode::<T>(
+					token,
+					&DecodingKey::from_secret(legacy_secret.as_bytes()),
+					&validation
+				)
+				.map_err(|_| "invalid_jwt")
+		}
+	}
+}
+
+//	GET /.well-known/jwks.json
+pub async fn jwks_endpoint() -> impl IntoResponse {
+	(
+		[(axum::http::header::CACHE_CONTROL, "public, max-age=300")],
+		Json(keyring_current().jwks()),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use serde::Deserialize;
+
+	const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+	#[derive(Debug, Serialize, Deserialize, PartialEq)]
+	struct Claims {
+		sub: String,
+		exp: usize,
+	}
+
+	fn claims() -> Claims {
+		Claims {
+			sub: "01HQ0000000000000000000000".to_string(),
+			exp: (chrono::Utc::now().timestamp() + 3600) as usize,
+		}
+	}
+
+	fn key_dir() -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("kbve-keyring-{}", Ulid::new()));
+		std::fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	//	A kid whose ulid timestamp lies `days_ago` in the past.
+	fn kid_days_ago(days_ago: u64) -> Ulid {
+		let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+		Ulid::from_parts(now_ms - days_ago * DAY_MS, Ulid::new().random())
+	}
+
+	#[test]
+	fn sign_verify_round_trip() {
+		let dir = key_dir();
+		let kid = keyring_generate_ed25519(&dir).unwrap();
+		let ring = keyring_load(&dir, 30).unwrap();
+
+		let token = jwt_encode_ring(&ring, &claims(), "secret").unwrap();
+		let header = jsonwebtoken::decode_header(&token).unwrap();
+		assert_eq!(header.alg, Algorithm::EdDSA);
+		assert_eq!(header.kid.as_deref(), Some(kid.as_str()));
+
+		let decoded = jwt_decode_ring::<Claims>(&ring, true, &token, "secret", |_| {}).unwrap();
+		assert_eq!(decoded.claims, claims());
+
+		let empty = KeyRing::default();
+		let legacy = jwt_encode_ring(&empty, &claims(), "secret").unwrap();
+		assert_eq!(jsonwebtoken::decode_header(&legacy).unwrap().alg, Algorithm::HS256);
+		assert!(jwt_decode_ring::<Claims>(&empty, true, &legacy, "secret", |_| {}).is_ok());
+		assert!(jwt_decode_ring::<Claims>(&empty, true, &legacy, "other", |_| {}).is_err());
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn newest_key_signs_and_verify_picks_by_kid() {
+		let dir = key_dir();
+		let previous = keyring_write_ed25519(&dir, kid_days_ago(20)).unwrap();
+		let newest = keyring_write_ed25519(&dir, kid_days_ago(1)).unwrap();
+		let ring = keyring_load(&dir, 30).unwrap();
+
+		assert_eq!(ring.signing_key().unwrap().kid, newest);
+
+		let token = jwt_encode_ring(&ring, &claims(), "secret").unwrap();
+		assert_eq!(jsonwebtoken::decode_header(&token).unwrap().kid, Some(newest));
+
+		//	A token signed by the previous key still verifies against its own kid.
+		let old_key = ring.find(&previous).unwrap();
+		let mut header = Header::new(old_key.algorithm);
+		header.kid = Some(previous.clone());
+		let old_token = jsonwebtoken::encode(&header, &claims(), &old_key.encoding).unwrap();
+		assert!(jwt_decode_ring::<Claims>(&ring, true, &old_token, "secret", |_| {}).is_ok());
+
+		//	The same signature under another kid fails, and a kid outside the ring is unknown.
+		header.kid = Some(ring.signing_key().unwrap().kid.clone());
+		let swapped = jsonwebtoken::encode(&header, &claims(), &old_key.encoding).unwrap();
+		assert_eq!(
+			jwt_decode_ring::<Claims>(&ring, true, &swapped, "secret", |_| {}).unwrap_err(),
+			"invalid_jwt"
+		);
+
+		header.kid = Some(Ulid::new().to_string());
+		let unknown = jsonwebtoken::encode(&header, &claims(), &old_key.encoding).unwrap();
+		assert_eq!(
+			jwt_decode_ring::<Claims>(&ring, true, &unknown, "secret", |_| {}).unwrap_err(),
+			"unknown_kid"
+		);
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn keys_past_two_rotation_periods_are_retired() {
+		let dir = key_dir();
+		let retired = keyring_write_ed25519(&dir, kid_days_ago(61)).unwrap();
+		let kept = keyring_write_ed25519(&dir, kid_days_ago(45)).unwrap();
+
+		let ring = keyring_load(&dir, 30).unwrap();
+		assert!(ring.find(&retired).is_none());
+		assert!(ring.find(&kept).is_some());
+
+		//	A longer rotation period keeps both.
+		let ring = keyring_load(&dir, 31).unwrap();
+		assert!(ring.find(&retired).is_some());
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn hs256_rejected_when_disabled() {
+		let dir = key_dir();
+		keyring_generate_ed25519(&dir).unwrap();
+		let ring = keyring_load(&dir, 30).unwrap();
+
+		let legacy = jwt_encode_ring(&KeyRing::default(), &claims(), "secret").unwrap();
+
+		assert!(jwt_decode_ring::<Claims>(&ring, true, &legacy, "secret", |_| {}).is_ok());
+		assert_eq!(
+			jwt_decode_ring::<Claims>(&ring, false, &legacy, "secret", |_| {}).unwrap_err(),
+			"invalid_jwt"
+		);
+
+		//	Without any key in the ring HS256 is the only option and stays accepted.
+		let empty = KeyRing::default();
+		assert!(jwt_decode_ring::<Claims>(&empty, false, &legacy, "secret", |_| {}).is_ok());
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn jwks_lists_the_public_keys() {
+		let dir = key_dir();
+		let first = keyring_write_ed25519(&dir, kid_days_ago(2)).unwrap();
+		let second = keyring_generate_ed25519(&dir).unwrap();
+		let ring = keyring_load(&dir, 30).unwrap();
+
+		let jwks = ring.jwks();
+		let keys = jwks["keys"].as_array().unwrap();
+		assert_eq!(keys.len(), 2);
+		assert_eq!(keys[0]["kid"], first);
+		assert_eq!(keys[1]["kid"], second);
+
+		for key in keys {
+			assert_eq!(key["kty"], "OKP");
+			assert_eq!(key["crv"], "Ed25519");
+			assert_eq!(key["alg"], "EdDSA");
+			assert_eq!(key["use"], "sig");
+			assert!(key.get("d").is_none());
+
+			let x = URL_SAFE_NO_PAD.decode(key["x"].as_str().unwrap()).unwrap();
+			assert_eq!(x.len(), 32);
+		}
+
+		assert_eq!(KeyRing::default().jwks(), json!({ "keys": [] }));
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,82 +1,88 @@
 use std::sync::Arc;
 
-use axum_extra::extract::cookie::CookieJar;
-
 use crate::db::{ Pool };
 
-use crate::session::TokenJWT;
+use crate::error::{ KbveError };
 
-use axum::{
-	http::{ StatusCode, Request, header },
-	extract::{ Extension, Json, State },
-	response::IntoResponse,
-    middleware::Next,
+use crate::session::{
+	TokenJWT,
+	Scopes,
+	Role,
+	AuthConfig,
+	auth_authorize_request,
 };
 
-use serde_json::json;
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use crate::entity::{ API_KEY_PREFIX, hazardous_blocking_api_key_owner_by_hash };
 
-//  Macro Migration of SpellBook -> Global - Deadlock Warning
-use crate::spellbook_get_global;
+use crate::utility::{ hash_secure_token };
 
-//  Graceful Replacement -> Middleware JWT
+use axum::{
+	http::{ Request },
+	extract::{ State },
+	response::IntoResponse,
+    middleware::Next,
+};
 
+//  Graceful Replacement -> Middleware JWT, a `from_fn` wrapper over the `AuthLayer` checks.
 
 pub async fn middleware_jwt<B>(
-	cookie_jar: CookieJar,
-	State(_data): State<Arc<Pool>>,
+	State(data): State<Arc<Pool>>,
 	mut req: Request<B>,
 	next: Next<B>
 ) -> impl IntoResponse {
-	let token_result: Result<String, ()> = cookie_jar
-		.get("token")
-		.map(|cookie| cookie.value().to_string())
-		.or_else(|| {
-			req.headers()
-				.get(header::AUTHORIZATION)
-				.and_then(|auth_header| auth_header.to_str().ok())
-				.and_then(|auth_value|
-					auth_value.strip_prefix("Bearer ").map(String::from)
-				)
-		})
-		.ok_or_else(|| ());
+	match
+		auth_authorize_request::<TokenJWT, B>(
+			&AuthConfig::default(),
+			data,
+			&mut req
+		).await
+	{
+		Ok(()) => next.run(req).await.into_response(),
+		Err(response) => response,
+	}
+}
 
-	let jwt_secret = match spellbook_get_global!("jwt_secret", "invalid_jwt") {
-		Ok(secret) => secret,
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
-		}
-	};
+//  API Key -> Middleware for service-to-service callers
 
-	let token: &str = match token_result {
-		Ok(ref token_str) => token_str.as_str(),
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+pub async fn middleware_api_key<B>(
+	State(data): State<Arc<Pool>>,
+	mut req: Request<B>,
+	next: Next<B>
+) -> impl IntoResponse {
+	let api_key = match
+		req
+			.headers()
+			.get(API_KEY_HEADER)
+			.and_then(|header_value| header_value.to_str().ok())
+			.map(|header_value| header_value.trim())
+			.filter(|header_value| header_value.starts_with(API_KEY_PREFIX))
+	{
+		Some(api_key) => api_key.to_string(),
+		None => {
+			return KbveError::Unauthorized("invalid_api_key").into_response();
 		}
 	};
 
-	let privatedata = match
-		jsonwebtoken::decode::<TokenJWT>(
-			&token,
-			&jsonwebtoken::DecodingKey::from_secret(jwt_secret.as_bytes()),
-			&jsonwebtoken::Validation::default()
-		)
+	let claims = match
+		hazardous_blocking_api_key_owner_by_hash(
+			hash_secure_token(&api_key),
+			data
+		).await
 	{
-		Ok(privatedata) => { privatedata }
-		Err(_) => {
-			return (
-				StatusCode::UNAUTHORIZED,
-				Json(json!({"error": "invalid_jwt"})),
-			).into_response();
+		Ok(claims) => claims,
+		Err(e) => {
+			return e.into_response();
 		}
 	};
 
-	req.extensions_mut().insert(privatedata);
+	let scopes = Scopes::for_api_key(
+		Role::from_i32(claims.role),
+		&claims.permissions
+	);
+
+	req.extensions_mut().insert(claims);
+	req.extensions_mut().insert(scopes);
 	next.run(req).await.into_response()
-}
\ No newline at end of file
+}
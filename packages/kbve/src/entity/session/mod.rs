@@ -2,8 +2,18 @@ pub mod jwt;
 pub mod state;
 pub mod token;
 pub mod middleware;
+pub mod scope;
+pub mod revocation;
+pub mod keyring;
+pub mod auth_layer;
+pub mod rate_limit;
 
 pub use jwt::*;
 pub use state::*;
 pub use token::*;
-pub use middleware::*;
\ No newline at end of file
+pub use middleware::*;
+pub use scope::*;
+pub use revocation::*;
+pub use keyring::*;
+pub use auth_layer::*;
+pub use rate_limit::*;
\ No newline at end of file
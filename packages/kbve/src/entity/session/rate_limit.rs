@@ -0,0 +1,489 @@
+use std::future::Future;
+use std::net::{ IpAddr, SocketAddr };
+use std::pin::Pin;
+use std::task::{ Context, Poll };
+use std::time::Instant;
+
+use axum::{
+	extract::ConnectInfo,
+	http::{ Request, HeaderMap },
+	response::{ IntoResponse, Response },
+};
+
+use dashmap::DashMap;
+
+use jsonwebtoken::TokenData;
+
+use once_cell::sync::Lazy;
+
+use tower::{ Layer, Service };
+
+use crate::runes::{ TokenRune };
+use crate::session::{ TokenJWT, TokenAPIKey };
+
+use crate::error::{ KbveError };
+
+//	?	[RateLimit]
+
+/**
+	Token bucket throttling for single routes, keyed by client IP, by user or by API key.
+
+	Each layer has a name, its limit is read from the `rate_limit_<name>` global as
+	`<requests>/<seconds>` (e.g. `5/60`: bursts of five, one request back every twelve seconds),
+	or `off` to lift it. Without the global the layer's default applies.
+
+	`X-Forwarded-For` is only honoured when the connecting peer is listed in the
+	`rate_limit_trusted_proxies` global (comma separated addresses or CIDR ranges), the client
+	is then the right-most address that is not a trusted proxy. The peer address comes from
+	`ConnectInfo<SocketAddr>`, so serve the router `into_make_service_with_connect_info`.
+
+	User and API key limits read the claims left by the auth middleware, so the layer has to
+	run after it. Requests without claims fall back to their IP.
+**/
+
+pub const RATE_LIMIT_MAX_BUCKETS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+	Ip,
+	User,
+	ApiKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+	pub requests: u32,
+	pub seconds: u64,
+}
+
+impl RateLimit {
+	pub const fn new(requests: u32, seconds: u64) -> Self {
+		RateLimit { requests, seconds }
+	}
+
+	//	`None` when the limit is switched off.
+	pub fn parse(value: &str) -> Result<Option<Self>, &'static str> {
+		let value = value.trim();
+
+		if value.eq_ignore_ascii_case("off") {
+			return Ok(None);
+		}
+
+		let (requests, seconds) = value.split_once('/').ok_or("invalid_rate_limit")?;
+		let requests = requests.trim().parse::<u32>().map_err(|_| "invalid_rate_limit")?;
+		let seconds = seconds.trim().parse::<u64>().map_err(|_| "invalid_rate_limit")?;
+
+		if requests == 0 || seconds == 0 {
+			return Err("invalid_rate_limit");
+		}
+
+		Ok(Some(RateLimit { requests, seconds }))
+	}
+
+	fn refill_per_second(&self) -> f64 {
+		(self.requests as f64) / (self.seconds as f64)
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RateLimitBucket {
+	tokens: f64,
+	updated_at: Instant,
+	seconds: u64,
+}
+
+//	(layer name, key) -> bucket.
+static RATE_LIMIT_BUCKETS: Lazy<DashMap<(&'static str, String), RateLimitBucket>> = Lazy::new(
+	DashMap::new
+);
+
+//	Takes one token, or returns how many seconds until one is available again.
+fn rate_limit_take(name: &'static str, key: String, limit: RateLimit) -> Result<(), u64> {
+	rate_limit_take_at(name, key, limit, Instant::now())
+}
+
+fn rate_limit_take_at(
+	name: &'static str,
+	key: String,
+	limit: RateLimit,
+	now: Instant
+) -> Result<(), u64> {
+	if RATE_LIMIT_BUCKETS.len() >= RATE_LIMIT_MAX_BUCKETS {
+		//	A bucket idle for its whole period is full again, dropping it changes nothing.
+		RATE_LIMIT_BUCKETS.retain(|_, bucket| {
+			now.duration_since(bucket.updated_at).as_secs() < bucket.seconds
+		});
+	}
+
+	let capacity = limit.requests as f64;
+	let refill = limit.refill_per_second();
+
+	let mut bucket = RATE_LIMIT_BUCKETS.entry((name, key)).or_insert(RateLimitBucket {
+		tokens: capacity,
+		updated_at: now,
+		seconds: limit.seconds,
+	});
+
+	let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+	bucket.tokens = (bucket.tokens + elapsed * refill).min(capacity);
+	bucket.updated_at = now;
+	bucket.seconds = limit.seconds;
+
+	if bucket.tokens >= 1.0 {
+		bucket.tokens -= 1.0;
+		Ok(())
+	} else {
+		Err(((1.0 - bucket.tokens) / refill).ceil().max(1.0) as u64)
+	}
+}
+
+fn rate_limit_global(key: &str) -> Option<String> {
+	crate::config::kbve_config().and_then(|config| config.get(key).map(String::from))
+}
+
+fn rate_limit_for(name: &str, default: RateLimit) -> Option<RateLimit> {
+	match rate_limit_global(&format!("rate_limit_{}", name)) {
+		Some(value) =>
+			match RateLimit::parse(&value) {
+				Ok(limit) => limit,
+				Err(_) => {
+					tracing::warn!("[RateLimit] invalid rate_limit_{} {}, using the default", name, value);
+					Some(default)
+				}
+			}
+		None => Some(default),
+	}
+}
+
+//	?	[Client IP]
+
+//	Address or CIDR range, e.g. `10.0.0.1` or `10.0.0.0/8`.
+fn rate_limit_proxy_matches(proxy: &str, ip: IpAddr) -> bool {
+	let (network, prefix) = match proxy.split_once('/') {
+		Some((network, prefix)) => (network, prefix.parse::<u32>().ok()),
+		None => (proxy, None),
+	};
+
+	let Ok(network) = network.trim().parse::<IpAddr>() else {
+		return false;
+	};
+
+	match (network, ip) {
+		(IpAddr::V4(network), IpAddr::V4(ip)) => {
+			let prefix = prefix.unwrap_or(32).min(32);
+			let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+			(u32::from(network) & mask) == (u32::from(ip) & mask)
+		}
+		(IpAddr::V6(network), IpAddr::V6(ip)) => {
+			let prefix = prefix.unwrap_or(128).min(128);
+			let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+			(u128::from(network) & mask) == (u128::from(ip) & mask)
+		}
+		_ => false,
+	}
+}
+
+fn rate_limit_is_trusted(trusted: &[String], ip: IpAddr) -> bool {
+	trusted.iter().any(|proxy| rate_limit_proxy_matches(proxy, ip))
+}
+
+pub fn rate_limit_client_ip(headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+	let trusted: Vec<String> = rate_limit_global("rate_limit_trusted_proxies")
+		.map(|proxies| {
+			proxies
+				.split(',')
+				.map(|proxy| proxy.trim().to_string())
+				.filter(|proxy| !proxy.is_empty())
+				.collect()
+		})
+		.unwrap_or_default();
+
+	rate_limit_client_ip_from(headers, peer, &trusted)
+}
+
+fn rate_limit_client_ip_from(
+	headers: &HeaderMap,
+	peer: Option<IpAddr>,
+	trusted: &[String]
+) -> Option<IpAddr> {
+	let peer = peer?;
+
+	if !rate_limit_is_trusted(trusted, peer) {
+		return Some(peer);
+	}
+
+	let forwarded: Vec<IpAddr> = headers
+		.get_all("x-forwarded-for")
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+		.collect();
+
+	Some(
+		forwarded
+			.iter()
+			.rev()
+			.find(|hop| !rate_limit_is_trusted(trusted, **hop))
+			.copied()
+			.or_else(|| forwarded.first().copied())
+			.unwrap_or(peer)
+	)
+}
+
+//	?	[Layer]
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitLayer {
+	name: &'static str,
+	key: RateLimitKey,
+	default: RateLimit,
+}
+
+impl RateLimitLayer {
+	pub fn new(name: &'static str, key: RateLimitKey, default: RateLimit) -> Self {
+		RateLimitLayer { name, key, default }
+	}
+
+	pub fn per_ip(name: &'static str, default: RateLimit) -> Self {
+		Self::new(name, RateLimitKey::Ip, default)
+	}
+
+	pub fn per_user(name: &'static str, default: RateLimit) -> Self {
+		Self::new(name, RateLimitKey::User, default)
+	}
+
+	pub fn per_api_key(name: &'static str, default: RateLimit) -> Self {
+		Self::new(name, RateLimitKey::ApiKey, default)
+	}
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+	type Service = RateLimitService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		RateLimitService { inner, limiter: *self }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+	inner: S,
+	limiter: RateLimitLayer,
+}
+
+impl<S> RateLimitService<S> {
+	fn bucket_key<B>(&self, req: &Request<B>) -> String {
+		let extensions = req.extensions();
+
+		let claimed = match self.limiter.key {
+			RateLimitKey::Ip => None,
+			RateLimitKey::User =>
+				extensions
+					.get::<TokenData<TokenJWT>>()
+					.map(|privatedata| privatedata.claims.userid.clone())
+					.or_else(|| {
+						extensions
+							.get::<TokenData<TokenRune>>()
+							.map(|privatedata| privatedata.claims.userid.clone())
+					})
+					.or_else(|| extensions.get::<TokenAPIKey>().map(|key| key.userid.clone()))
+					.map(|userid| format!("user:{}", userid)),
+			RateLimitKey::ApiKey =>
+				extensions
+					.get::<TokenAPIKey>()
+					.map(|key| format!("key:{}", key.keyid)),
+		};
+
+		claimed.unwrap_or_else(|| {
+			let peer = extensions
+				.get::<ConnectInfo<SocketAddr>>()
+				.map(|ConnectInfo(addr)| addr.ip());
+
+			match rate_limit_client_ip(req.headers(), peer) {
+				Some(ip) => format!("ip:{}", ip),
+				None => "ip:unknown".to_string(),
+			}
+		})
+	}
+}
+
+impl<S, B> Service<Request<B>>
+	for RateLimitService<S>
+	where
+		S: Service<Request<B>, Response = Response> + Send + 'static,
+		S::Future: Send + 'static,
+		B: Send + 'static
+{
+	type Response = Response;
+	type Error = S::Error;
+	type Future = Pin<
+		Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>
+	>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Request<B>) -> Self::Future {
+		let Some(limit) = rate_limit_for(self.limiter.name, self.limiter.default) else {
+			return Box::pin(self.inner.call(req));
+		};
+
+		let key = self.bucket_key(&req);
+
+		match rate_limit_take(self.limiter.name, key, limit) {
+			Ok(()) => Box::pin(self.inner.call(req)),
+			Err(retry_after) => {
+				let response = KbveError::RateLimited { retry_after }.into_response();
+
+				Box::pin(async move { Ok(response) })
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Duration;
+
+	//	Every test takes its own layer name, the buckets are shared by the whole process.
+	fn take(name: &'static str, limit: RateLimit, now: Instant) -> Result<(), u64> {
+		rate_limit_take_at(name, "ip:192.0.2.1".to_string(), limit, now)
+	}
+
+	fn ip(value: &str) -> IpAddr {
+		value.parse().unwrap()
+	}
+
+	fn forwarded(value: &str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert("x-forwarded-for", value.parse().unwrap());
+		headers
+	}
+
+	fn trusted(proxies: &[&str]) -> Vec<String> {
+		proxies.iter().map(|proxy| proxy.to_string()).collect()
+	}
+
+	#[test]
+	fn parses_limits() {
+		assert_eq!(RateLimit::parse("5/60"), Ok(Some(RateLimit::new(5, 60))));
+		assert_eq!(RateLimit::parse(" 10 / 1 "), Ok(Some(RateLimit::new(10, 1))));
+		assert_eq!(RateLimit::parse("OFF"), Ok(None));
+
+		for invalid in ["", "5", "0/60", "5/0", "five/60", "-1/60"] {
+			assert_eq!(RateLimit::parse(invalid), Err("invalid_rate_limit"), "{}", invalid);
+		}
+	}
+
+	#[test]
+	fn bucket_drains_then_answers_retry_after() {
+		let limit = RateLimit::new(4, 32);
+		let now = Instant::now();
+
+		for _ in 0..4 {
+			assert_eq!(take("test_drain", limit, now), Ok(()));
+		}
+
+		//	One token comes back every eight seconds.
+		assert_eq!(take("test_drain", limit, now), Err(8));
+		assert_eq!(take("test_drain", limit, now + Duration::from_secs(3)), Err(5));
+
+		//	Other keys and other layers have their own buckets.
+		assert_eq!(rate_limit_take_at("test_drain", "ip:192.0.2.2".to_string(), limit, now), Ok(()));
+		assert_eq!(take("test_drain_other", limit, now), Ok(()));
+	}
+
+	#[test]
+	fn bucket_refills_over_time_up_to_its_capacity() {
+		let limit = RateLimit::new(2, 10);
+		let now = Instant::now();
+
+		assert_eq!(take("test_refill", limit, now), Ok(()));
+		assert_eq!(take("test_refill", limit, now), Ok(()));
+		assert!(take("test_refill", limit, now).is_err());
+
+		//	Five seconds bring back one token.
+		let later = now + Duration::from_secs(5);
+		assert_eq!(take("test_refill", limit, later), Ok(()));
+		assert!(take("test_refill", limit, later).is_err());
+
+		//	An idle bucket holds no more than `requests` tokens.
+		let idle = later + Duration::from_secs(600);
+		assert_eq!(take("test_refill", limit, idle), Ok(()));
+		assert_eq!(take("test_refill", limit, idle), Ok(()));
+		assert!(take("test_refill", limit, idle).is_err());
+	}
+
+	#[test]
+	fn retry_after_is_at_least_one_second() {
+		let limit = RateLimit::new(100, 1);
+		let now = Instant::now();
+
+		for _ in 0..100 {
+			assert_eq!(take("test_retry_floor", limit, now), Ok(()));
+		}
+
+		assert_eq!(take("test_retry_floor", limit, now), Err(1));
+	}
+
+	#[test]
+	fn proxy_ranges_match_addresses_and_cidrs() {
+		assert!(rate_limit_proxy_matches("10.0.0.1", ip("10.0.0.1")));
+		assert!(!rate_limit_proxy_matches("10.0.0.1", ip("10.0.0.2")));
+		assert!(rate_limit_proxy_matches("10.0.0.0/8", ip("10.200.3.4")));
+		assert!(!rate_limit_proxy_matches("10.0.0.0/8", ip("11.0.0.1")));
+		assert!(rate_limit_proxy_matches("0.0.0.0/0", ip("203.0.113.9")));
+		assert!(rate_limit_proxy_matches("fd00::/8", ip("fd12::1")));
+		assert!(!rate_limit_proxy_matches("fd00::/8", ip("10.0.0.1")));
+		assert!(!rate_limit_proxy_matches("not-an-ip", ip("10.0.0.1")));
+	}
+
+	#[test]
+	fn client_ip_ignores_forwarded_for_from_untrusted_peers() {
+		let headers = forwarded("198.51.100.7");
+
+		assert_eq!(
+			rate_limit_client_ip_from(&headers, Some(ip("203.0.113.9")), &[]),
+			Some(ip("203.0.113.9"))
+		);
+		assert_eq!(
+			rate_limit_client_ip_from(&headers, Some(ip("203.0.113.9")), &trusted(&["10.0.0.0/8"])),
+			Some(ip("203.0.113.9"))
+		);
+		assert_eq!(rate_limit_client_ip_from(&headers, None, &trusted(&["10.0.0.0/8"])), None);
+	}
+
+	#[test]
+	fn client_ip_walks_forwarded_for_through_trusted_proxies() {
+		let proxies = trusted(&["10.0.0.0/8", "172.16.0.1"]);
+
+		//	The right-most hop that is not a trusted proxy is the client, anything left of it
+		//	was written by the client and can be spoofed.
+		let headers = forwarded("1.1.1.1, 198.51.100.7, 172.16.0.1, 10.1.2.3");
+		assert_eq!(
+			rate_limit_client_ip_from(&headers, Some(ip("10.0.0.5")), &proxies),
+			Some(ip("198.51.100.7"))
+		);
+
+		//	Only proxies in the chain, the first one is the best guess.
+		let headers = forwarded("172.16.0.1, 10.1.2.3");
+		assert_eq!(
+			rate_limit_client_ip_from(&headers, Some(ip("10.0.0.5")), &proxies),
+			Some(ip("172.16.0.1"))
+		);
+
+		//	No usable header, the trusted peer itself is the client.
+		assert_eq!(
+			rate_limit_client_ip_from(&HeaderMap::new(), Some(ip("10.0.0.5")), &proxies),
+			Some(ip("10.0.0.5"))
+		);
+		assert_eq!(
+			rate_limit_client_ip_from(&forwarded("garbage"), Some(ip("10.0.0.5")), &proxies),
+			Some(ip("10.0.0.5"))
+		);
+	}
+}
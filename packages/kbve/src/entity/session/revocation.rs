@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+use crate::db::{ Pool };
+use crate::error::{ KbveError };
+use crate::utility::{ convert_ulid_string_to_bytes };
+
+//	How long a "still live" answer from the sessions table is trusted before it is checked again.
+//	Revocations made by this instance are visible at once, other instances see them within this window.
+pub const SESSION_CACHE_TTL_SECONDS: i64 = 60;
+
+//	Once the cache grows past this, stale entries are pruned on the next insert.
+pub const SESSION_CACHE_MAX_ENTRIES: usize = 50_000;
+
+#[derive(Debug, Clone, Copy)]
+struct SessionCacheEntry {
+	revoked: bool,
+	checked_at: i64,
+}
+
+//	jti (session ulid string) -> last known revocation state.
+static SESSION_REVOCATION_CACHE: Lazy<DashMap<String, SessionCacheEntry>> = Lazy::new(
+	DashMap::new
+);
+
+fn session_cache_insert(jti: &str, revoked: bool) {
+	let now = chrono::Utc::now().timestamp();
+
+	if SESSION_REVOCATION_CACHE.len() >= SESSION_CACHE_MAX_ENTRIES {
+		SESSION_REVOCATION_CACHE.retain(
+			|_, entry| now - entry.checked_at < SESSION_CACHE_TTL_SECONDS
+		);
+	}
+
+	SESSION_REVOCATION_CACHE.insert(jti.to_string(), SessionCacheEntry {
+		revoked,
+		checked_at: now,
+	});
+}
+
+pub fn session_cache_mark_revoked(jti: &str) {
+	session_cache_insert(jti, true);
+}
+
+pub fn session_cache_mark_revoked_bytes(session_ulid: &[u8]) {
+	if let Ok(jti) = crate::utility::convert_ulid_bytes_to_string(session_ulid) {
+		session_cache_mark_revoked(&jti);
+	}
+}
+
+//	Revoked is final, so those entries never go back to the database.
+pub async fn session_jti_is_revoked(
+	jti: &str,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	if let Some(entry) = SESSION_REVOCATION_CACHE.get(jti) {
+		let fresh =
+			chrono::Utc::now().timestamp() - entry.checked_at <
+			SESSION_CACHE_TTL_SECONDS;
+
+		if entry.revoked || fresh {
+			return Ok(entry.revoked);
+		}
+	}
+
+	let session_ulid = convert_ulid_string_to_bytes(jti).map_err(|_|
+		KbveError::Unauthorized("invalid_jti")
+	)?;
+
+	let revoked = crate::guild::hazardous_session_is_revoked(
+		session_ulid,
+		pool
+	).await?;
+
+	session_cache_insert(jti, revoked);
+
+	Ok(revoked)
+}
@@ -0,0 +1,284 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ Context, Poll };
+
+use axum::{
+	http::{ StatusCode, Request },
+	response::{ IntoResponse, Response },
+};
+
+use diesel::prelude::*;
+
+use serde::{ Serialize, Deserialize };
+use serde_json::json;
+
+use tower::{ Layer, Service };
+
+use crate::db::{ Pool, run_blocking };
+use crate::error::{ KbveError };
+use crate::schema::{ users };
+use crate::response::{ GenericResponse };
+
+//	?	[Scopes]
+
+pub const SCOPE_ALL: &str = "*";
+
+pub const SCOPE_PROFILE_READ: &str = "profile:read";
+pub const SCOPE_PROFILE_WRITE: &str = "profile:write";
+pub const SCOPE_CHARACTER_READ: &str = "character:read";
+pub const SCOPE_CHARACTER_WRITE: &str = "character:write";
+//	Lets services grant experience, no player role holds it.
+pub const SCOPE_CHARACTER_EXPERIENCE: &str = "character:experience";
+//	Lets services damage, heal and drain characters, also service only.
+pub const SCOPE_CHARACTER_VITALS: &str = "character:vitals";
+//	Lets services hand out items, service only as well.
+pub const SCOPE_CHARACTER_ITEMS: &str = "character:items";
+pub const SCOPE_APIKEY_READ: &str = "apikey:read";
+pub const SCOPE_APIKEY_WRITE: &str = "apikey:write";
+pub const SCOPE_SETTINGS_READ: &str = "settings:read";
+pub const SCOPE_SETTINGS_WRITE: &str = "settings:write";
+pub const SCOPE_ADMIN_READ: &str = "admin:read";
+pub const SCOPE_ADMIN_SHIELDWALL: &str = "admin:shieldwall";
+pub const SCOPE_ADMIN_GLOBALS: &str = "admin:globals";
+pub const SCOPE_ADMIN_AUDIT: &str = "admin:audit";
+pub const SCOPE_ADMIN_CHARACTERS: &str = "admin:characters";
+
+pub const KNOWN_SCOPES: &[&str] = &[
+	SCOPE_PROFILE_READ,
+	SCOPE_PROFILE_WRITE,
+	SCOPE_CHARACTER_READ,
+	SCOPE_CHARACTER_WRITE,
+	SCOPE_CHARACTER_EXPERIENCE,
+	SCOPE_CHARACTER_VITALS,
+	SCOPE_CHARACTER_ITEMS,
+	SCOPE_APIKEY_READ,
+	SCOPE_APIKEY_WRITE,
+	SCOPE_SETTINGS_READ,
+	SCOPE_SETTINGS_WRITE,
+	SCOPE_ADMIN_READ,
+	SCOPE_ADMIN_SHIELDWALL,
+	SCOPE_ADMIN_GLOBALS,
+	SCOPE_ADMIN_AUDIT,
+	SCOPE_ADMIN_CHARACTERS,
+];
+
+const PLAYER_SCOPES: &[&str] = &[
+	SCOPE_PROFILE_READ,
+	SCOPE_PROFILE_WRITE,
+	SCOPE_CHARACTER_READ,
+	SCOPE_CHARACTER_WRITE,
+	SCOPE_APIKEY_READ,
+	SCOPE_APIKEY_WRITE,
+	SCOPE_SETTINGS_READ,
+	SCOPE_SETTINGS_WRITE,
+];
+
+const MODERATOR_SCOPES: &[&str] = &[
+	SCOPE_PROFILE_READ,
+	SCOPE_PROFILE_WRITE,
+	SCOPE_CHARACTER_READ,
+	SCOPE_CHARACTER_WRITE,
+	SCOPE_APIKEY_READ,
+	SCOPE_APIKEY_WRITE,
+	SCOPE_SETTINGS_READ,
+	SCOPE_SETTINGS_WRITE,
+	SCOPE_ADMIN_READ,
+];
+
+const ADMIN_SCOPES: &[&str] = &[SCOPE_ALL];
+
+//	A granted scope of `*` covers everything, `character:*` covers every `character:` scope.
+pub fn scope_matches(granted: &str, required: &str) -> bool {
+	if granted == SCOPE_ALL || granted == required {
+		return true;
+	}
+
+	match granted.strip_suffix('*') {
+		Some(prefix) if prefix.ends_with(':') => required.starts_with(prefix),
+		_ => false,
+	}
+}
+
+pub fn is_known_scope(scope: &str) -> bool {
+	if scope == SCOPE_ALL || KNOWN_SCOPES.contains(&scope) {
+		return true;
+	}
+
+	match scope.strip_suffix('*') {
+		Some(prefix) if prefix.ends_with(':') =>
+			KNOWN_SCOPES.iter().any(|known| known.starts_with(prefix)),
+		_ => false,
+	}
+}
+
+//	?	[Roles]
+
+//	Stored in `users.role`, unknown values fall back to the least privileged role.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+	Player = 0,
+	Supporter = 1,
+	Moderator = 2,
+	Admin = 3,
+}
+
+impl Role {
+	pub fn from_i32(role: i32) -> Self {
+		match role {
+			1 => Role::Supporter,
+			2 => Role::Moderator,
+			3 => Role::Admin,
+			_ => Role::Player,
+		}
+	}
+
+	pub fn scopes(&self) -> &'static [&'static str] {
+		match self {
+			Role::Player | Role::Supporter => PLAYER_SCOPES,
+			Role::Moderator => MODERATOR_SCOPES,
+			Role::Admin => ADMIN_SCOPES,
+		}
+	}
+}
+
+//	Inserted into the request extensions by the auth middlewares, read by `RequireScope`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Scopes {
+	pub role: Role,
+	pub granted: Vec<String>,
+}
+
+impl Scopes {
+	pub fn for_role(role: Role) -> Self {
+		Scopes {
+			role,
+			granted: role
+				.scopes()
+				.iter()
+				.map(|scope| scope.to_string())
+				.collect(),
+		}
+	}
+
+	//	An API key never holds more than its owner's role, even if the role was lowered after issuing.
+	pub fn for_api_key(role: Role, permissions: &[String]) -> Self {
+		let owner = Scopes::for_role(role);
+
+		Scopes {
+			role,
+			granted: permissions
+				.iter()
+				.filter(|permission| owner.allows(permission))
+				.cloned()
+				.collect(),
+		}
+	}
+
+	pub fn allows(&self, required: &str) -> bool {
+		self.granted.iter().any(|granted| scope_matches(granted, required))
+	}
+}
+
+pub async fn hazardous_blocking_role_by_userid(
+	dirty_userid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Role, KbveError> {
+	run_blocking(pool, move |conn| {
+		users::table
+			.filter(users::userid.eq(dirty_userid))
+			.select(users::role)
+			.first::<i32>(conn)
+			.map(Role::from_i32)
+			.map_err(|err| {
+				match err {
+					diesel::result::Error::NotFound => KbveError::NotFound("user_not_found"),
+					err => KbveError::from(err),
+				}
+			})
+	}).await
+}
+
+//	Role lookup shared by the JWT middlewares, the token only carries the ulid string.
+pub async fn hazardous_scopes_for_userid(
+	userid: &str,
+	pool: Arc<Pool>
+) -> Result<Scopes, KbveError> {
+	let userid_bytes = crate::utility
+		::convert_ulid_string_to_bytes(userid)
+		.map_err(|_| KbveError::Validation("invalid_ulid"))?;
+
+	let role = hazardous_blocking_role_by_userid(userid_bytes, pool).await?;
+
+	Ok(Scopes::for_role(role))
+}
+
+//	?	[RequireScope]
+
+/**
+	Route layer that only lets a request through when the `Scopes` placed by
+	`AuthLayer` or `middleware_api_key` allow the given scope.
+	It has to sit inside one of those layers, e.g.
+
+	.route_layer(RequireScope(SCOPE_CHARACTER_WRITE))
+	.route_layer(AuthLayer::<TokenJWT>::new(pool))
+**/
+#[derive(Debug, Clone, Copy)]
+pub struct RequireScope(pub &'static str);
+
+impl<S> Layer<S> for RequireScope {
+	type Service = RequireScopeService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		RequireScopeService { inner, scope: self.0 }
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct RequireScopeService<S> {
+	inner: S,
+	scope: &'static str,
+}
+
+impl<S, B> Service<Request<B>>
+	for RequireScopeService<S>
+	where
+		S: Service<Request<B>, Response = Response> + Send + 'static,
+		S::Future: Send + 'static,
+		B: Send + 'static
+{
+	type Response = Response;
+	type Error = S::Error;
+	type Future = Pin<
+		Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>
+	>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, req: Request<B>) -> Self::Future {
+		let response = match req.extensions().get::<Scopes>() {
+			Some(scopes) if scopes.allows(self.scope) => {
+				return Box::pin(self.inner.call(req));
+			}
+			Some(_) =>
+				GenericResponse::error(
+					json!({"required_scope": self.scope}),
+					json!("Missing the permission required for this route"),
+					"insufficient_scope".to_string(),
+					StatusCode::FORBIDDEN
+				),
+			None =>
+				GenericResponse::error(
+					json!({"required_scope": self.scope}),
+					json!("Authentication is required for this route"),
+					"unauthenticated".to_string(),
+					StatusCode::UNAUTHORIZED
+				),
+		};
+
+		Box::pin(async move { Ok(response.into_response()) })
+	}
+}
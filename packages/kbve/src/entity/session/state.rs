@@ -1,10 +1,13 @@
 use jedi::builder::ValidatorBuilder;
 use crate::db::{ self };
+use crate::utils::{ MailSender, LogMailSender, CaptchaVerifier, GlobalCaptchaVerifier };
 use std::sync::Arc;
 
 pub struct KbveState {
 	pub db_pool: Arc<db::Pool>,
 	pub validator_builder: Arc<ValidatorBuilder<String, String>>,
+	pub mailer: Arc<dyn MailSender>,
+	pub captcha: Arc<dyn CaptchaVerifier>,
 }
 
 impl KbveState {
@@ -12,6 +15,21 @@ impl KbveState {
 		db_pool: Arc<db::Pool>,
 		validator_builder: Arc<ValidatorBuilder<String, String>>
 	) -> Self {
-		KbveState { db_pool, validator_builder }
+		KbveState {
+			db_pool,
+			validator_builder,
+			mailer: Arc::new(LogMailSender),
+			captcha: Arc::new(GlobalCaptchaVerifier),
+		}
+	}
+
+	pub fn with_mailer(mut self, mailer: Arc<dyn MailSender>) -> Self {
+		self.mailer = mailer;
+		self
+	}
+
+	pub fn with_captcha(mut self, captcha: Arc<dyn CaptchaVerifier>) -> Self {
+		self.captcha = captcha;
+		self
 	}
 }
@@ -7,4 +7,16 @@ pub struct TokenJWT {
 	pub username: String,
 	pub iat: usize,
 	pub exp: usize,
-}
\ No newline at end of file
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub jti: Option<String>,
+}
+
+//	Claims resolved from an `X-API-Key` header by `middleware_api_key`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenAPIKey {
+	pub keyid: String,
+	pub userid: String,
+	pub username: String,
+	pub role: i32,
+	pub permissions: Vec<String>,
+}
@@ -0,0 +1,116 @@
+//!         [ERROR]
+//?         Typed errors for the handlers and their HTTP mapping.
+
+use axum::{
+	http::{ StatusCode, HeaderValue, header },
+	response::{ IntoResponse, Response },
+};
+
+use serde_json::json;
+
+use crate::db::{ DbError };
+
+use crate::entity::response::{ GenericResponse };
+
+//	?	[KbveError]
+
+/**
+	Every error a handler can answer with, grouped by what the client should do about it.
+
+	The `&'static str` carried by each variant is its code, a stable snake_case string that
+	ends up in the `error` field of the `GenericResponse` body and in the `x-kbve-shield`
+	header. Clients branch on the code, the message next to it is for humans and may change.
+
+	Validation 400, auth 401 or 403, not found 404, conflict 409, rate limit 429 with
+	`Retry-After`, infrastructure 500, or 503 for a database that can not be reached and for
+	work that kept losing a race and is worth retrying.
+**/
+
+#[derive(Debug, thiserror::Error)]
+pub enum KbveError {
+	#[error("The request is not valid")]
+	Validation(&'static str),
+	#[error("Authentication failed")]
+	Unauthorized(&'static str),
+	#[error("Not allowed")]
+	Forbidden(&'static str),
+	#[error("Not found")]
+	NotFound(&'static str),
+	#[error("Conflicts with the current state")]
+	Conflict(&'static str),
+	#[error("Too many requests, retry in {retry_after} seconds")]
+	RateLimited {
+		retry_after: u64,
+	},
+	#[error("Database error")]
+	Database(#[from] DbError),
+	#[error("Internal error")]
+	Internal(&'static str),
+	#[error("Temporarily unavailable, try again")]
+	Unavailable(&'static str),
+}
+
+impl KbveError {
+	pub fn code(&self) -> &'static str {
+		match self {
+			KbveError::Validation(code) => code,
+			KbveError::Unauthorized(code) => code,
+			KbveError::Forbidden(code) => code,
+			KbveError::NotFound(code) => code,
+			KbveError::Conflict(code) => code,
+			KbveError::RateLimited { .. } => "rate_limited",
+			KbveError::Database(error) => error.code(),
+			KbveError::Internal(code) => code,
+			KbveError::Unavailable(code) => code,
+		}
+	}
+
+	pub fn status(&self) -> StatusCode {
+		match self {
+			KbveError::Validation(_) => StatusCode::BAD_REQUEST,
+			KbveError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+			KbveError::Forbidden(_) => StatusCode::FORBIDDEN,
+			KbveError::NotFound(_) => StatusCode::NOT_FOUND,
+			KbveError::Conflict(_) => StatusCode::CONFLICT,
+			KbveError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+			KbveError::Database(DbError::Pool(_)) => StatusCode::SERVICE_UNAVAILABLE,
+			KbveError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			KbveError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+			KbveError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+		}
+	}
+}
+
+//	Query errors without a more specific meaning are infrastructure, the details stay in the log.
+impl From<diesel::result::Error> for KbveError {
+	fn from(error: diesel::result::Error) -> Self {
+		match error {
+			diesel::result::Error::NotFound => KbveError::NotFound("not_found"),
+			diesel::result::Error::DatabaseError(
+				diesel::result::DatabaseErrorKind::UniqueViolation,
+				_,
+			) => KbveError::Conflict("already_exists"),
+			error => {
+				tracing::error!("[Error] query failed: {}", error);
+				KbveError::Internal("db_error")
+			}
+		}
+	}
+}
+
+impl IntoResponse for KbveError {
+	fn into_response(self) -> Response {
+		let mut response = GenericResponse::error(
+			json!({}),
+			json!(self.to_string()),
+			self.code().to_string(),
+			self.status()
+		).into_response();
+
+		if let KbveError::RateLimited { retry_after } = self {
+			response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+		}
+
+		response
+	}
+}
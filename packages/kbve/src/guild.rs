@@ -8,35 +8,45 @@ use diesel::insert_into;
 
 use chrono::Utc;
 
-use crate::db::{ Pool };
+use crate::db::{ DbConnection, Pool, RowId, db_for_update, run_blocking };
+use crate::error::{ KbveError };
+use crate::runes::{ AuthTwoFactorSchema, SessionRefreshOutcome };
+use crate::models::{ Session };
 
 use crate::{
-	spellbook_pool_conn,
 	spellbook_hazardous_boolean_exist_via_ulid,
 	spellbook_hazardous_task_fetch,
 	spellbook_generate_ulid_bytes,
 };
 
 // use crate::schema::{ auth, profile, users, apikey, n8n, appwrite, globals };
-use crate::schema::{ auth, profile, users, apikey, n8n };
+use crate::schema::{
+	auth,
+	profile,
+	users,
+	apikey,
+	n8n,
+	sessions,
+	settings,
+	characters,
+	oauth_accounts,
+};
 
 
 pub async fn hazardous_boolean_username_exist(
 	clean_username: String,
 	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
-
-	match
-		users::table
-			.filter(users::username.eq(clean_username))
-			.select(users::userid)
-			.first::<Vec<u8>>(&mut conn)
-	{
-		Ok(_) => Ok(true),
-		Err(diesel::NotFound) => Ok(false),
-		Err(_) => Err("db_error"),
-	}
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		Ok(
+			users::table
+				.filter(users::username.eq(clean_username))
+				.select(users::userid)
+				.first::<Vec<u8>>(conn)
+				.optional()?
+				.is_some()
+		)
+	}).await
 }
 
 spellbook_hazardous_boolean_exist_via_ulid!(
@@ -84,118 +94,859 @@ spellbook_hazardous_task_fetch!(
 );
 
 
+//			?[Hazardous] -> Unit of Work
+
+/**
+	Writes that only make sense together. `hazardous_unit_of_work` runs `work` inside one
+	transaction, an `Err` from any step rolls back every statement before it, so a caller
+	never has to clean up after itself. The steps are plain functions on the connection,
+	a failed query surfaces through `KbveError::from` unless the step maps it first.
+**/
+
+pub async fn hazardous_unit_of_work<T, F>(pool: Arc<Pool>, work: F) -> Result<T, KbveError>
+	where
+		F: FnOnce(&mut DbConnection) -> Result<T, KbveError> + Send + 'static,
+		T: Send + 'static
+{
+	run_blocking(pool, move |conn| conn.transaction::<T, KbveError, _>(work)).await
+}
+
+fn is_unique_violation(error: &diesel::result::Error) -> bool {
+	matches!(
+		error,
+		diesel::result::Error::DatabaseError(
+			diesel::result::DatabaseErrorKind::UniqueViolation,
+			_
+		)
+	)
+}
+
 //			?[Hazardous] -> Create User
 
-pub async fn hazardous_create_user(
-	clean_username: String,
-	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
-
-	let clean_ulid = spellbook_generate_ulid_bytes!();
-
-	match
-		insert_into(users::table)
-			.values((
-				users::id.eq(0), // Setting to 0
-				users::userid.eq(clean_ulid), // Adding the clean ulid!
-				users::username.eq(clean_username),
-				users::role.eq(0), // Setting role to 0
-				users::reputation.eq(0), // Setting reputation to 0
-				users::exp.eq(0), // Setting exp to 0
-				users::created_at.eq(Utc::now().naive_utc()), // Setting current UTC time
-			))
-			.execute(&mut conn)
-	{
-		Ok(_) => Ok(true),
-		Err(_) => Err("Failed to insert user into database"),
-	}
+pub fn hazardous_create_user(
+	conn: &mut DbConnection,
+	clean_user_ulid: &[u8],
+	clean_username: &str
+) -> QueryResult<usize> {
+	insert_into(users::table)
+		.values((
+			users::userid.eq(clean_user_ulid), // Adding the clean ulid!
+			users::username.eq(clean_username),
+			users::role.eq(0), // Setting role to 0
+			users::reputation.eq(0), // Setting reputation to 0
+			users::exp.eq(0), // Setting exp to 0
+			users::created_at.eq(Utc::now().naive_utc()), // Setting current UTC time
+		))
+		.execute(conn)
 }
 
 pub async fn task_fetch_userid_by_username(
 	username: String,
 	pool: Arc<Pool>
-) -> Result<Vec<u8>, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
+) -> Result<Vec<u8>, KbveError> {
+	run_blocking(pool, move |conn| {
+		//let clean_username = spellbook_internal_username!(&username.clone());
+
+		let clean_username = match crate::utility::sanitize_username(&username) {
+			Ok(sanitized) => sanitized,
+	        Err(_) => return Err(KbveError::Validation("invalid_username")),
+		};
+
+		match
+			users::table
+				.filter(users::username.eq(clean_username))
+				.select(users::userid)
+				.first::<Vec<u8>>(conn)
+		{
+			Ok(user_id) => Ok(user_id),
+			Err(diesel::NotFound) => Err(KbveError::NotFound("user_not_found")),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+pub fn hazardous_create_auth_from_ulid(
+	conn: &mut DbConnection,
+	clean_hash_password: &str,
+	clean_email: &str,
+	clean_verification_token_hash: &str,
+	clean_user_ulid: &[u8]
+) -> QueryResult<usize> {
+	let clean_auth_ulid = spellbook_generate_ulid_bytes!();
+
+	insert_into(auth::table)
+		.values((
+			auth::ulid.eq(clean_auth_ulid),
+			auth::userid.eq(clean_user_ulid),
+			auth::email.eq(clean_email),
+			auth::hash.eq(clean_hash_password),
+			auth::salt.eq("0"),
+			auth::password_reset_token.eq("0"),
+			auth::password_reset_expiry.eq(Utc::now().naive_utc()),
+			auth::verification_token.eq(clean_verification_token_hash),
+			auth::verification_expiry.eq(
+				Utc::now().naive_utc() +
+					chrono::Duration::hours(crate::authentication::VERIFICATION_EXPIRY_HOURS)
+			),
+			auth::status.eq(crate::authentication::AUTH_STATUS_UNVERIFIED),
+			auth::last_login_at.eq(Utc::now().naive_utc()),
+			auth::failed_login_attempts.eq(0),
+			auth::lockout_until.eq(Utc::now().naive_utc()),
+			auth::two_factor_secret.eq("0"),
+			auth::recovery_codes.eq("0"),
+		))
+		.execute(conn)
+}
+
+pub fn hazardous_create_profile_from_ulid(
+	conn: &mut DbConnection,
+	clean_name: &str,
+	clean_user_ulid: &[u8]
+) -> QueryResult<usize> {
+	let clean_profile_ulid = spellbook_generate_ulid_bytes!();
+
+	insert_into(profile::table)
+		.values((
+			profile::ulid.eq(clean_profile_ulid),
+			profile::userid.eq(clean_user_ulid),
+			profile::name.eq(clean_name),
+			profile::bio.eq("default"),
+			profile::unsplash.eq("0"),
+			profile::github.eq("0"),
+			profile::instagram.eq("0"),
+			profile::discord.eq("0"),
+		))
+		.execute(conn)
+}
+
+//			?[Hazardous] -> Register
+
+//	Creates the users, auth and profile rows of a new player as one unit of work and
+//	returns the user ulid. A username or email taken by a concurrent registration
+//	surfaces as a `Conflict` with `username-exists` or `email-exists`.
+pub async fn hazardous_register_account(
+	clean_username: String,
+	clean_email: String,
+	clean_hash_password: String,
+	clean_verification_token_hash: String,
+	pool: Arc<Pool>
+) -> Result<Vec<u8>, KbveError> {
+	hazardous_unit_of_work(pool, move |conn| {
+		let clean_user_ulid = spellbook_generate_ulid_bytes!();
+
+		hazardous_create_user(conn, &clean_user_ulid, &clean_username).map_err(|e| {
+			if is_unique_violation(&e) {
+				KbveError::Conflict("username-exists")
+			} else {
+				KbveError::from(e)
+			}
+		})?;
+
+		hazardous_create_auth_from_ulid(
+			conn,
+			&clean_hash_password,
+			&clean_email,
+			&clean_verification_token_hash,
+			&clean_user_ulid
+		).map_err(|e| {
+			if is_unique_violation(&e) {
+				KbveError::Conflict("email-exists")
+			} else {
+				KbveError::from(e)
+			}
+		})?;
+
+		hazardous_create_profile_from_ulid(conn, &clean_username, &clean_user_ulid)?;
+
+		Ok(clean_user_ulid)
+	}).await
+}
+
+//			?[Hazardous] -> Account Repair
 
-	//let clean_username = spellbook_internal_username!(&username.clone());
+/**
+	Cleans up accounts left behind by registrations from before they were transactional.
 
-	let clean_username = match crate::utility::sanitize_username(&username) {
-		Ok(sanitized) => sanitized,
-        Err(_) => return Err("Username failed sanitization"),
-	};
+	A `users` row without an `auth` row can never sign in and only holds its username, it is
+	deleted along with any profile, sessions and settings. One that already owns characters,
+	api keys or linked oauth accounts is skipped and reported instead. A `users` row with an
+	`auth` row but no profile is kept and gets its default profile back.
 
-	match
-		users::table
-			.filter(users::username.eq(clean_username))
-			.select(users::userid)
-			.first::<Vec<u8>>(&mut conn)
-	{
-		Ok(user_id) => Ok(user_id),
-		Err(_) => Err("User not found or database error"),
-	}
+	Only accounts older than `ACCOUNT_REPAIR_GRACE_MINUTES` are touched, so a registration
+	still in flight on another instance is never mistaken for a partial one.
+**/
+
+pub const ACCOUNT_REPAIR_GRACE_MINUTES: i64 = 10;
+
+#[derive(Debug, Default)]
+pub struct AccountRepairReport {
+	pub removed: Vec<String>,
+	pub profiles_restored: Vec<String>,
+	//	`(username, reason)`
+	pub skipped: Vec<(String, &'static str)>,
+}
+
+fn hazardous_remove_partial_account(
+	conn: &mut DbConnection,
+	clean_user_ulid: &[u8]
+) -> Result<(), KbveError> {
+	conn.transaction::<(), KbveError, _>(|conn| {
+		let owns_characters = characters::table
+			.filter(characters::userid.eq(clean_user_ulid))
+			.select(characters::id)
+			.first::<RowId>(conn)
+			.optional()?
+			.is_some();
+
+		let owns_apikeys = apikey::table
+			.filter(apikey::userid.eq(clean_user_ulid))
+			.select(apikey::id)
+			.first::<RowId>(conn)
+			.optional()?
+			.is_some();
+
+		let owns_oauth = oauth_accounts::table
+			.filter(oauth_accounts::userid.eq(clean_user_ulid))
+			.select(oauth_accounts::id)
+			.first::<RowId>(conn)
+			.optional()?
+			.is_some();
+
+		if owns_characters || owns_apikeys || owns_oauth {
+			return Err(KbveError::Conflict("has_dependents"));
+		}
+
+		diesel::delete(profile::table.filter(profile::userid.eq(clean_user_ulid))).execute(conn)?;
+		diesel::delete(sessions::table.filter(sessions::userid.eq(clean_user_ulid))).execute(conn)?;
+		diesel::delete(settings::table.filter(settings::userid.eq(clean_user_ulid))).execute(conn)?;
+		diesel::delete(users::table.filter(users::userid.eq(clean_user_ulid))).execute(conn)?;
+
+		Ok(())
+	})
+}
+
+//	`dry_run` only reports what would change.
+pub async fn hazardous_repair_partial_accounts(
+	dry_run: bool,
+	pool: Arc<Pool>
+) -> Result<AccountRepairReport, KbveError> {
+	run_blocking(pool, move |conn| {
+		let cutoff =
+			Utc::now().naive_utc() - chrono::Duration::minutes(ACCOUNT_REPAIR_GRACE_MINUTES);
+
+		let mut report = AccountRepairReport::default();
+
+		let without_auth = users::table
+			.filter(users::created_at.lt(cutoff))
+			.filter(
+				diesel::dsl::not(
+					diesel::dsl::exists(auth::table.filter(auth::userid.eq(users::userid)))
+				)
+			)
+			.select((users::userid, users::username))
+			.load::<(Vec<u8>, String)>(conn)?;
+
+		for (clean_user_ulid, username) in without_auth {
+			if dry_run {
+				report.removed.push(username);
+				continue;
+			}
+
+			match hazardous_remove_partial_account(conn, &clean_user_ulid) {
+				Ok(()) => report.removed.push(username),
+				Err(e) => report.skipped.push((username, e.code())),
+			}
+		}
+
+		let without_profile = users::table
+			.filter(users::created_at.lt(cutoff))
+			.filter(diesel::dsl::exists(auth::table.filter(auth::userid.eq(users::userid))))
+			.filter(
+				diesel::dsl::not(
+					diesel::dsl::exists(profile::table.filter(profile::userid.eq(users::userid)))
+				)
+			)
+			.select((users::userid, users::username))
+			.load::<(Vec<u8>, String)>(conn)?;
+
+		for (clean_user_ulid, username) in without_profile {
+			if dry_run {
+				report.profiles_restored.push(username);
+				continue;
+			}
+
+			match hazardous_create_profile_from_ulid(conn, &username, &clean_user_ulid) {
+				Ok(_) => report.profiles_restored.push(username),
+				Err(_) => report.skipped.push((username, "profile_failed")),
+			}
+		}
+
+		Ok(report)
+	}).await
+}
+
+//			?[Hazardous] -> Login Tracking
+
+//	Records a failed login against the auth row and, once the counter reaches `max_attempts`,
+//	stamps `lockout_until` and resets the counter. Returns true when this failure locked the account.
+pub async fn hazardous_login_failure_from_ulid(
+	clean_user_ulid: Vec<u8>,
+	max_attempts: i32,
+	lockout_minutes: i64,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn.transaction::<bool, diesel::result::Error, _>(|conn| {
+			diesel
+				::update(auth::table.filter(auth::userid.eq(clean_user_ulid.clone())))
+				.set(
+					auth::failed_login_attempts.eq(auth::failed_login_attempts + 1)
+				)
+				.execute(conn)?;
+
+			let locked = diesel
+				::update(
+					auth::table
+						.filter(auth::userid.eq(clean_user_ulid.clone()))
+						.filter(auth::failed_login_attempts.ge(max_attempts))
+				)
+				.set((
+					auth::lockout_until.eq(
+						Utc::now().naive_utc() +
+							chrono::Duration::minutes(lockout_minutes)
+					),
+					auth::failed_login_attempts.eq(0),
+				))
+				.execute(conn)?;
+
+			Ok(locked > 0)
+		}).map_err(KbveError::from)
+	}).await
+}
+
+pub async fn hazardous_login_success_from_ulid(
+	clean_user_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			diesel
+				::update(auth::table.filter(auth::userid.eq(clean_user_ulid)))
+				.set((
+					auth::failed_login_attempts.eq(0),
+					auth::last_login_at.eq(Utc::now().naive_utc()),
+				))
+				.execute(conn)
+		{
+			Ok(_) => Ok(true),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//			?[Hazardous] -> Password Reset
+
+//	Stores the hashed reset token for the email, returns false when no auth row matched.
+pub async fn hazardous_password_reset_store_by_email(
+	clean_email: String,
+	clean_token_hash: String,
+	expiry_minutes: i64,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			diesel
+				::update(auth::table.filter(auth::email.eq(clean_email)))
+				.set((
+					auth::password_reset_token.eq(clean_token_hash),
+					auth::password_reset_expiry.eq(
+						Utc::now().naive_utc() +
+							chrono::Duration::minutes(expiry_minutes)
+					),
+				))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
 }
 
-pub async fn hazardous_create_auth_from_ulid(
+//	Swaps in the new password hash and burns the token, so a token can only ever be redeemed
+//	once, and lifts any login lockout. Every session of the account is revoked in the same
+//	transaction, returns their ulids for the revocation cache or `None` for a bad token.
+pub async fn hazardous_password_reset_consume(
+	clean_token_hash: String,
 	clean_hash_password: String,
+	pool: Arc<Pool>
+) -> Result<Option<Vec<Vec<u8>>>, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+
+		conn.transaction::<Option<Vec<Vec<u8>>>, diesel::result::Error, _>(|conn| {
+			let Some(clean_user_ulid) = db_for_update!(
+				auth::table
+					.filter(auth::password_reset_token.eq(&clean_token_hash))
+					.filter(auth::password_reset_expiry.gt(now))
+					.select(auth::userid)
+			)
+				.first::<Vec<u8>>(conn)
+				.optional()? else {
+				return Ok(None);
+			};
+
+			let rows = diesel
+				::update(
+					auth::table
+						.filter(auth::userid.eq(&clean_user_ulid))
+						.filter(auth::password_reset_token.eq(&clean_token_hash))
+				)
+				.set((
+					auth::hash.eq(&clean_hash_password),
+					auth::password_reset_token.eq("0"),
+					auth::password_reset_expiry.eq(now),
+					auth::failed_login_attempts.eq(0),
+					auth::lockout_until.eq(now),
+				))
+				.execute(conn)?;
+
+			if rows == 0 {
+				return Ok(None);
+			}
+
+			hazardous_session_revoke_all(conn, &clean_user_ulid).map(Some)
+		}).map_err(KbveError::from)
+	}).await
+}
+
+//			?[Hazardous] -> Email Verification
+
+//	Marks the account behind an unexpired verification token as verified and burns the token.
+pub async fn hazardous_verification_confirm(
+	clean_token_hash: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+
+		match
+			diesel
+				::update(
+					auth::table
+						.filter(auth::verification_token.eq(clean_token_hash))
+						.filter(auth::verification_expiry.gt(now))
+						.filter(
+							auth::status.eq(
+								crate::authentication::AUTH_STATUS_UNVERIFIED
+							)
+						)
+				)
+				.set((
+					auth::status.eq(crate::authentication::AUTH_STATUS_VERIFIED),
+					auth::verification_token.eq("0"),
+					auth::verification_expiry.eq(now),
+				))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//	Replaces the verification token of an unverified account. The previous token must be at
+//	least `cooldown_minutes` old, which is derived from its expiry, so resends are rate limited
+//	without any extra state. Returns false when nothing was reissued.
+pub async fn hazardous_verification_reissue_by_email(
 	clean_email: String,
+	clean_token_hash: String,
+	cooldown_minutes: i64,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+		let lifetime = chrono::Duration::hours(
+			crate::authentication::VERIFICATION_EXPIRY_HOURS
+		);
+
+		match
+			diesel
+				::update(
+					auth::table
+						.filter(auth::email.eq(clean_email))
+						.filter(
+							auth::status.eq(
+								crate::authentication::AUTH_STATUS_UNVERIFIED
+							)
+						)
+						.filter(
+							auth::verification_expiry.le(
+								now + lifetime - chrono::Duration::minutes(cooldown_minutes)
+							)
+						)
+				)
+				.set((
+					auth::verification_token.eq(clean_token_hash),
+					auth::verification_expiry.eq(now + lifetime),
+				))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//			?[Hazardous] -> Two Factor
+
+pub async fn hazardous_two_factor_fetch_by_userid(
 	clean_user_ulid: Vec<u8>,
 	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
+) -> Result<AuthTwoFactorSchema, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			auth::table
+				.inner_join(users::table.on(auth::userid.eq(users::userid)))
+				.filter(auth::userid.eq(clean_user_ulid))
+				.select((
+					users::username,
+					auth::email,
+					users::userid,
+					auth::lockout_until,
+					auth::two_factor_secret,
+					auth::recovery_codes,
+					auth::two_factor_last_step,
+				))
+				.first::<AuthTwoFactorSchema>(conn)
+		{
+			Ok(data) => Ok(data),
+			Err(diesel::NotFound) => Err(KbveError::NotFound("auth_not_found")),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
 
-	let clean_auth_ulid = spellbook_generate_ulid_bytes!();
+//	Writes the 2FA columns, but only if `recovery_codes` still holds `expected_recovery_codes`.
+//	The compare-and-set keeps a recovery code single-use even under concurrent logins.
+pub async fn hazardous_two_factor_update_by_userid(
+	clean_user_ulid: Vec<u8>,
+	expected_recovery_codes: String,
+	clean_two_factor_secret: String,
+	clean_recovery_codes: String,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			diesel
+				::update(
+					auth::table
+						.filter(auth::userid.eq(clean_user_ulid))
+						.filter(auth::recovery_codes.eq(expected_recovery_codes))
+				)
+				.set((
+					auth::two_factor_secret.eq(clean_two_factor_secret),
+					auth::recovery_codes.eq(clean_recovery_codes),
+				))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
 
-	match
-		insert_into(auth::table)
-			.values((
-				auth::ulid.eq(clean_auth_ulid),
-				auth::userid.eq(clean_user_ulid),
-				auth::email.eq(clean_email),
-				auth::hash.eq(clean_hash_password),
-				auth::salt.eq("0"),
-				auth::password_reset_token.eq("0"),
-				auth::password_reset_expiry.eq(Utc::now().naive_utc()),
-				auth::verification_token.eq("0"),
-				auth::verification_expiry.eq(Utc::now().naive_utc()),
-				auth::status.eq(0),
-				auth::last_login_at.eq(Utc::now().naive_utc()),
-				auth::failed_login_attempts.eq(0),
-				auth::lockout_until.eq(Utc::now().naive_utc()),
-				auth::two_factor_secret.eq("0"),
-				auth::recovery_codes.eq("0"),
-			))
-			.execute(&mut conn)
-	{
-		Ok(_) => Ok(true),
-		Err(_) => Err("Failed to create auth row for user"),
-	}
-}
-
-pub async fn hazardous_create_profile_from_ulid(
-	clean_name: String,
+//	Records `step` as the last accepted TOTP step. The update only lands while the stored step is
+//	older, so of two logins racing with the same code only one gets `true`.
+pub async fn hazardous_two_factor_step_advance(
 	clean_user_ulid: Vec<u8>,
+	step: i64,
 	pool: Arc<Pool>
-) -> Result<bool, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			diesel
+				::update(
+					auth::table
+						.filter(auth::userid.eq(clean_user_ulid))
+						.filter(auth::two_factor_last_step.lt(step))
+				)
+				.set(auth::two_factor_last_step.eq(step))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
 
-	let clean_profile_ulid = spellbook_generate_ulid_bytes!();
+//	Turns 2FA off, guarded by the same compare-and-set as `hazardous_two_factor_update_by_userid`,
+//	and revokes every session of the account in the same transaction. Returns the revoked
+//	ulids for the revocation cache, or `None` when the recovery codes changed underneath.
+pub async fn hazardous_two_factor_disable_by_userid(
+	clean_user_ulid: Vec<u8>,
+	expected_recovery_codes: String,
+	pool: Arc<Pool>
+) -> Result<Option<Vec<Vec<u8>>>, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn.transaction::<Option<Vec<Vec<u8>>>, diesel::result::Error, _>(|conn| {
+			let rows = diesel
+				::update(
+					auth::table
+						.filter(auth::userid.eq(&clean_user_ulid))
+						.filter(auth::recovery_codes.eq(&expected_recovery_codes))
+				)
+				.set((
+					auth::two_factor_secret.eq(crate::authentication::TWO_FACTOR_DISABLED),
+					auth::recovery_codes.eq(crate::authentication::TWO_FACTOR_DISABLED),
+				))
+				.execute(conn)?;
 
-	match
-		insert_into(profile::table)
-			.values((
-				profile::ulid.eq(clean_profile_ulid),
-				profile::userid.eq(clean_user_ulid),
-				profile::name.eq(clean_name),
-				profile::bio.eq("default"),
-				profile::unsplash.eq("0"),
-				profile::github.eq("0"),
-				profile::instagram.eq("0"),
-				profile::discord.eq("0"),
-			))
-			.execute(&mut conn)
-	{
-		Ok(_) => Ok(true),
-		Err(_) => Err("Failed to insert profile into database"),
-	}
+			if rows == 0 {
+				return Ok(None);
+			}
+
+			hazardous_session_revoke_all(conn, &clean_user_ulid).map(Some)
+		}).map_err(KbveError::from)
+	}).await
+}
+
+//			?[Hazardous] -> Sessions
+
+//	Revokes every live session of the user on `conn`, for callers that already hold a transaction.
+pub fn hazardous_session_revoke_all(
+	conn: &mut DbConnection,
+	clean_user_ulid: &[u8]
+) -> QueryResult<Vec<Vec<u8>>> {
+	let live = sessions::table
+		.filter(sessions::userid.eq(clean_user_ulid))
+		.filter(sessions::revoked.eq(0))
+		.select(sessions::ulid)
+		.load::<Vec<u8>>(conn)?;
+
+	diesel
+		::update(
+			sessions::table
+				.filter(sessions::userid.eq(clean_user_ulid))
+				.filter(sessions::revoked.eq(0))
+		)
+		.set(sessions::revoked.eq(1))
+		.execute(conn)?;
+
+	Ok(live)
+}
+
+//	A session row backs every refresh token, its ulid is the `jti` of the access tokens it issues.
+pub async fn hazardous_session_create(
+	clean_session_ulid: Vec<u8>,
+	clean_user_ulid: Vec<u8>,
+	clean_refresh_hash: String,
+	clean_user_agent: String,
+	clean_ip: String,
+	lifetime_days: i64,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+
+		match
+			insert_into(sessions::table)
+				.values((
+					sessions::ulid.eq(clean_session_ulid),
+					sessions::userid.eq(clean_user_ulid),
+					sessions::refresh_hash.eq(clean_refresh_hash),
+					sessions::user_agent.eq(clean_user_agent),
+					sessions::ip.eq(clean_ip),
+					sessions::last_used_at.eq(now),
+					sessions::expires_at.eq(
+						now + chrono::Duration::days(lifetime_days)
+					),
+				))
+				.execute(conn)
+		{
+			Ok(_) => Ok(true),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//	Swaps the refresh hash of a live session for a new one. The update is conditioned on the
+//	presented hash, so two concurrent refreshes with the same token cannot both succeed.
+pub async fn hazardous_session_rotate(
+	clean_refresh_hash: String,
+	clean_new_refresh_hash: String,
+	lifetime_days: i64,
+	pool: Arc<Pool>
+) -> Result<SessionRefreshOutcome, KbveError> {
+	run_blocking(pool, move |conn| {
+		let now = Utc::now().naive_utc();
+
+		conn.transaction::<SessionRefreshOutcome, diesel::result::Error, _>(|conn| {
+			let live = sessions::table
+				.filter(sessions::refresh_hash.eq(&clean_refresh_hash))
+				.filter(sessions::revoked.eq(0))
+				.filter(sessions::expires_at.gt(now))
+				.select((sessions::ulid, sessions::userid))
+				.first::<(Vec<u8>, Vec<u8>)>(conn)
+				.optional()?;
+
+			if let Some((session, userid)) = live {
+				let rotated = diesel
+					::update(
+						sessions::table
+							.filter(sessions::ulid.eq(&session))
+							.filter(sessions::refresh_hash.eq(&clean_refresh_hash))
+					)
+					.set((
+						sessions::previous_hash.eq(&clean_refresh_hash),
+						sessions::refresh_hash.eq(&clean_new_refresh_hash),
+						sessions::last_used_at.eq(now),
+						sessions::expires_at.eq(
+							now + chrono::Duration::days(lifetime_days)
+						),
+					))
+					.execute(conn)?;
+
+				return Ok(match rotated {
+					1 => SessionRefreshOutcome::Rotated { session, userid },
+					_ => SessionRefreshOutcome::Invalid,
+				});
+			}
+
+			let reused = sessions::table
+				.filter(sessions::previous_hash.eq(&clean_refresh_hash))
+				.filter(sessions::revoked.eq(0))
+				.select(sessions::ulid)
+				.first::<Vec<u8>>(conn)
+				.optional()?;
+
+			match reused {
+				Some(session) => {
+					diesel
+						::update(sessions::table.filter(sessions::ulid.eq(&session)))
+						.set(sessions::revoked.eq(1))
+						.execute(conn)?;
+
+					Ok(SessionRefreshOutcome::Reused { session })
+				}
+				None => Ok(SessionRefreshOutcome::Invalid),
+			}
+		}).map_err(KbveError::from)
+	}).await
+}
+
+//	Username and email for the access token minted on refresh.
+pub async fn hazardous_session_identity_by_userid(
+	clean_user_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<(String, String), KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			auth::table
+				.inner_join(users::table.on(auth::userid.eq(users::userid)))
+				.filter(auth::userid.eq(clean_user_ulid))
+				.select((users::username, auth::email))
+				.first::<(String, String)>(conn)
+		{
+			Ok(identity) => Ok(identity),
+			Err(diesel::NotFound) => Err(KbveError::NotFound("auth_not_found")),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//	Missing sessions count as revoked, a token can only be as valid as the row behind it.
+pub async fn hazardous_session_is_revoked(
+	clean_session_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			sessions::table
+				.filter(sessions::ulid.eq(clean_session_ulid))
+				.select(sessions::revoked)
+				.first::<i32>(conn)
+		{
+			Ok(revoked) => Ok(revoked != 0),
+			Err(diesel::NotFound) => Ok(true),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+pub async fn hazardous_session_list_by_userid(
+	clean_user_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Vec<Session>, KbveError> {
+	run_blocking(pool, move |conn| {
+		sessions::table
+			.filter(sessions::userid.eq(clean_user_ulid))
+			.filter(sessions::revoked.eq(0))
+			.filter(sessions::expires_at.gt(Utc::now().naive_utc()))
+			.order(sessions::last_used_at.desc())
+			.load::<Session>(conn)
+			.map_err(KbveError::from)
+	}).await
+}
+
+//	Scoped to the owner so one user can never revoke another user's session.
+pub async fn hazardous_session_revoke_by_ulid(
+	clean_session_ulid: Vec<u8>,
+	clean_user_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<bool, KbveError> {
+	run_blocking(pool, move |conn| {
+		match
+			diesel
+				::update(
+					sessions::table
+						.filter(sessions::ulid.eq(clean_session_ulid))
+						.filter(sessions::userid.eq(clean_user_ulid))
+						.filter(sessions::revoked.eq(0))
+				)
+				.set(sessions::revoked.eq(1))
+				.execute(conn)
+		{
+			Ok(rows) => Ok(rows > 0),
+			Err(e) => Err(KbveError::from(e)),
+		}
+	}).await
+}
+
+//	Revokes the session behind a refresh token and returns its ulid, used by logout.
+pub async fn hazardous_session_revoke_by_refresh_hash(
+	clean_refresh_hash: String,
+	pool: Arc<Pool>
+) -> Result<Option<Vec<u8>>, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn.transaction::<Option<Vec<u8>>, diesel::result::Error, _>(|conn| {
+			let session = sessions::table
+				.filter(sessions::refresh_hash.eq(&clean_refresh_hash))
+				.filter(sessions::revoked.eq(0))
+				.select(sessions::ulid)
+				.first::<Vec<u8>>(conn)
+				.optional()?;
+
+			if let Some(ref session) = session {
+				diesel
+					::update(sessions::table.filter(sessions::ulid.eq(session)))
+					.set(sessions::revoked.eq(1))
+					.execute(conn)?;
+			}
+
+			Ok(session)
+		}).map_err(KbveError::from)
+	}).await
+}
+
+//	Revokes every live session of the user and returns their ulids so the caller can
+//	update the revocation cache.
+pub async fn hazardous_session_revoke_all_by_userid(
+	clean_user_ulid: Vec<u8>,
+	pool: Arc<Pool>
+) -> Result<Vec<Vec<u8>>, KbveError> {
+	run_blocking(pool, move |conn| {
+		conn.transaction::<Vec<Vec<u8>>, diesel::result::Error, _>(|conn| {
+			hazardous_session_revoke_all(conn, &clean_user_ulid)
+		}).map_err(KbveError::from)
+	}).await
 }
@@ -5,6 +5,9 @@
 //  * [MODS]
 pub mod schema;
 pub mod db;
+pub mod error;
+pub mod config;
+pub mod audit;
 pub mod models;
 pub mod utility;
 pub mod guild;
@@ -20,6 +23,9 @@ pub mod entity;
 
 pub use schema::*;
 pub use db::*;
+pub use error::*;
+pub use config::*;
+pub use audit::*;
 pub use models::*;
 pub use utility::*;
 pub use guild::*;
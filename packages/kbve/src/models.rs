@@ -5,12 +5,14 @@
 use diesel::prelude::*;
 use serde::{ Serialize, Deserialize};
 
+use crate::db::RowId;
+
 
 use chrono::NaiveDateTime;
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = apikey)]
 pub struct Apikey {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub userid: Vec<u8>,
     pub permissions: String,
@@ -21,7 +23,7 @@ pub struct Apikey {
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = appwrite)]
 pub struct Appwrite {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub userid: Vec<u8>,
     pub appwrite_endpoint: String,
@@ -31,10 +33,24 @@ pub struct Appwrite {
     pub created_at: NaiveDateTime,
 }
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLog {
+    pub id: RowId,
+    pub ulid: Vec<u8>,
+    pub actor: Option<Vec<u8>>,
+    pub event: String,
+    pub outcome: String,
+    pub ip: String,
+    pub user_agent: String,
+    pub detail: String,
+    pub created_at: NaiveDateTime,
+}
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = auth)]
 pub struct Auth {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub userid: Vec<u8>,
     pub email: String,
@@ -50,12 +66,25 @@ pub struct Auth {
     pub lockout_until: NaiveDateTime,
     pub two_factor_secret: String,
     pub recovery_codes: String,
+    pub two_factor_last_step: i64,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = character_items)]
+pub struct CharacterItem {
+    pub id: RowId,
+    pub ulid: Vec<u8>,
+    pub cid: Vec<u8>,
+    pub itemid: Vec<u8>,
+    pub quantity: i32,
+    pub slot: Option<String>,
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = characters)]
 pub struct Character {
-    pub id: u64,
+    pub id: RowId,
     pub cid: Vec<u8>,
     pub userid: Vec<u8>,
     pub hp: i32,
@@ -73,20 +102,38 @@ pub struct Character {
     pub experience: i32,
     pub reputation: i32,
     pub faith: i32,
+    pub stat_points: i32,
+    pub updated_at: NaiveDateTime,
 }
 
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = globals)]
 pub struct Global {
-    pub id: u64,
+    pub id: RowId,
     pub key: String,
     pub value: String,
 }
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = items)]
+pub struct Item {
+    pub id: RowId,
+    pub ulid: Vec<u8>,
+    pub name: String,
+    pub description: String,
+    pub slot: String,
+    pub max_stack: i32,
+    pub armour: i32,
+    pub agility: i32,
+    pub strength: i32,
+    pub intelligence: i32,
+    pub created_at: NaiveDateTime,
+}
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = n8n)]
 pub struct N8n {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub userid: Vec<u8>,
     pub webhook: String,
@@ -95,10 +142,23 @@ pub struct N8n {
     pub label: String,
 }
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = oauth_accounts)]
+pub struct OauthAccount {
+    pub id: RowId,
+    pub ulid: Vec<u8>,
+    pub userid: Vec<u8>,
+    pub provider: String,
+    pub subject: String,
+    pub email: String,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: NaiveDateTime,
+}
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = profile)]
 pub struct Profile {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub name: String,
     pub bio: String,
@@ -109,10 +169,26 @@ pub struct Profile {
     pub userid: Vec<u8>,
 }
 
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = sessions)]
+pub struct Session {
+    pub id: RowId,
+    pub ulid: Vec<u8>,
+    pub userid: Vec<u8>,
+    pub refresh_hash: String,
+    pub previous_hash: String,
+    pub user_agent: String,
+    pub ip: String,
+    pub revoked: i32,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = settings)]
 pub struct Setting {
-    pub id: u64,
+    pub id: RowId,
     pub ulid: Vec<u8>,
     pub userid: Vec<u8>,
     pub key: String,
@@ -122,7 +198,7 @@ pub struct Setting {
 #[derive(Queryable, Serialize, Deserialize, Debug)]
 #[diesel(table_name = users)]
 pub struct User {
-    pub id: u64,
+    pub id: RowId,
     pub userid: Vec<u8>,
     pub username: String,
     pub role: i32,
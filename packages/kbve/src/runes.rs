@@ -10,6 +10,8 @@ use dashmap::DashMap;
 
 use std::sync::{ Arc, OnceLock };
 
+use chrono::NaiveDateTime;
+
 use crate::{ spellbook_sanitize_fields };
 
 //			*Schema
@@ -19,6 +21,8 @@ use crate::schema::{ profile };
 
 
 //         [GLOBALS]
+//	Read-only compatibility shim, `config::config_sync_global` mirrors `KbveConfig` into it so
+//	`spellbook_get_global!` keeps working. New code reads `config::kbve_config()` instead.
 pub type GlobalStore = DashMap<String, String>;
 pub static GLOBAL: OnceLock<Arc<GlobalStore>> = OnceLock::new();
 
@@ -31,6 +35,8 @@ pub struct TokenRune {
 	pub username: String,
 	pub iat: usize,
 	pub exp: usize,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub jti: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -59,12 +65,91 @@ pub struct RegisterUserSchema {
 	pub captcha: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequestSchema {
+	pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetConfirmSchema {
+	pub token: String,
+	pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailVerificationSchema {
+	pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailVerificationResendSchema {
+	pub email: String,
+}
+
 #[derive(Debug, Queryable, Deserialize, Serialize, Clone)]
 pub struct AuthVerificationSchema {
 	pub username: String,
 	pub email: String,
 	pub userid: Vec<u8>,
 	pub hash: String,
+	pub failed_login_attempts: i32,
+	pub lockout_until: NaiveDateTime,
+	pub status: i32,
+	pub created_at: NaiveDateTime,
+	pub two_factor_secret: String,
+}
+
+#[derive(Debug, Queryable, Deserialize, Serialize, Clone)]
+pub struct AuthTwoFactorSchema {
+	pub username: String,
+	pub email: String,
+	pub userid: Vec<u8>,
+	pub lockout_until: NaiveDateTime,
+	pub two_factor_secret: String,
+	pub recovery_codes: String,
+	pub two_factor_last_step: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeSchema {
+	pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginTwoFactorSchema {
+	pub mfa_token: String,
+	pub code: String,
+}
+
+//	Claims of the short-lived token handed out between the password and the 2FA step.
+//	It deliberately lacks the `TokenRune` fields so it can never pass as a session token.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MfaPendingRune {
+	pub sub: String,
+	pub purpose: String,
+	//	Single use, `authentication::mfa_pending_consume` burns it once the second step passes.
+	pub jti: String,
+	pub iat: usize,
+	pub exp: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenSchema {
+	pub refresh_token: Option<String>,
+}
+
+//	Result of presenting a refresh token to `guild::hazardous_session_rotate`.
+#[derive(Debug)]
+pub enum SessionRefreshOutcome {
+	Rotated {
+		session: Vec<u8>,
+		userid: Vec<u8>,
+	},
+	//	An already rotated token came back, the whole session is revoked as it was likely stolen.
+	Reused {
+		session: Vec<u8>,
+	},
+	Invalid,
 }
 
 
@@ -35,6 +35,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    audit_log (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 16]
+        ulid -> Binary,
+        #[max_length = 16]
+        actor -> Nullable<Binary>,
+        #[max_length = 64]
+        event -> Varchar,
+        #[max_length = 16]
+        outcome -> Varchar,
+        #[max_length = 64]
+        ip -> Varchar,
+        #[max_length = 255]
+        user_agent -> Varchar,
+        #[max_length = 1024]
+        detail -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     auth (id) {
         id -> Unsigned<Bigint>,
@@ -61,6 +82,23 @@ diesel::table! {
         #[max_length = 255]
         two_factor_secret -> Varchar,
         recovery_codes -> Text,
+        two_factor_last_step -> Bigint,
+    }
+}
+
+diesel::table! {
+    character_items (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 16]
+        ulid -> Binary,
+        #[max_length = 16]
+        cid -> Binary,
+        #[max_length = 16]
+        itemid -> Binary,
+        quantity -> Integer,
+        #[max_length = 16]
+        slot -> Nullable<Varchar>,
+        created_at -> Timestamp,
     }
 }
 
@@ -88,6 +126,8 @@ diesel::table! {
         experience -> Integer,
         reputation -> Integer,
         faith -> Integer,
+        stat_points -> Integer,
+        updated_at -> Timestamp,
     }
 }
 
@@ -101,6 +141,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    items (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 16]
+        ulid -> Binary,
+        #[max_length = 64]
+        name -> Varchar,
+        #[max_length = 255]
+        description -> Varchar,
+        #[max_length = 16]
+        slot -> Varchar,
+        max_stack -> Integer,
+        armour -> Integer,
+        agility -> Integer,
+        strength -> Integer,
+        intelligence -> Integer,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     n8n (id) {
         id -> Unsigned<Bigint>,
@@ -119,6 +179,24 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    oauth_accounts (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 16]
+        ulid -> Binary,
+        #[max_length = 16]
+        userid -> Binary,
+        #[max_length = 64]
+        provider -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        #[max_length = 255]
+        email -> Varchar,
+        created_at -> Timestamp,
+        last_login_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     profile (id) {
         id -> Unsigned<Bigint>,
@@ -141,6 +219,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    sessions (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 16]
+        ulid -> Binary,
+        #[max_length = 16]
+        userid -> Binary,
+        #[max_length = 255]
+        refresh_hash -> Varchar,
+        #[max_length = 255]
+        previous_hash -> Varchar,
+        #[max_length = 255]
+        user_agent -> Varchar,
+        #[max_length = 64]
+        ip -> Varchar,
+        revoked -> Integer,
+        created_at -> Timestamp,
+        last_used_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     settings (id) {
         id -> Unsigned<Bigint>,
@@ -172,11 +272,16 @@ diesel::table! {
 diesel::allow_tables_to_appear_in_same_query!(
     apikey,
     appwrite,
+    audit_log,
     auth,
+    character_items,
     characters,
     globals,
+    items,
     n8n,
+    oauth_accounts,
     profile,
+    sessions,
     settings,
     users,
 );
@@ -4,26 +4,27 @@
 #[macro_export]
 macro_rules! spellbook_create_jwt {
 	($ulid:expr, $email:expr, $username:expr, $secret:expr, $hours:expr) => {
+		$crate::spellbook_create_jwt!($ulid, $email, $username, $secret, $hours, None::<String>)
+	};
+	//	Session bound token, `$jti` is the ulid of the `sessions` row that can revoke it.
+	($ulid:expr, $email:expr, $username:expr, $secret:expr, $hours:expr, $jti:expr) => {
 		{
 
-		use jsonwebtoken::{encode, EncodingKey, Header};
-
         let now = chrono::Utc::now();
         let exp = now + chrono::Duration::minutes($hours * 60);
 
-        let jwt_token = encode(
-            &Header::default(),
+        //  Signed by the newest key of the session key ring, HS256 over `$secret` without one.
+        crate::entity::session::jwt_encode(
             &crate::runes::TokenRune {
                 userid: $ulid.to_string(),
                 email: $email.to_string(),
                 username: $username.to_string(),
                 iat: now.timestamp() as usize,
                 exp: exp.timestamp() as usize,
+                jti: $jti,
             },
-            &EncodingKey::from_secret($secret.as_bytes()),
-        ).unwrap(); 
-
-		jwt_token
+            &$secret,
+        ).map_err(|_| crate::error::KbveError::Internal("jwt_encode_failed"))
 		}
 	};
 }
@@ -40,6 +41,8 @@ macro_rules! spellbook_create_cookie {
 	};
 }
 
+// Reads a key from the `runes::GLOBAL` mirror of the config. Kept for compatibility, typed
+// settings such as `jwt_secret` should come from `config::kbve_config()`.
 #[macro_export]
 macro_rules! spellbook_get_global {
 	($key:expr, $err:expr) => {
@@ -55,29 +58,26 @@ macro_rules! spellbook_get_global {
 
 // The `spellbook_error` macro is designed for use in Axum-based web applications.
 // It simplifies the creation of HTTP error responses. When invoked, it creates
-// an Axum response with a specified HTTP status code and a JSON body containing 
-// an error message. Additionally, it sets a custom header "x-kbve-shield" with 
-// the error message as its value. This macro is useful for consistently handling 
-// error responses throughout your web application.
+// an Axum response with a specified HTTP status code and a `GenericResponse` error body
+// whose `error` field holds the error message. `GenericResponse` also sets the custom
+// header "x-kbve-shield" with the error message as its value. New handlers should return
+// a `KbveError`, which picks the status code from the kind of error.
 
 #[macro_export]
  macro_rules! spellbook_error {
      // The macro takes two parameters: `$status` for the HTTP status code, and `$error` for the error message.
      ($status:expr, $error:expr) => {{
-         // Creates a JSON body with the provided error message.
-         let response_body = axum::Json(serde_json::json!({ "error": $error }));
+         let error = $error;
 
-         // Constructs an Axum response using the specified status code and the JSON body.
-         let mut response: axum::response::Response = ($status, response_body).into_response();
+         // Builds the error body, the message doubles as the machine-readable code.
+         let response: axum::response::Response = $crate::entity::response::GenericResponse::error(
+             serde_json::json!({}),
+             serde_json::json!(error),
+             error.to_string(),
+             $status,
+         ).into_response();
 
-         // Inserts a custom header "x-kbve-shield" into the response. The value of this header is the error message.
-         // `expect` is used here to handle any potential error while converting the error message into a header value.
-         response.headers_mut().insert(
-             axum::http::header::HeaderName::from_static("x-kbve-shield"),
-             axum::http::HeaderValue::from_str($error).expect("Invalid header value"),
-         );
-
-         // Returns the modified response.
+         // Returns the response, the "x-kbve-shield" header is already set.
          response
      }};
 }
@@ -112,17 +112,10 @@ macro_rules! spellbook_pool {
             Ok(conn) => conn,
 
             // If there's an error (e.g., the pool is exhausted or connection failed),
-            // the macro returns an HTTP response indicating an internal server error.
+            // the macro returns a 503 `db_unavailable` response through `KbveError`.
             // This return statement is designed to exit from the calling function.
-            Err(_) => return (
-                // Sets the HTTP status code to UNAUTHORIZED (401).
-                // Although the error is from the database, the response indicates a more general server error.
-                axum::http::StatusCode::UNAUTHORIZED,
-
-                // The body of the response is a JSON object with an error message.
-                axum::Json(serde_json::json!({"error": "db_error"})),
-
-                // Converts the tuple into an Axum response type.
+            Err(e) => return $crate::error::KbveError::from(
+                $crate::db::DbError::Pool(e.to_string())
             ).into_response(),
         }
 	};
@@ -146,12 +139,14 @@ macro_rules! spellbook_complete {
 	};
 }
 
+//	Sanitizers for token claims, a claim that fails answers 401 through `KbveError`.
+
 #[macro_export]
 macro_rules! spellbook_username {
 	($username:expr) => {
         match crate::utility::sanitize_username($username) {
             Ok(username) => username,
-            Err(e) => return (axum::http::StatusCode::UNAUTHORIZED, axum::Json(serde_json::json!({"error": format!("{}",e)}))).into_response()
+            Err(_) => return $crate::error::KbveError::Unauthorized("invalid_username").into_response()
         }
 	};
 }
@@ -161,7 +156,7 @@ macro_rules! spellbook_ulid {
 	($ulid:expr) => {
         match crate::utility::sanitizie_ulid($ulid) {
             Ok(ulid) => ulid,
-            Err(e) => return (axum::http::StatusCode::UNAUTHORIZED, axum::Json(serde_json::json!({"error": format!("{}",e)}))).into_response()
+            Err(_) => return $crate::error::KbveError::Unauthorized("invalid_ulid").into_response()
         }
 	};
 }
@@ -171,7 +166,7 @@ macro_rules! spellbook_email {
 	($email:expr) => {
         match crate::utility::sanitize_email($email) {
             Ok(email) => email,
-            Err(e) => return (axum::http::StatusCode::UNAUTHORIZED, axum::Json(serde_json::json!({"error": format!("{}",e)}))).into_response()
+            Err(_) => return $crate::error::KbveError::Unauthorized("invalid_email").into_response()
         }
 	};
 }
@@ -244,18 +239,18 @@ macro_rules! spellbook_hazardous_boolean_exist_via_ulid {
         pub async fn $func_name(
             $param: $param_type,
             pool: Arc<Pool>
-        ) -> Result<bool, &'static str> {
-            let mut conn = spellbook_pool_conn!(pool);
-
-            match $table::table
-                .filter($table::$column.eq($param))
-                .select($table::ulid)
-                .first::<Vec<u8>>(&mut conn)
-            {
-                Ok(_) => Ok(true),
-                Err(diesel::NotFound) => Ok(false),
-                Err(_) => Err("db_error"),
-            }
+        ) -> Result<bool, $crate::error::KbveError> {
+            run_blocking(pool, move |conn| {
+                match $table::table
+                    .filter($table::$column.eq($param))
+                    .select($table::ulid)
+                    .first::<Vec<u8>>(conn)
+                {
+                    Ok(_) => Ok(true),
+                    Err(diesel::NotFound) => Ok(false),
+                    Err(e) => Err($crate::error::KbveError::from(e)),
+                }
+            }).await
         }
 	};
 }
@@ -275,19 +270,17 @@ macro_rules! spellbook_hazardous_task_fetch {
 		pub async fn $func_name(
 			$param: $param_type,
 			pool: Arc<Pool>
-		) -> Result<$return_type, &'static str> {
-			let mut conn = spellbook_pool_conn!(pool);
-
-			match $table::table
-				.filter($table::$param.eq($param))
-				.select($table::$column)
-				.first::<$return_type>(&mut conn)
-				{
-					Ok(data) => Ok(data),
-					Err(diesel::NotFound) => Err("db_error"),
-					Err(_) => Err("db_error"),
-				}
-
+		) -> Result<$return_type, $crate::error::KbveError> {
+			run_blocking(pool, move |conn| {
+				match $table::table
+					.filter($table::$param.eq($param))
+					.select($table::$column)
+					.first::<$return_type>(conn)
+					{
+						Ok(data) => Ok(data),
+						Err(e) => Err($crate::error::KbveError::from(e)),
+					}
+			}).await
 		}
 	};
 }
\ No newline at end of file
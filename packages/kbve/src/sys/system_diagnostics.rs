@@ -1,5 +1,5 @@
 use crate::runes::{ WizardResponse };
-use crate::db::Pool;
+use crate::db::{ Pool, run_blocking };
 
 use std::sync::Arc;
 
@@ -61,36 +61,24 @@ pub async fn system_database_speed_test(Extension(
 ): Extension<Arc<Pool>>) -> impl IntoResponse {
 	let start_time = Instant::now();
 
-	let query_result = task::spawn_blocking(
-		move || -> Result<(), Error> {
-			let mut conn = pool
-				.get()
-				.map_err(|e|
-					anyhow::Error::msg(
-						format!("Failed to get database connection: {}", e)
-					)
-				)?;
+	let query_result = run_blocking(pool, move |conn| -> Result<(), Error> {
+		diesel
+			::sql_query("SELECT 1")
+			.execute(conn)
+			.map_err(|e| anyhow::Error::msg(format!("Database query failed: {}", e)))?;
 
-			diesel
-				::sql_query("SELECT 1")
-				.execute(&mut conn)
-				.map_err(|e|
-					anyhow::Error::msg(format!("Database query failed: {}", e))
-				)?;
-
-			Ok(())
-		}
-	).await;
+		Ok(())
+	}).await;
 
 	match query_result {
-		Ok(Ok(_)) => {
+		Ok(_) => {
 			let elapsed_time = start_time.elapsed().as_millis() as u64;
 			(WizardResponse {
 				data: json!({"status": "success"}),
 				message: json!({"time_ms": elapsed_time}),
 			}).into_response()
 		}
-		Ok(Err(e)) => {
+		Err(e) => {
 			let error_message = format!("Error: {}", e);
 			let error_response = WizardResponse {
 				data: json!({"status": "error"}),
@@ -101,16 +89,5 @@ pub async fn system_database_speed_test(Extension(
 				Json(error_response),
 			).into_response()
 		}
-		Err(_) => {
-			let error_message = "Internal server error: Task failed to execute";
-			let error_response = WizardResponse {
-				data: json!({"status": "error"}),
-				message: json!({ "error": error_message }),
-			};
-			(
-				StatusCode::INTERNAL_SERVER_ERROR,
-				Json(error_response),
-			).into_response()
-		}
 	}
 }
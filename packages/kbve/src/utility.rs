@@ -35,10 +35,12 @@ use tokio::task;
 
 use ulid::Ulid;
 
-use crate::runes::{ WizardResponse };
-use crate::db::Pool;
+use rand_core::{ OsRng, RngCore };
+use sha2::{ Digest, Sha256 };
 
-use crate::{ spellbook_pool_conn}; 
+use crate::runes::{ WizardResponse };
+use crate::db::{ Pool, run_blocking };
+use crate::error::{ KbveError };
 
 use crate::schema::{ globals };
 
@@ -230,30 +232,29 @@ pub fn extract_unsplash_photo_id(url: &str) -> Option<String> {
 
 pub async fn global_map_init(
 	pool: Arc<Pool>
-) -> Result<DashMap<String, String>, &'static str> {
-	let mut conn = spellbook_pool_conn!(pool);
-
-	let map = DashMap::new();
-
-	match
-		globals::table
-			.select((globals::key, globals::value))
-			.load::<(String, String)>(&mut conn)
-	{
-		Ok(results) => {
-			if results.is_empty() {
-				Err("empty_case")
-			} else {
-				for (key, value) in results {
-					println!("key {} inserted", key.to_string());
-					map.insert(key, value);
+) -> Result<DashMap<String, String>, KbveError> {
+	run_blocking(pool, move |conn| {
+		let map = DashMap::new();
+
+		match
+			globals::table
+				.select((globals::key, globals::value))
+				.load::<(String, String)>(conn)
+		{
+			Ok(results) => {
+				if results.is_empty() {
+					Err(KbveError::NotFound("empty_case"))
+				} else {
+					for (key, value) in results {
+						println!("key {} inserted", key.to_string());
+						map.insert(key, value);
+					}
+					Ok(map)
 				}
-				Ok(map)
 			}
+			Err(e) => Err(KbveError::from(e)),
 		}
-		Err(diesel::NotFound) => Err("not_found_error"),
-		Err(_) => { Err("database_error") }
-	}
+	}).await
 }
 
 //			?[ULIDS]
@@ -293,6 +294,20 @@ pub fn convert_ulid_bytes_to_string(ulid_bytes: &[u8]) -> Result<String, String>
     Ok(ulid.to_string())
 }
 
+//			?[TOKENS]
+
+//	Random single-use secret, hex encoded, for reset / verification links.
+pub fn generate_secure_token() -> String {
+	let mut bytes = [0u8; 32];
+	OsRng.fill_bytes(&mut bytes);
+	hex::encode(bytes)
+}
+
+//	Only the SHA-256 digest of a token is ever stored in the database.
+pub fn hash_secure_token(token: &str) -> String {
+	hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 //?         [FALLBACK]
 pub async fn fallback(uri: Uri) -> impl IntoResponse {
 	let final_path = sanitize_path(&uri.to_string());
@@ -326,8 +341,8 @@ pub fn cors_service() -> CorsLayer {
 			AUTHORIZATION,
 			ACCEPT,
 			CONTENT_TYPE,
-			HeaderName::from_static("x-kbve-shieldwall"),
 			HeaderName::from_static("x-kbve-api"),
+			HeaderName::from_static("x-api-key"),
 		])
 }
 
@@ -404,16 +419,14 @@ pub async fn speed_test(Extension(pool): Extension<Arc<Pool>>) -> Result<
 > {
 	let start_time = Instant::now();
 
-	// Use `block_in_place` or `spawn_blocking` for the blocking database operation
-	let query_result = task::block_in_place(|| {
-		let mut conn = pool.get().map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
-
+	let query_result = run_blocking(pool, move |conn| {
 		// Execute a simple query
 		diesel
 			::sql_query("SELECT 1")
-			.execute(&mut conn)
-			.map_err(|_| StatusCode::SERVICE_UNAVAILABLE)
-	});
+			.execute(conn)
+			.map_err(KbveError::from)
+	}).await
+		.map_err(|_: KbveError| StatusCode::SERVICE_UNAVAILABLE);
 
 	match query_result {
 		Ok(_) => {
@@ -1,20 +1,264 @@
-//      [hCaptcha]
+//      [Captcha]
 
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
 use reqwest::{ Client };
 use serde::{ Deserialize };
-use std::collections::HashMap;
+use std::time::Duration;
 
-// A struct to deserialize the hCaptcha response
+//	Checks the captcha token sent with a form, swapped through `KbveState::with_captcha`.
+//	`Ok(false)` is a rejected token, `Err` means the provider could not be asked.
+#[async_trait]
+pub trait CaptchaVerifier: Send + Sync {
+	async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<bool, String>;
+}
+
+pub const CAPTCHA_HTTP_TIMEOUT_SECONDS: u64 = 10;
+
+static CAPTCHA_HTTP: Lazy<Client> = Lazy::new(|| {
+	Client::builder()
+		.timeout(Duration::from_secs(CAPTCHA_HTTP_TIMEOUT_SECONDS))
+		.build()
+		.unwrap_or_default()
+});
+
+//	?	[SiteVerify]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+	HCaptcha,
+	Turnstile,
+	ReCaptcha,
+}
+
+impl CaptchaProvider {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.trim().to_lowercase().as_str() {
+			"hcaptcha" => Some(CaptchaProvider::HCaptcha),
+			"turnstile" => Some(CaptchaProvider::Turnstile),
+			"recaptcha" => Some(CaptchaProvider::ReCaptcha),
+			_ => None,
+		}
+	}
+
+	pub fn verify_url(&self) -> &'static str {
+		match self {
+			CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+			CaptchaProvider::Turnstile =>
+				"https://challenges.cloudflare.com/turnstile/v0/siteverify",
+			CaptchaProvider::ReCaptcha => "https://www.google.com/recaptcha/api/siteverify",
+		}
+	}
+}
+
+// The siteverify answer, shared by hCaptcha, Turnstile and reCAPTCHA.
 #[derive(Deserialize)]
-struct HCaptchaResponse {
+struct SiteVerifyResponse {
 	success: bool,
-	challenge_ts: Option<String>, // Timestamp of the challenge
 	hostname: Option<String>, // Hostname of the site
-	credit: Option<bool>, // Deprecated field
+	score: Option<f64>, // reCAPTCHA v3 and hCaptcha Enterprise only
 	#[serde(rename = "error-codes")]
 	error_codes: Option<Vec<String>>, // Error codes, if any
 }
 
+/**
+	hCaptcha, Cloudflare Turnstile and reCAPTCHA all speak the same siteverify protocol:
+	a form POST of `secret`, `response` and `remoteip`, answered with `success`, `hostname`
+	and, for the scoring products, `score`.
+
+	With `hostnames` set the solved challenge must come from one of them. With `min_score`
+	set a reported score must reach it, providers that report no score are not held to it.
+**/
+#[derive(Debug, Clone)]
+pub struct SiteVerifyCaptcha {
+	pub provider: CaptchaProvider,
+	pub secret: String,
+	pub verify_url: String,
+	pub hostnames: Vec<String>,
+	pub min_score: Option<f64>,
+}
+
+impl SiteVerifyCaptcha {
+	pub fn new(provider: CaptchaProvider, secret: impl Into<String>) -> Self {
+		SiteVerifyCaptcha {
+			provider,
+			secret: secret.into(),
+			verify_url: provider.verify_url().to_string(),
+			hostnames: Vec::new(),
+			min_score: None,
+		}
+	}
+
+	pub fn hcaptcha(secret: impl Into<String>) -> Self {
+		Self::new(CaptchaProvider::HCaptcha, secret)
+	}
+
+	pub fn turnstile(secret: impl Into<String>) -> Self {
+		Self::new(CaptchaProvider::Turnstile, secret)
+	}
+
+	pub fn recaptcha(secret: impl Into<String>) -> Self {
+		Self::new(CaptchaProvider::ReCaptcha, secret)
+	}
+
+	pub fn with_verify_url(mut self, verify_url: impl Into<String>) -> Self {
+		self.verify_url = verify_url.into();
+		self
+	}
+
+	pub fn with_hostnames(mut self, hostnames: Vec<String>) -> Self {
+		self.hostnames = hostnames;
+		self
+	}
+
+	pub fn with_min_score(mut self, min_score: f64) -> Self {
+		self.min_score = Some(min_score);
+		self
+	}
+
+	fn accepts(&self, response: &SiteVerifyResponse) -> bool {
+		if !response.success {
+			tracing::debug!(
+				provider = ?self.provider,
+				"[Captcha] rejected: {:?}",
+				response.error_codes
+			);
+			return false;
+		}
+
+		if !self.hostnames.is_empty() {
+			let hostname_allowed = response.hostname
+				.as_deref()
+				.map(|hostname| self.hostnames.iter().any(|allowed| allowed == hostname))
+				.unwrap_or(false);
+
+			if !hostname_allowed {
+				return false;
+			}
+		}
+
+		match (self.min_score, response.score) {
+			(Some(min_score), Some(score)) => score >= min_score,
+			_ => true,
+		}
+	}
+}
+
+#[async_trait]
+impl CaptchaVerifier for SiteVerifyCaptcha {
+	async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<bool, String> {
+		if token.trim().is_empty() {
+			return Ok(false);
+		}
+
+		let mut params = vec![("response", token), ("secret", self.secret.as_str())];
+		if let Some(remote_ip) = remote_ip.filter(|ip| !ip.is_empty()) {
+			params.push(("remoteip", remote_ip));
+		}
+
+		let res = CAPTCHA_HTTP.post(&self.verify_url)
+			.form(&params)
+			.send().await
+			.map_err(|e| format!("Failed to reach captcha provider: {}", e))?;
+
+		if !res.status().is_success() {
+			return Err(format!("Captcha provider answered {}", res.status()));
+		}
+
+		let response: SiteVerifyResponse = res
+			.json().await
+			.map_err(|e| format!("Invalid captcha response: {}", e))?;
+
+		Ok(self.accepts(&response))
+	}
+}
+
+//	?	[Globals]
+
+/**
+	The default verifier, configured from the `globals` table at verification time:
+	`captcha_provider` (`hcaptcha`, `turnstile` or `recaptcha`, defaults to `hcaptcha`),
+	`captcha_secret` (falls back to the older `hcaptcha` key), `captcha_verify_url`,
+	`captcha_hostnames` (comma separated) and `captcha_min_score`.
+
+	There is deliberately no config value that skips the check, the deterministic verifiers
+	are only reachable in code through `KbveState::with_captcha`.
+**/
+pub struct GlobalCaptchaVerifier;
+
+fn captcha_global(key: &str) -> Option<String> {
+	crate::config::kbve_config().and_then(|config| config.get(key).map(String::from))
+}
+
+#[async_trait]
+impl CaptchaVerifier for GlobalCaptchaVerifier {
+	async fn verify(&self, token: &str, remote_ip: Option<&str>) -> Result<bool, String> {
+		let provider_name = captcha_global("captcha_provider").unwrap_or_else(||
+			"hcaptcha".to_string()
+		);
+
+		let provider = CaptchaProvider::from_name(&provider_name).ok_or_else(||
+			format!("Unknown captcha provider {}", provider_name)
+		)?;
+
+		let secret = captcha_global("captcha_secret")
+			.or_else(|| captcha_global("hcaptcha"))
+			.ok_or_else(|| "missing_captcha".to_string())?;
+
+		let mut verifier = SiteVerifyCaptcha::new(provider, secret);
+
+		if let Some(verify_url) = captcha_global("captcha_verify_url") {
+			verifier = verifier.with_verify_url(verify_url);
+		}
+
+		if let Some(hostnames) = captcha_global("captcha_hostnames") {
+			verifier = verifier.with_hostnames(
+				hostnames
+					.split(',')
+					.map(|hostname| hostname.trim().to_string())
+					.filter(|hostname| !hostname.is_empty())
+					.collect()
+			);
+		}
+
+		if let Some(min_score) = captcha_global("captcha_min_score") {
+			let min_score = min_score
+				.parse::<f64>()
+				.map_err(|_| format!("Invalid captcha_min_score {}", min_score))?;
+			verifier = verifier.with_min_score(min_score);
+		}
+
+		verifier.verify(token, remote_ip).await
+	}
+}
+
+//	?	[Static]
+
+//	Deterministic verifier for tests, never touches the network. Only installed through
+//	`KbveState::with_captcha`, no config value selects it.
+#[derive(Debug, Clone, Copy)]
+pub struct StaticCaptchaVerifier {
+	pub pass: bool,
+}
+
+impl StaticCaptchaVerifier {
+	pub fn pass() -> Self {
+		StaticCaptchaVerifier { pass: true }
+	}
+
+	pub fn fail() -> Self {
+		StaticCaptchaVerifier { pass: false }
+	}
+}
+
+#[async_trait]
+impl CaptchaVerifier for StaticCaptchaVerifier {
+	async fn verify(&self, _token: &str, _remote_ip: Option<&str>) -> Result<bool, String> {
+		Ok(self.pass)
+	}
+}
+
+//	Kept for existing callers, checks the token against hCaptcha with the `hcaptcha` global.
 pub async fn verify_token_via_hcaptcha(
 	captcha_token: &str
 ) -> Result<bool, Box<dyn std::error::Error>> {
@@ -23,50 +267,101 @@ pub async fn verify_token_via_hcaptcha(
 		return Err("Captcha token is empty".into());
 	}
 
-	// Retrieve the secret key from GLOBAL Map
-	// TODO Migrate out the GLOBAL Map to AtomicCells ?!
-	let secret_key = match crate::runes::GLOBAL.get() {
-		Some(global_map) =>
-			match global_map.get("hcaptcha") {
-				Some(value) => value.value().clone(),
-				None => {
-					return Err("missing_captcha".into());
-				}
-			}
-		None => {
-			return Err("invalid_global_map".into());
-		}
-	};
-
-	// Set up the HTTP client
-	let client = Client::new();
-	let mut params = HashMap::new();
-	params.insert("response", captcha_token);
-	params.insert("secret", secret_key.as_str());
-
-	// Send the request to hCaptcha
-	let res = client
-		.post("https://api.hcaptcha.com/siteverify")
-		.form(&params)
-		.send().await
-		.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-
-	// Check the response status
-	if res.status().is_success() {
-		let captcha_response: HCaptchaResponse = res
-			.json().await
-			.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-		if captcha_response.success {
-			Ok(true)
-		} else {
-			let error_message = format!(
-				"Captcha verification failed: {:?}",
-				captcha_response.error_codes
-			);
-			Err(error_message.into())
+	let secret_key = captcha_global("hcaptcha").ok_or("missing_captcha")?;
+
+	match SiteVerifyCaptcha::hcaptcha(secret_key).verify(captcha_token, None).await? {
+		true => Ok(true),
+		false => Err("Captcha verification failed".into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use axum::{ routing::post, Form, Json, Router };
+	use std::collections::HashMap;
+	use std::net::{ SocketAddr, TcpListener };
+
+	//	Local siteverify endpoint, `ok-token` passes from `kbve.com` with a 0.9 score.
+	fn mock_siteverify() -> String {
+		async fn siteverify(Form(form): Form<HashMap<String, String>>) -> Json<serde_json::Value> {
+			let success =
+				form.get("secret").map(String::as_str) == Some("test-secret") &&
+				form.get("response").map(String::as_str) == Some("ok-token");
+
+			Json(
+				serde_json::json!({
+					"success": success,
+					"hostname": "kbve.com",
+					"score": 0.9,
+					"remoteip": form.get("remoteip"),
+				})
+			)
 		}
-	} else {
-		// Handle non-successful response statuses
-		Err("Failed to verify captcha".into())
+
+		let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+		let url = format!("http://{}/siteverify", listener.local_addr().unwrap());
+		let app = Router::new().route("/siteverify", post(siteverify));
+
+		tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+
+		url
+	}
+
+	#[tokio::test]
+	async fn static_verifiers_are_deterministic() {
+		assert_eq!(StaticCaptchaVerifier::pass().verify("", None).await, Ok(true));
+		assert_eq!(StaticCaptchaVerifier::fail().verify("ok-token", None).await, Ok(false));
+	}
+
+	#[test]
+	fn only_real_providers_are_named() {
+		assert_eq!(CaptchaProvider::from_name(" Turnstile "), Some(CaptchaProvider::Turnstile));
+		assert_eq!(CaptchaProvider::from_name("pass"), None);
+		assert_eq!(CaptchaProvider::from_name("fail"), None);
+	}
+
+	#[tokio::test]
+	async fn global_verifier_refuses_without_a_secret() {
+		assert!(GlobalCaptchaVerifier.verify("ok-token", Some("203.0.113.7")).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn siteverify_accepts_a_solved_token() {
+		let verifier = SiteVerifyCaptcha::hcaptcha("test-secret").with_verify_url(mock_siteverify());
+
+		assert_eq!(verifier.verify("ok-token", Some("203.0.113.7")).await, Ok(true));
+		assert_eq!(verifier.verify("bad-token", None).await, Ok(false));
+		assert_eq!(verifier.verify("  ", None).await, Ok(false));
+	}
+
+	#[tokio::test]
+	async fn siteverify_checks_hostname_and_score() {
+		let url = mock_siteverify();
+
+		let other_host = SiteVerifyCaptcha::turnstile("test-secret")
+			.with_verify_url(&url)
+			.with_hostnames(vec!["example.com".to_string()]);
+		assert_eq!(other_host.verify("ok-token", None).await, Ok(false));
+
+		let same_host = SiteVerifyCaptcha::turnstile("test-secret")
+			.with_verify_url(&url)
+			.with_hostnames(vec!["kbve.com".to_string()]);
+		assert_eq!(same_host.verify("ok-token", None).await, Ok(true));
+
+		let high_score = SiteVerifyCaptcha::recaptcha("test-secret")
+			.with_verify_url(&url)
+			.with_min_score(0.95);
+		assert_eq!(high_score.verify("ok-token", None).await, Ok(false));
+	}
+
+	#[tokio::test]
+	async fn siteverify_reports_an_unreachable_provider() {
+		let verifier = SiteVerifyCaptcha::hcaptcha("test-secret").with_verify_url(
+			"http://127.0.0.1:9/siteverify"
+		);
+
+		assert!(verifier.verify("ok-token", None).await.is_err());
 	}
 }
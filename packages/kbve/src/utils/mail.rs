@@ -0,0 +1,362 @@
+//      [Mail]
+
+use async_trait::async_trait;
+use lettre::{
+	message::{ header::ContentType, Mailbox },
+	transport::smtp::authentication::Credentials,
+	AsyncSmtpTransport,
+	AsyncTransport,
+	Message,
+	Tokio1Executor,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{ Serialize, Deserialize };
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{ KbveConfig };
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MailMessage {
+	pub to: String,
+	pub subject: String,
+	pub body: String,
+}
+
+//	Delivery backend for outgoing mail, swapped through `KbveState::with_mailer`.
+#[async_trait]
+pub trait MailSender: Send + Sync {
+	async fn send(&self, message: MailMessage) -> Result<(), String>;
+}
+
+pub const MAIL_REDACTED: &str = "[redacted]";
+
+//	Long enough to be a reset or verification token, short enough to leave words alone.
+static MAIL_TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9_\-]{16,}").unwrap());
+
+//	The body with anything token shaped, bare or inside a link, replaced by `MAIL_REDACTED`.
+pub fn mail_redact_tokens(body: &str) -> String {
+	MAIL_TOKEN_REGEX.replace_all(body, MAIL_REDACTED).into_owned()
+}
+
+//	Writes the message to the tracing log, the default when no transport is configured.
+//	Nothing is delivered, and the tokens are redacted so the log can not be used to take over
+//	an account, use `FileMailSender` to read them during local development.
+pub struct LogMailSender;
+
+#[async_trait]
+impl MailSender for LogMailSender {
+	async fn send(&self, message: MailMessage) -> Result<(), String> {
+		tracing::info!(
+			to = %message.to,
+			subject = %message.subject,
+			"[Mail] {}",
+			mail_redact_tokens(&message.body)
+		);
+		Ok(())
+	}
+}
+
+//	Appends each message as a JSON line to a file, useful for tests and local development.
+pub struct FileMailSender {
+	pub path: PathBuf,
+}
+
+impl FileMailSender {
+	pub fn new(path: impl Into<PathBuf>) -> Self {
+		FileMailSender { path: path.into() }
+	}
+}
+
+#[async_trait]
+impl MailSender for FileMailSender {
+	async fn send(&self, message: MailMessage) -> Result<(), String> {
+		let mut line = serde_json
+			::to_string(&message)
+			.map_err(|e| format!("Failed to serialize mail: {}", e))?;
+		line.push('\n');
+
+		let mut file = tokio::fs::OpenOptions
+			::new()
+			.create(true)
+			.append(true)
+			.open(&self.path).await
+			.map_err(|e| format!("Failed to open mail file: {}", e))?;
+
+		file
+			.write_all(line.as_bytes()).await
+			.map_err(|e| format!("Failed to write mail file: {}", e))
+	}
+}
+
+//	?	[SMTP]
+
+pub const MAIL_SMTP_PORT_DEFAULT: u16 = 587;
+pub const MAIL_SMTP_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+	//	Plain connection upgraded with STARTTLS, required to succeed.
+	StartTls,
+	//	TLS from the first byte, usually port 465.
+	Tls,
+	//	No encryption at all, only for a relay on localhost or inside the cluster.
+	None,
+}
+
+impl SmtpSecurity {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.trim().to_lowercase().as_str() {
+			"starttls" => Some(SmtpSecurity::StartTls),
+			"tls" => Some(SmtpSecurity::Tls),
+			"none" => Some(SmtpSecurity::None),
+			_ => None,
+		}
+	}
+}
+
+//	Delivers through an SMTP relay, each message is sent as plain text from `from`.
+pub struct SmtpMailSender {
+	transport: AsyncSmtpTransport<Tokio1Executor>,
+	from: Mailbox,
+}
+
+impl SmtpMailSender {
+	pub fn new(
+		host: &str,
+		port: u16,
+		security: SmtpSecurity,
+		credentials: Option<(String, String)>,
+		from: &str
+	) -> Result<Self, String> {
+		let from = from
+			.parse::<Mailbox>()
+			.map_err(|e| format!("Invalid mail_from {}: {}", from, e))?;
+
+		let builder = match security {
+			SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+			SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+			SmtpSecurity::None =>
+				Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+		}.map_err(|e| format!("Invalid mail_smtp_host {}: {}", host, e))?;
+
+		let mut builder = builder
+			.port(port)
+			.timeout(Some(Duration::from_secs(MAIL_SMTP_TIMEOUT_SECONDS)));
+
+		if let Some((username, password)) = credentials {
+			builder = builder.credentials(Credentials::new(username, password));
+		}
+
+		Ok(SmtpMailSender { transport: builder.build(), from })
+	}
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+	async fn send(&self, message: MailMessage) -> Result<(), String> {
+		let to = message.to
+			.parse::<Mailbox>()
+			.map_err(|e| format!("Invalid recipient: {}", e))?;
+
+		let email = Message::builder()
+			.from(self.from.clone())
+			.to(to)
+			.subject(message.subject)
+			.header(ContentType::TEXT_PLAIN)
+			.body(message.body)
+			.map_err(|e| format!("Failed to build mail: {}", e))?;
+
+		self.transport
+			.send(email).await
+			.map(|_| ())
+			.map_err(|e| format!("SMTP delivery failed: {}", e))
+	}
+}
+
+//	?	[Config]
+
+/**
+	Picks the sender named by `mail_transport`, read once at startup.
+
+	`smtp` needs `mail_smtp_host` and `mail_from`, with optional `mail_smtp_port` (587),
+	`mail_smtp_security` (`starttls`, `tls` or `none`) and `mail_smtp_username` /
+	`mail_smtp_password`. `file` writes to `mail_file_path` and `log` only logs, both deliver
+	nothing and are refused unless `environment` is `dev` or `test`, as is a missing transport.
+**/
+pub fn mail_sender_from_config(config: &KbveConfig) -> Result<Arc<dyn MailSender>, String> {
+	let development = matches!(
+		config.get("environment").map(|environment| environment.to_lowercase()).as_deref(),
+		Some("dev" | "development" | "test")
+	);
+
+	let transport = config.get("mail_transport").map(|transport| transport.to_lowercase());
+
+	match transport.as_deref() {
+		Some("smtp") => {
+			let host = config.get("mail_smtp_host").ok_or("mail_transport smtp needs mail_smtp_host")?;
+			let from = config.get("mail_from").ok_or("mail_transport smtp needs mail_from")?;
+
+			let port = match config.get("mail_smtp_port") {
+				Some(port) =>
+					port.parse::<u16>().map_err(|_| format!("Invalid mail_smtp_port {}", port))?,
+				None => MAIL_SMTP_PORT_DEFAULT,
+			};
+
+			let security = match config.get("mail_smtp_security") {
+				Some(name) =>
+					SmtpSecurity::from_name(name).ok_or_else(||
+						format!("Invalid mail_smtp_security {}", name)
+					)?,
+				None => SmtpSecurity::StartTls,
+			};
+
+			let credentials = match (config.get("mail_smtp_username"), config.get("mail_smtp_password")) {
+				(Some(username), Some(password)) => Some((username.to_string(), password.to_string())),
+				(None, None) => None,
+				_ => {
+					return Err("mail_smtp_username and mail_smtp_password go together".to_string());
+				}
+			};
+
+			Ok(Arc::new(SmtpMailSender::new(host, port, security, credentials, from)?))
+		}
+		Some("file") if development => {
+			let path = config.get("mail_file_path").ok_or("mail_transport file needs mail_file_path")?;
+			Ok(Arc::new(FileMailSender::new(path)))
+		}
+		Some("log") | None if development => Ok(Arc::new(LogMailSender)),
+		Some("file" | "log") | None =>
+			Err(
+				"No mail transport configured, set mail_transport to smtp, or environment to dev or test".to_string()
+			),
+		Some(other) => Err(format!("Unknown mail_transport {}", other)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn message(body: &str) -> MailMessage {
+		MailMessage {
+			to: "player@kbve.com".to_string(),
+			subject: "Password reset".to_string(),
+			body: body.to_string(),
+		}
+	}
+
+	#[test]
+	fn redacts_bare_tokens_and_links() {
+		let token = crate::utility::generate_secure_token();
+		let body = format!(
+			"Your reset token is {} and it expires in 30 minutes.\n\nhttps://kbve.com/reset?token={}",
+			token,
+			token
+		);
+
+		let redacted = mail_redact_tokens(&body);
+
+		assert!(!redacted.contains(&token));
+		assert_eq!(redacted.matches(MAIL_REDACTED).count(), 2);
+		assert!(redacted.starts_with("Your reset token is [redacted] and it expires in 30 minutes."));
+	}
+
+	#[test]
+	fn leaves_plain_text_alone() {
+		let body = "Welcome! Your email verification token expires in 24 hours.";
+		assert_eq!(mail_redact_tokens(body), body);
+	}
+
+	#[tokio::test]
+	async fn file_sender_appends_json_lines() {
+		let path = std::env::temp_dir().join(
+			format!("kbve-mail-{}.jsonl", crate::utility::generate_secure_token())
+		);
+		let sender = FileMailSender::new(&path);
+
+		sender.send(message("first")).await.unwrap();
+		sender.send(message("second")).await.unwrap();
+
+		let written = tokio::fs::read_to_string(&path).await.unwrap();
+		let _ = tokio::fs::remove_file(&path).await;
+
+		let lines: Vec<MailMessage> = written
+			.lines()
+			.map(|line| serde_json::from_str(line).unwrap())
+			.collect();
+
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0].to, "player@kbve.com");
+		assert_eq!(lines[0].body, "first");
+		assert_eq!(lines[1].body, "second");
+	}
+
+	#[tokio::test]
+	async fn file_sender_reports_an_unwritable_path() {
+		let sender = FileMailSender::new(std::env::temp_dir().join("kbve-missing-dir/mail.jsonl"));
+		assert!(sender.send(message("lost")).await.is_err());
+	}
+
+	fn config(values: &[(&str, &str)]) -> KbveConfig {
+		let mut source: std::collections::BTreeMap<String, String> = values
+			.iter()
+			.map(|(key, value)| (key.to_string(), value.to_string()))
+			.collect();
+		source.insert("jwt_secret".to_string(), "secret".to_string());
+
+		KbveConfig::from_sources(&[source]).unwrap()
+	}
+
+	#[test]
+	fn refuses_to_run_without_a_transport_outside_dev() {
+		assert!(mail_sender_from_config(&config(&[])).is_err());
+		assert!(mail_sender_from_config(&config(&[("mail_transport", "log")])).is_err());
+		assert!(
+			mail_sender_from_config(
+				&config(&[("mail_transport", "file"), ("mail_file_path", "/tmp/mail.jsonl")])
+			).is_err()
+		);
+		assert!(
+			mail_sender_from_config(&config(&[("environment", "production")])).is_err()
+		);
+	}
+
+	#[test]
+	fn dev_and_test_fall_back_to_the_log() {
+		assert!(mail_sender_from_config(&config(&[("environment", "dev")])).is_ok());
+		assert!(mail_sender_from_config(&config(&[("environment", "TEST")])).is_ok());
+		assert!(
+			mail_sender_from_config(
+				&config(&[("environment", "dev"), ("mail_transport", "file")])
+			).is_err()
+		);
+	}
+
+	#[test]
+	fn smtp_needs_a_host_and_a_sender() {
+		let smtp = [
+			("mail_transport", "smtp"),
+			("mail_smtp_host", "smtp.kbve.com"),
+			("mail_from", "KBVE <noreply@kbve.com>"),
+		];
+		assert!(mail_sender_from_config(&config(&smtp)).is_ok());
+		assert!(mail_sender_from_config(&config(&smtp[..2])).is_err());
+		assert!(mail_sender_from_config(&config(&[smtp[0], smtp[2]])).is_err());
+
+		let mut invalid = smtp.to_vec();
+		invalid.push(("mail_smtp_port", "smtp"));
+		assert!(mail_sender_from_config(&config(&invalid)).is_err());
+
+		let mut half_credentials = smtp.to_vec();
+		half_credentials.push(("mail_smtp_username", "kbve"));
+		assert!(mail_sender_from_config(&config(&half_credentials)).is_err());
+
+		assert!(
+			mail_sender_from_config(&config(&[("mail_transport", "carrier-pigeon")])).is_err()
+		);
+	}
+}
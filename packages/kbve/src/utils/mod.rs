@@ -1,5 +1,9 @@
 pub mod captcha;
 pub mod sanitization;
+pub mod mail;
+pub mod totp;
 
 pub use captcha::*;
 pub use sanitization::*;
+pub use mail::*;
+pub use totp::*;
@@ -0,0 +1,246 @@
+//      [TOTP]
+//      RFC 6238 time-based one-time passwords over RFC 4226 HOTP (HMAC-SHA1, 6 digits, 30s step).
+
+use hmac::{ Hmac, Mac };
+use sha1::Sha1;
+use rand_core::{ OsRng, RngCore };
+
+type HmacSha1 = Hmac<Sha1>;
+
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+//	Accepted clock drift, in steps, on either side of the current one.
+pub const TOTP_SKEW_STEPS: u64 = 1;
+
+const TOTP_SECRET_ALPHABET: base32::Alphabet = base32::Alphabet::RFC4648 {
+	padding: false,
+};
+
+//	160-bit shared secret, base32 encoded as authenticator apps expect.
+pub fn generate_totp_secret() -> String {
+	let mut bytes = [0u8; 20];
+	OsRng.fill_bytes(&mut bytes);
+	base32::encode(TOTP_SECRET_ALPHABET, &bytes)
+}
+
+pub fn decode_totp_secret(secret: &str) -> Option<Vec<u8>> {
+	base32::decode(TOTP_SECRET_ALPHABET, &secret.trim().to_uppercase())
+}
+
+pub fn hotp_code(secret: &[u8], counter: u64) -> Option<u32> {
+	let mut mac = HmacSha1::new_from_slice(secret).ok()?;
+	mac.update(&counter.to_be_bytes());
+	let digest = mac.finalize().into_bytes();
+
+	// Dynamic truncation, RFC 4226 section 5.3
+	let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+	let binary =
+		(((digest[offset] & 0x7f) as u32) << 24) |
+		((digest[offset + 1] as u32) << 16) |
+		((digest[offset + 2] as u32) << 8) |
+		(digest[offset + 3] as u32);
+
+	Some(binary % (10u32).pow(TOTP_DIGITS))
+}
+
+pub fn totp_code_at(secret: &[u8], unix_time: u64) -> Option<u32> {
+	hotp_code(secret, unix_time / TOTP_STEP_SECONDS)
+}
+
+//	The time step `code` matches around `unix_time`. Only steps after `last_step` count, so a code
+//	that was already accepted can not be replayed while it is still inside the skew window.
+pub fn verify_totp_step_at(
+	secret: &str,
+	code: &str,
+	unix_time: u64,
+	last_step: u64
+) -> Option<u64> {
+	let code = code.trim();
+
+	if code.len() != (TOTP_DIGITS as usize) || !code.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+
+	let (Some(secret), Ok(code)) = (decode_totp_secret(secret), code.parse::<u32>()) else {
+		return None;
+	};
+
+	let counter = unix_time / TOTP_STEP_SECONDS;
+
+	(counter.saturating_sub(TOTP_SKEW_STEPS)..=counter + TOTP_SKEW_STEPS)
+		.filter(|step| *step > last_step)
+		.find(|step| hotp_code(&secret, *step) == Some(code))
+}
+
+pub fn verify_totp_step(secret: &str, code: &str, last_step: u64) -> Option<u64> {
+	verify_totp_step_at(secret, code, chrono::Utc::now().timestamp() as u64, last_step)
+}
+
+pub fn verify_totp_at(secret: &str, code: &str, unix_time: u64) -> bool {
+	verify_totp_step_at(secret, code, unix_time, 0).is_some()
+}
+
+pub fn verify_totp(secret: &str, code: &str) -> bool {
+	verify_totp_at(secret, code, chrono::Utc::now().timestamp() as u64)
+}
+
+//	Key URI for QR enrolment, see https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+pub fn totp_otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+	format!(
+		"otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+		totp_uri_encode(issuer),
+		totp_uri_encode(account),
+		secret,
+		totp_uri_encode(issuer),
+		TOTP_DIGITS,
+		TOTP_STEP_SECONDS
+	)
+}
+
+fn totp_uri_encode(input: &str) -> String {
+	input
+		.bytes()
+		.map(|b| {
+			if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+				(b as char).to_string()
+			} else {
+				format!("%{:02X}", b)
+			}
+		})
+		.collect()
+}
+
+//	Recovery codes are shown once as `xxxxx-xxxxx` and only their SHA-256 digests are stored.
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+	(0..count)
+		.map(|_| {
+			let mut bytes = [0u8; 5];
+			OsRng.fill_bytes(&mut bytes);
+			let code = hex::encode(bytes);
+			format!("{}-{}", &code[..5], &code[5..])
+		})
+		.collect()
+}
+
+pub fn normalize_recovery_code(code: &str) -> String {
+	code.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::authentication::{ two_factor_check, TwoFactorCheck, TWO_FACTOR_DISABLED };
+
+	//	RFC 6238 Appendix B SHA1 seed "12345678901234567890", base32 encoded.
+	const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+	//	Appendix B lists 8 digit codes, the 6 digit ones are their last six digits.
+	const RFC_VECTORS: &[(u64, &str)] = &[
+		(59, "287082"),
+		(1111111109, "081804"),
+		(1111111111, "050471"),
+		(1234567890, "005924"),
+		(2000000000, "279037"),
+		(20000000000, "353130"),
+	];
+
+	#[test]
+	fn hotp_matches_rfc4226_vectors() {
+		let expected = [
+			755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+		];
+
+		for (counter, code) in expected.iter().enumerate() {
+			assert_eq!(hotp_code(b"12345678901234567890", counter as u64), Some(*code));
+		}
+	}
+
+	#[test]
+	fn totp_matches_rfc6238_vectors() {
+		let secret = decode_totp_secret(RFC_SECRET).unwrap();
+
+		for (unix_time, code) in RFC_VECTORS {
+			assert_eq!(format!("{:06}", totp_code_at(&secret, *unix_time).unwrap()), *code);
+			assert!(verify_totp_at(RFC_SECRET, code, *unix_time));
+		}
+	}
+
+	#[test]
+	fn verify_accepts_one_step_of_skew() {
+		assert!(verify_totp_at(RFC_SECRET, "081804", 1111111109 + TOTP_STEP_SECONDS));
+		assert!(verify_totp_at(RFC_SECRET, "081804", 1111111109 - TOTP_STEP_SECONDS));
+		assert!(!verify_totp_at(RFC_SECRET, "081804", 1111111109 + 3 * TOTP_STEP_SECONDS));
+	}
+
+	#[test]
+	fn verify_refuses_steps_at_or_below_the_last_one() {
+		let step = verify_totp_step_at(RFC_SECRET, "050471", 1111111111, 0).unwrap();
+		assert_eq!(step, 1111111111 / TOTP_STEP_SECONDS);
+
+		assert_eq!(verify_totp_step_at(RFC_SECRET, "050471", 1111111111, step), None);
+		assert_eq!(verify_totp_step_at(RFC_SECRET, "050471", 1111111111, step + 1), None);
+		assert_eq!(verify_totp_step_at(RFC_SECRET, "050471", 1111111111, step - 1), Some(step));
+	}
+
+	#[test]
+	fn verify_refuses_malformed_codes() {
+		for code in ["", "28708", "2870820", "28708a", "+87082", "287 82"] {
+			assert!(!verify_totp_at(RFC_SECRET, code, 59), "{:?}", code);
+		}
+
+		assert!(verify_totp_at(RFC_SECRET, " 287082 ", 59));
+		assert!(!verify_totp_at("not base32!", "287082", 59));
+	}
+
+	#[test]
+	fn generated_secret_round_trips() {
+		let secret = generate_totp_secret();
+
+		assert_eq!(decode_totp_secret(&secret).map(|bytes| bytes.len()), Some(20));
+		assert_ne!(secret, generate_totp_secret());
+	}
+
+	#[test]
+	fn recovery_codes_are_formatted_and_unique() {
+		let codes = generate_recovery_codes(10);
+
+		assert_eq!(codes.len(), 10);
+		for code in &codes {
+			assert_eq!(code.len(), 11);
+			assert_eq!(&code[5..6], "-");
+			assert!(code.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+			assert_eq!(normalize_recovery_code(&format!(" {} ", code.to_uppercase())), *code);
+		}
+
+		let mut unique = codes.clone();
+		unique.sort();
+		unique.dedup();
+		assert_eq!(unique.len(), codes.len());
+	}
+
+	#[test]
+	fn recovery_code_is_used_once() {
+		let codes = generate_recovery_codes(2);
+		let stored = codes
+			.iter()
+			.map(|code| crate::utility::hash_secure_token(code))
+			.collect::<Vec<String>>()
+			.join(",");
+
+		let remaining = match two_factor_check(RFC_SECRET, &stored, 0, &codes[0].to_uppercase()) {
+			TwoFactorCheck::Recovery(remaining) => remaining,
+			_ => panic!("recovery code refused"),
+		};
+		assert_eq!(remaining, crate::utility::hash_secure_token(&codes[1]));
+
+		assert!(matches!(two_factor_check(RFC_SECRET, &remaining, 0, &codes[0]), TwoFactorCheck::Invalid));
+
+		match two_factor_check(RFC_SECRET, &remaining, 0, &codes[1]) {
+			TwoFactorCheck::Recovery(remaining) => assert_eq!(remaining, TWO_FACTOR_DISABLED),
+			_ => panic!("recovery code refused"),
+		}
+
+		assert!(matches!(two_factor_check(RFC_SECRET, TWO_FACTOR_DISABLED, 0, "not-a-code"), TwoFactorCheck::Invalid));
+	}
+}